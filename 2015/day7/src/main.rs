@@ -8,7 +8,10 @@ use ahash::AHashSet;
 use anyhow::{self, Context};
 use lazy_static::lazy_static;
 use regex::{Captures, Regex};
+use std::env;
+use std::fs;
 use std::io::{self, BufRead};
+use toposort::{toposort, Cycle};
 
 #[derive(Debug, PartialEq)]
 enum Value {
@@ -98,6 +101,31 @@ impl TryFrom<&str> for Gate {
     }
 }
 
+/// Parse a list of forced wire values, one per line, as either `b: 19` or `b = 19`. Blank lines are skipped, and
+/// values that don't fit on a 16-bit wire are an error.
+fn parse_overrides(text: &str) -> anyhow::Result<AHashMap<String, u64>> {
+    lazy_static! {
+        static ref OVERRIDE_PATTERN: Regex = Regex::new("^(?P<id>[a-z]+) *[:=] *(?P<num>0|[1-9][0-9]*)$").unwrap();
+    }
+    let mut overrides = AHashMap::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let captures = OVERRIDE_PATTERN
+            .captures(line)
+            .ok_or_else(|| anyhow::anyhow!("Cannot parse ‘{line}’ as a wire value"))?;
+        let id = captures.name("id").unwrap().as_str();
+        let num = captures
+            .name("num")
+            .unwrap()
+            .as_str()
+            .parse::<u16>()
+            .context("This integer is too large for a wire value")?;
+        if overrides.insert(id.to_string(), u64::from(num)).is_some() {
+            anyhow::bail!("Wire {id} is given more than one value");
+        }
+    }
+    Ok(overrides)
+}
+
 #[derive(Debug)]
 struct Circuit {
     signals: AHashMap<String, Option<u64>>,
@@ -136,39 +164,72 @@ impl FromIterator<StringWrap> for anyhow::Result<Circuit> {
     }
 }
 
+impl Value {
+    /// The signal this value carries, if it's known yet.
+    fn signal(&self, signals: &AHashMap<String, Option<u64>>) -> Option<u64> {
+        match self {
+            Value::Number(num) => Some(*num),
+            Value::Identifier(id) => signals.get(id).copied().flatten(),
+        }
+    }
+}
+
+impl Gate {
+    /// The wire this gate drives.
+    fn output(&self) -> &str {
+        match self {
+            Gate::And(_, _, name)
+            | Gate::Or(_, _, name)
+            | Gate::Not(_, name)
+            | Gate::Lshift(_, _, name)
+            | Gate::Rshift(_, _, name)
+            | Gate::Identity(_, name) => name,
+        }
+    }
+
+    /// The wires this gate reads.
+    fn inputs(&self) -> Vec<&str> {
+        let values = match self {
+            Gate::And(left, right, _) | Gate::Or(left, right, _) => vec![left, right],
+            Gate::Not(value, _) | Gate::Lshift(value, _, _) | Gate::Rshift(value, _, _) | Gate::Identity(value, _) => {
+                vec![value]
+            }
+        };
+        values
+            .into_iter()
+            .filter_map(|value| match value {
+                Value::Identifier(id) => Some(id.as_str()),
+                Value::Number(_) => None,
+            })
+            .collect()
+    }
+
+    /// The signal this gate puts on its output, or `None` if any of its inputs has no signal.
+    fn evaluate(&self, signals: &AHashMap<String, Option<u64>>) -> Option<u64> {
+        match self {
+            Gate::And(left, right, _) => Some(left.signal(signals)? & right.signal(signals)?),
+            Gate::Or(left, right, _) => Some(left.signal(signals)? | right.signal(signals)?),
+            Gate::Not(value, _) => Some(value.signal(signals)? ^ 0xFFFF),
+            Gate::Lshift(value, shift_amt, _) => Some(value.signal(signals)? << *shift_amt),
+            Gate::Rshift(value, shift_amt, _) => Some(value.signal(signals)? >> *shift_amt),
+            Gate::Identity(value, _) => value.signal(signals),
+        }
+    }
+}
+
 impl Circuit {
     fn add(&mut self, gate: Gate) {
-        match &gate {
-            Gate::Identity(Value::Identifier(id), name)
-            | Gate::Not(Value::Identifier(id), name)
-            | Gate::And(Value::Number(_), Value::Identifier(id), name)
-            | Gate::And(Value::Identifier(id), Value::Number(_), name)
-            | Gate::Or(Value::Number(_), Value::Identifier(id), name)
-            | Gate::Or(Value::Identifier(id), Value::Number(_), name)
-            | Gate::Lshift(Value::Identifier(id), _, name)
-            | Gate::Rshift(Value::Identifier(id), _, name) => {
-                self.signals.insert(id.clone(), None);
-                self.signals.insert(name.clone(), None);
-            }
-            Gate::Identity(Value::Number(_), name)
-            | Gate::Not(Value::Number(_), name)
-            | Gate::And(Value::Number(_), Value::Number(_), name)
-            | Gate::Or(Value::Number(_), Value::Number(_), name)
-            | Gate::Lshift(Value::Number(_), _, name)
-            | Gate::Rshift(Value::Number(_), _, name) => {
-                self.signals.insert(name.clone(), None);
-            }
-            Gate::And(Value::Identifier(left), Value::Identifier(right), name)
-            | Gate::Or(Value::Identifier(left), Value::Identifier(right), name) => {
-                self.signals.insert(left.clone(), None);
-                self.signals.insert(right.clone(), None);
-                self.signals.insert(name.clone(), None);
-            }
+        for id in gate.inputs().into_iter().chain([gate.output()]) {
+            self.signals.insert(id.to_string(), None);
         }
         self.gates.push(gate);
     }
 
-    fn run(&mut self, initial_values: AHashMap<String, u64>) {
+    /// Settle every wire in the circuit, with the wires in `initial_values` forced to those values.
+    ///
+    /// Each wire is visited just once, after all the wires feeding it. If the wiring loops back on itself (other
+    /// than through a forced wire), that's an error.
+    fn run(&mut self, initial_values: AHashMap<String, u64>) -> Result<(), Cycle<String>> {
         for val_ref in self.signals.values_mut() {
             *val_ref = None;
         }
@@ -178,218 +239,48 @@ impl Circuit {
             self.signals.insert(key, Some(val));
         }
 
-        loop {
-            let mut changes_seen = false;
-            for g in self.gates.iter() {
-                match g {
-                    Gate::Identity(Value::Identifier(id), name) => {
-                        if self.overrides.contains(name) {
-                            continue;
-                        }
-                        if let Some(value) = self.signals[id] {
-                            let dest = self.signals.get_mut(name).unwrap();
-                            if dest.is_none() {
-                                *dest = Some(value);
-                                changes_seen = true;
-                            } else {
-                                assert_eq!(*dest, Some(value));
-                            }
-                        }
-                    }
-                    Gate::Identity(Value::Number(num), name) => {
-                        if self.overrides.contains(name) {
-                            continue;
-                        }
-                        let dest = self.signals.get_mut(name).unwrap();
-                        if dest.is_none() {
-                            *dest = Some(*num);
-                            changes_seen = true;
-                        } else {
-                            assert_eq!(*dest, Some(*num));
-                        }
-                    }
-                    Gate::Not(Value::Identifier(id), name) => {
-                        if self.overrides.contains(name) {
-                            continue;
-                        }
-                        if let Some(value) = self.signals[id] {
-                            let dest = self.signals.get_mut(name).unwrap();
-                            let result = value ^ 0xFFFF;
-                            if dest.is_none() {
-                                *dest = Some(result);
-                                changes_seen = true;
-                            } else {
-                                assert_eq!(*dest, Some(result));
-                            }
-                        }
-                    }
-                    Gate::Not(Value::Number(num), name) => {
-                        if self.overrides.contains(name) {
-                            continue;
-                        }
-                        let dest = self.signals.get_mut(name).unwrap();
-                        let result = *num ^ 0xFFFF;
-                        if dest.is_none() {
-                            *dest = Some(result);
-                            changes_seen = true;
-                        } else {
-                            assert_eq!(*dest, Some(result));
-                        }
-                    }
-                    Gate::And(Value::Identifier(left), Value::Identifier(right), name) => {
-                        if self.overrides.contains(name) {
-                            continue;
-                        }
-                        if let Some(left_value) = self.signals[left] {
-                            if let Some(right_value) = self.signals[right] {
-                                let dest = self.signals.get_mut(name).unwrap();
-                                let result = left_value & right_value;
-                                if dest.is_none() {
-                                    *dest = Some(result);
-                                    changes_seen = true;
-                                } else {
-                                    assert_eq!(*dest, Some(result));
-                                }
-                            }
-                        }
-                    }
-                    Gate::And(Value::Number(num), Value::Identifier(id), name)
-                    | Gate::And(Value::Identifier(id), Value::Number(num), name) => {
-                        if self.overrides.contains(name) {
-                            continue;
-                        }
-                        if let Some(value) = self.signals[id] {
-                            let dest = self.signals.get_mut(name).unwrap();
-                            let result = num & value;
-                            if dest.is_none() {
-                                *dest = Some(result);
-                                changes_seen = true;
-                            } else {
-                                assert_eq!(*dest, Some(result));
-                            }
-                        }
-                    }
-                    Gate::And(Value::Number(left), Value::Number(right), name) => {
-                        if self.overrides.contains(name) {
-                            continue;
-                        }
-                        let dest = self.signals.get_mut(name).unwrap();
-                        let result = left & right;
-                        if dest.is_none() {
-                            *dest = Some(result);
-                            changes_seen = true;
-                        } else {
-                            assert_eq!(*dest, Some(result));
-                        }
-                    }
-                    Gate::Or(Value::Identifier(left), Value::Identifier(right), name) => {
-                        if self.overrides.contains(name) {
-                            continue;
-                        }
-                        if let Some(left_value) = self.signals[left] {
-                            if let Some(right_value) = self.signals[right] {
-                                let dest = self.signals.get_mut(name).unwrap();
-                                let result = left_value | right_value;
-                                if dest.is_none() {
-                                    *dest = Some(result);
-                                    changes_seen = true;
-                                } else {
-                                    assert_eq!(*dest, Some(result));
-                                }
-                            }
-                        }
-                    }
-                    Gate::Or(Value::Number(num), Value::Identifier(id), name)
-                    | Gate::Or(Value::Identifier(id), Value::Number(num), name) => {
-                        if self.overrides.contains(name) {
-                            continue;
-                        }
-                        if let Some(value) = self.signals[id] {
-                            let dest = self.signals.get_mut(name).unwrap();
-                            let result = num | value;
-                            if dest.is_none() {
-                                *dest = Some(result);
-                                changes_seen = true;
-                            } else {
-                                assert_eq!(*dest, Some(result));
-                            }
-                        }
-                    }
-                    Gate::Or(Value::Number(left), Value::Number(right), name) => {
-                        if self.overrides.contains(name) {
-                            continue;
-                        }
-                        let dest = self.signals.get_mut(name).unwrap();
-                        let result = left | right;
-                        if dest.is_none() {
-                            *dest = Some(result);
-                            changes_seen = true;
-                        } else {
-                            assert_eq!(*dest, Some(result));
-                        }
-                    }
-                    Gate::Lshift(Value::Identifier(id), shift_amt, name) => {
-                        if self.overrides.contains(name) {
-                            continue;
-                        }
-                        if let Some(value) = self.signals[id] {
-                            let dest = self.signals.get_mut(name).unwrap();
-                            let result = value << *shift_amt;
-                            if dest.is_none() {
-                                *dest = Some(result);
-                                changes_seen = true;
-                            } else {
-                                assert_eq!(*dest, Some(result));
-                            }
-                        }
-                    }
-                    Gate::Lshift(Value::Number(num), shift_amt, name) => {
-                        if self.overrides.contains(name) {
-                            continue;
-                        }
-                        let dest = self.signals.get_mut(name).unwrap();
-                        let result = *num << *shift_amt;
-                        if dest.is_none() {
-                            *dest = Some(result);
-                            changes_seen = true;
-                        } else {
-                            assert_eq!(*dest, Some(result));
-                        }
-                    }
-                    Gate::Rshift(Value::Identifier(id), shift_amt, name) => {
-                        if self.overrides.contains(name) {
-                            continue;
-                        }
-                        if let Some(value) = self.signals[id] {
-                            let dest = self.signals.get_mut(name).unwrap();
-                            let result = value >> *shift_amt;
-                            if dest.is_none() {
-                                *dest = Some(result);
-                                changes_seen = true;
-                            } else {
-                                assert_eq!(*dest, Some(result));
-                            }
-                        }
-                    }
-                    Gate::Rshift(Value::Number(num), shift_amt, name) => {
-                        if self.overrides.contains(name) {
-                            continue;
-                        }
-                        let dest = self.signals.get_mut(name).unwrap();
-                        let result = *num >> *shift_amt;
-                        if dest.is_none() {
-                            *dest = Some(result);
-                            changes_seen = true;
-                        } else {
-                            assert_eq!(*dest, Some(result));
-                        }
+        // The gates driving each wire. (A forced wire ignores its gates, so it doesn't depend on anything.)
+        let mut drivers = AHashMap::<&str, Vec<&Gate>>::new();
+        for gate in self.gates.iter().filter(|gate| !self.overrides.contains(gate.output())) {
+            drivers.entry(gate.output()).or_default().push(gate);
+        }
+        let order = toposort(self.signals.keys().cloned(), |wire| {
+            drivers
+                .get(wire.as_str())
+                .into_iter()
+                .flatten()
+                .flat_map(|gate| gate.inputs())
+                .map(String::from)
+                .collect()
+        })?;
+
+        for wire in order {
+            for gate in drivers.get(wire.as_str()).into_iter().flatten() {
+                if let Some(result) = gate.evaluate(&self.signals) {
+                    let dest = self.signals.get_mut(&wire).unwrap();
+                    if dest.is_none() {
+                        *dest = Some(result);
+                    } else {
+                        assert_eq!(*dest, Some(result));
                     }
                 }
             }
-            if !changes_seen {
-                break;
-            }
         }
+        Ok(())
+    }
+
+    /// Settle every wire in the circuit, with the forced wire values read from `text` (see [parse_overrides]).
+    fn run_from_text_overrides(&mut self, text: &str) -> anyhow::Result<()> {
+        self.run(parse_overrides(text)?)?;
+        Ok(())
+    }
+
+    /// Run the circuit with the given wires forced to fixed values, then return the signal on `target`.
+    ///
+    /// Returns `None` if `target` isn't a wire in this circuit, or if no signal ever reaches it.
+    fn solve(&mut self, target: &str, overrides: AHashMap<String, u64>) -> anyhow::Result<Option<u64>> {
+        self.run(overrides)?;
+        Ok(self.signals.get(target).copied().flatten())
     }
 }
 
@@ -403,27 +294,33 @@ fn main() -> anyhow::Result<()> {
 
     let mut circuit = lines.into_iter().collect::<anyhow::Result<Circuit>>()?;
 
-    circuit.run(AHashMap::new());
-
-    let a_signal = *circuit.signals.get(&"a".to_string()).unwrap();
-    let a_repr = match a_signal {
+    let part1 = circuit.solve("a", AHashMap::new())?;
+    let a_repr = match part1 {
         None => "--".to_string(),
         Some(x) => format!("{x}"),
     };
     println!("Part 1: Value of signal \"a\": {a_repr}");
 
-    let mut overrides = AHashMap::new();
-    overrides.insert("b".to_string(), a_signal.unwrap());
-
-    circuit.run(overrides);
-
-    let a_signal = *circuit.signals.get(&"a".to_string()).unwrap();
-    let a_repr = match a_signal {
+    let part1 = part1.ok_or_else(|| anyhow::anyhow!("No signal reached wire \"a\""))?;
+    let part2 = circuit.solve("a", AHashMap::from([("b".to_string(), part1)]))?;
+    let a_repr = match part2 {
         None => "--".to_string(),
         Some(x) => format!("{x}"),
     };
     println!("Part 2: Value of signal \"a\": {a_repr}");
 
+    // An optional argument names a file of wire values (see `parse_overrides`) to force before running the circuit
+    // one more time.
+    if let Some(path) = env::args().nth(1) {
+        let text = fs::read_to_string(&path).with_context(|| format!("Failed to read overrides from {path}"))?;
+        circuit.run_from_text_overrides(&text)?;
+        let a_repr = match circuit.signals.get("a").copied().flatten() {
+            None => "--".to_string(),
+            Some(x) => format!("{x}"),
+        };
+        println!("With the overrides from {path}: Value of signal \"a\": {a_repr}");
+    }
+
     Ok(())
 }
 
@@ -438,4 +335,89 @@ mod tests {
     fn gate_try_from(s: &str) -> Result<Gate, String> {
         Gate::try_from(s).map_err(|e| format!("{e}"))
     }
+
+    static SAMPLE: &str = indoc::indoc! {"
+        123 -> x
+        456 -> y
+        x AND y -> d
+        x OR y -> e
+        x LSHIFT 2 -> f
+        y RSHIFT 2 -> g
+        NOT x -> h
+        NOT y -> i
+    "};
+
+    #[test_case("d", &[] => Some(72); "d")]
+    #[test_case("e", &[] => Some(507); "e")]
+    #[test_case("f", &[] => Some(492); "f")]
+    #[test_case("g", &[] => Some(114); "g")]
+    #[test_case("h", &[] => Some(65412); "h")]
+    #[test_case("i", &[] => Some(65079); "i")]
+    #[test_case("x", &[] => Some(123); "x")]
+    #[test_case("y", &[] => Some(456); "y")]
+    #[test_case("zz", &[] => None; "missing wire")]
+    #[test_case("d", &[("x", 0xFF)] => Some(200); "override x")]
+    #[test_case("d", &[("d", 9)] => Some(9); "override target")]
+    fn solve(target: &str, overrides: &[(&str, u64)]) -> Option<u64> {
+        let mut circuit = SAMPLE
+            .lines()
+            .map(StringWrap::from)
+            .collect::<anyhow::Result<Circuit>>()
+            .unwrap();
+        let overrides = overrides
+            .iter()
+            .map(|&(k, v)| (k.to_string(), v))
+            .collect::<AHashMap<_, _>>();
+        circuit.solve(target, overrides).unwrap()
+    }
+
+    #[test_case(&[] => Err("dependency cycle".to_string()); "wires in a loop")]
+    #[test_case(&[("c", 7)] => Ok(Some(7 ^ 0xFFFF)); "loop broken by an override")]
+    fn looped(overrides: &[(&str, u64)]) -> Result<Option<u64>, String> {
+        let mut circuit = ["NOT c -> a", "a -> b", "b AND 65535 -> c"]
+            .into_iter()
+            .map(StringWrap::from)
+            .collect::<anyhow::Result<Circuit>>()
+            .unwrap();
+        let overrides = overrides
+            .iter()
+            .map(|&(k, v)| (k.to_string(), v))
+            .collect::<AHashMap<_, _>>();
+        // Where the cycle is reported as starting depends on hash order, so just check what kind of error it is.
+        circuit
+            .solve("b", overrides)
+            .map_err(|err| err.to_string().split(':').next().unwrap().to_string())
+    }
+
+    #[test_case("" => Ok(vec![]); "empty")]
+    #[test_case("b: 19" => Ok(vec![("b".to_string(), 19)]); "colon")]
+    #[test_case("b = 19" => Ok(vec![("b".to_string(), 19)]); "equals")]
+    #[test_case("b=0\n\n  xy :65535  \n" => Ok(vec![("b".to_string(), 0), ("xy".to_string(), 65535)]); "several")]
+    #[test_case("b -> 19" => Err("Cannot parse ‘b -> 19’ as a wire value".to_string()); "wrong separator")]
+    #[test_case("B: 19" => Err("Cannot parse ‘B: 19’ as a wire value".to_string()); "uppercase")]
+    #[test_case("b: 019" => Err("Cannot parse ‘b: 019’ as a wire value".to_string()); "leading zero")]
+    #[test_case("b: 65536" => Err("This integer is too large for a wire value".to_string()); "past 16 bits")]
+    #[test_case("b: 99999999999999999999" => Err("This integer is too large for a wire value".to_string()); "too big")]
+    #[test_case("b: 1\nb: 2" => Err("Wire b is given more than one value".to_string()); "repeated")]
+    fn parse_overrides(text: &str) -> Result<Vec<(String, u64)>, String> {
+        let mut overrides = super::parse_overrides(text)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .collect::<Vec<_>>();
+        overrides.sort();
+        Ok(overrides)
+    }
+
+    #[test_case("" => Some(72); "no overrides")]
+    #[test_case("x: 255" => Some(200); "override x")]
+    #[test_case("x = 255\ny = 15" => Some(15); "override both")]
+    fn run_from_text_overrides(text: &str) -> Option<u64> {
+        let mut circuit = SAMPLE
+            .lines()
+            .map(StringWrap::from)
+            .collect::<anyhow::Result<Circuit>>()
+            .unwrap();
+        circuit.run_from_text_overrides(text).unwrap();
+        circuit.signals.get("d").copied().flatten()
+    }
 }
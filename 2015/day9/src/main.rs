@@ -4,7 +4,7 @@
 //!
 use ahash::{AHashMap, AHashSet};
 use anyhow::Context;
-use combinations::{Combination, Permutation};
+use combinations::{pairs, Permutation};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use std::io::{self, Read};
@@ -59,20 +59,32 @@ impl FromIterator<DPResult> for anyhow::Result<Data> {
             data.locations.insert(point.location_b);
         }
         // Do some validation
-        // 1. All pairs must have a distance. (either (a,b) or (b,a))
-        // 2. If both orders exist, they must have the same distance ((a,b) = (b,a))
-        for pair in Combination::new(data.locations.iter().cloned().collect::<Vec<_>>().as_slice(), 2)
-            .map(|v| (v[0].clone(), v[1].clone()))
-        {
-            let alternate = (pair.1.clone(), pair.0.clone());
-            let exists = data.distances.contains_key(&pair);
-            let alt_exists = data.distances.contains_key(&alternate);
-            if !exists && !alt_exists {
-                anyhow::bail!("No distance found between {} and {}", pair.0, pair.1);
-            }
-            if exists && alt_exists && data.distances.get(&pair) != data.distances.get(&alternate) {
-                anyhow::bail!("Inconsistent distances between {} and {}", pair.0, pair.1);
-            }
+        // 1. If both orders exist, they must have the same distance ((a,b) = (b,a))
+        // 2. All pairs must have a distance. (either (a,b) or (b,a))
+        // (The pairs are sorted so that the error messages don't depend on hash order.)
+        let mut inconsistent = data
+            .distances
+            .iter()
+            .filter(|&((a, b), distance)| {
+                a < b
+                    && data
+                        .distances
+                        .get(&(b.clone(), a.clone()))
+                        .is_some_and(|alt| alt != distance)
+            })
+            .map(|((a, b), _)| format!("{a} and {b}"))
+            .collect::<Vec<_>>();
+        if !inconsistent.is_empty() {
+            inconsistent.sort();
+            anyhow::bail!("Inconsistent distances between {}", inconsistent.join(", "));
+        }
+        if !data.is_complete() {
+            let missing = data
+                .missing_edges()
+                .into_iter()
+                .map(|(a, b)| format!("{a} and {b}"))
+                .collect::<Vec<_>>();
+            anyhow::bail!("No distance found between {}", missing.join(", "));
         }
 
         Ok(data)
@@ -80,6 +92,25 @@ impl FromIterator<DPResult> for anyhow::Result<Data> {
 }
 
 impl Data {
+    /// The pairs of locations with no distance given between them (in either direction), in sorted order.
+    fn missing_edges(&self) -> Vec<(String, String)> {
+        let mut cities = self.locations.iter().cloned().collect::<Vec<_>>();
+        cities.sort();
+        pairs(&cities)
+            .filter(|&(a, b)| {
+                !self.distances.contains_key(&(a.clone(), b.clone()))
+                    && !self.distances.contains_key(&(b.clone(), a.clone()))
+            })
+            .map(|(a, b)| (a.clone(), b.clone()))
+            .collect()
+    }
+
+    /// Whether there's a distance between every pair of locations. (Without this, not every ordering of the
+    /// locations is a route.)
+    fn is_complete(&self) -> bool {
+        self.missing_edges().is_empty()
+    }
+
     fn distance(&self, start: String, finish: String) -> usize {
         match self.distances.get(&(start.clone(), finish.clone())) {
             Some(&val) => val,
@@ -87,101 +118,55 @@ impl Data {
         }
     }
 
-    fn shortest_between(&self, start: &str, finish: &str) -> anyhow::Result<Vec<String>> {
-        // Find the shortest path that visits all cities, starting at `start` and ending at `finish`.
-        let start = self
-            .locations
-            .get(start)
-            .ok_or_else(|| anyhow::anyhow!("No location named {start} in the dataset"))?;
-        let finish = self
-            .locations
-            .get(finish)
-            .ok_or_else(|| anyhow::anyhow!("No location named {finish} in the dataset"))?;
-        // My original code was based on geometry and the triangle inequality. The input data, however,
-        // clearly has wormholes & spacetime anomolies (i.e.: the triangle inequality does not hold). So the
-        // first method got scrapped. Think of these less as distances, and more like energy requirements,
-        // where things like catalytic reactions can take place, and where adding a step in the right spot can
-        // make the whole thing cheaper.
-
-        // The current method is just to try every permutation and see what comes out cheapest.
-        let inner_locations = self
-            .locations
+    /// The cities, in sorted order, along with the distance between every pair of them, indexed by position in
+    /// that list.
+    ///
+    /// The matrix is symmetric (the input's consistency was checked when the data was collected), with zeros on
+    /// the diagonal.
+    fn distance_matrix(&self) -> (Vec<String>, Vec<Vec<usize>>) {
+        let mut cities = self.locations.iter().cloned().collect::<Vec<_>>();
+        cities.sort();
+        let matrix = cities
             .iter()
-            .filter(|&loc| loc != start && loc != finish)
-            .collect::<Vec<_>>();
-        Ok(Permutation::new(inner_locations.as_slice())
-            .map(|potential| {
-                let mut path = vec![start];
-                path.extend(potential);
-                path.push(finish);
-                (
-                    self.path_distance(&path.iter().map(|&s| s.clone()).collect::<Vec<_>>()),
-                    path,
-                )
+            .map(|a| {
+                cities
+                    .iter()
+                    .map(|b| if a == b { 0 } else { self.distance(a.clone(), b.clone()) })
+                    .collect()
             })
-            .min_by(|&(a, _), &(c, _)| a.cmp(&c))
-            .map(|x| x.1.iter().map(|&x| x.clone()).collect::<Vec<_>>())
-            .unwrap())
+            .collect();
+        (cities, matrix)
     }
 
-    fn longest_between(&self, start: &str, finish: &str) -> anyhow::Result<Vec<String>> {
-        // Find the longest path that visits all cities, starting at `start` and ending at `finish`.
-        let start = self
-            .locations
-            .get(start)
-            .ok_or_else(|| anyhow::anyhow!("No location named {start} in the dataset"))?;
-        let finish = self
-            .locations
-            .get(finish)
-            .ok_or_else(|| anyhow::anyhow!("No location named {finish} in the dataset"))?;
-        // My original code was based on geometry and the triangle inequality. The input data, however,
-        // clearly has wormholes & spacetime anomolies (i.e.: the triangle inequality does not hold). So the
-        // first method got scrapped. Think of these less as distances, and more like energy requirements,
-        // where things like catalytic reactions can take place, and where adding a step in the right spot can
-        // make the whole thing cheaper.
-
-        // The current method is just to try every permutation and see what comes out cheapest.
-        let inner_locations = self
-            .locations
-            .iter()
-            .filter(|&loc| loc != start && loc != finish)
-            .collect::<Vec<_>>();
-        Ok(Permutation::new(inner_locations.as_slice())
-            .map(|potential| {
-                let mut path = vec![start];
-                path.extend(potential);
-                path.push(finish);
-                (
-                    self.path_distance(&path.iter().map(|&s| s.clone()).collect::<Vec<_>>()),
-                    path,
-                )
-            })
-            .max_by(|&(a, _), &(c, _)| a.cmp(&c))
-            .map(|x| x.1.iter().map(|&x| x.clone()).collect::<Vec<_>>())
-            .unwrap())
-    }
-
-    fn path_distance(&self, path: &[String]) -> usize {
-        path.windows(2)
-            .map(|v| (v[0].clone(), v[1].clone()))
-            .map(|pair| self.distance(pair.0, pair.1))
-            .sum()
+    // My original code was based on geometry and the triangle inequality. The input data, however, clearly has
+    // wormholes & spacetime anomolies (i.e.: the triangle inequality does not hold). So the first method got
+    // scrapped. Think of these less as distances, and more like energy requirements, where things like catalytic
+    // reactions can take place, and where adding a step in the right spot can make the whole thing cheaper.
+    //
+    // The current method is just to try every ordering of the cities and see what comes out cheapest (or most
+    // expensive). The orderings are of indices into the distance matrix, so no city names are touched until the
+    // winner is known.
+    fn best_path(
+        &self,
+        pick: impl FnOnce(&mut dyn Iterator<Item = (usize, Vec<usize>)>) -> Option<(usize, Vec<usize>)>,
+    ) -> Option<(Vec<String>, usize)> {
+        let (cities, matrix) = self.distance_matrix();
+        if cities.is_empty() {
+            return None;
+        }
+        let indices = (0..cities.len()).collect::<Vec<_>>();
+        let mut routes =
+            Permutation::new(&indices).map(|route| (route.windows(2).map(|leg| matrix[leg[0]][leg[1]]).sum(), route));
+        pick(&mut routes)
+            .map(|(distance, route)| (route.into_iter().map(|idx| cities[idx].clone()).collect(), distance))
     }
 
     fn shortest_path(&self) -> Option<(Vec<String>, usize)> {
-        Combination::new(&self.locations.iter().collect::<Vec<_>>(), 2)
-            .map(|endpoints| self.shortest_between(endpoints[0], endpoints[1]).unwrap())
-            .map(|city_list| (self.path_distance(&city_list), city_list))
-            .min_by(|&(dist_a, _), &(dist_b, _)| dist_a.cmp(&dist_b))
-            .map(|x| (x.1, x.0))
+        self.best_path(|routes| routes.min_by_key(|&(distance, _)| distance))
     }
 
     fn longest_path(&self) -> Option<(Vec<String>, usize)> {
-        Combination::new(&self.locations.iter().collect::<Vec<_>>(), 2)
-            .map(|endpoints| self.longest_between(endpoints[0], endpoints[1]).unwrap())
-            .map(|city_list| (self.path_distance(&city_list), city_list))
-            .max_by(|&(dist_a, _), &(dist_b, _)| dist_a.cmp(&dist_b))
-            .map(|x| (x.1, x.0))
+        self.best_path(|routes| routes.max_by_key(|&(distance, _)| distance))
     }
 }
 fn part1(input: &str) -> anyhow::Result<usize> {
@@ -221,6 +206,7 @@ fn main() -> anyhow::Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use combinations::Combination;
 
     static SAMPLE: &str = indoc::indoc! {"
         London to Dublin = 464
@@ -233,6 +219,85 @@ mod tests {
         assert_eq!(part1(SAMPLE).unwrap(), 605);
     }
 
+    #[test]
+    fn distance_matrix_is_symmetric() {
+        let data = SAMPLE
+            .lines()
+            .map(|line| DPResult(line.parse::<DataPoint>()))
+            .collect::<Result<Data, anyhow::Error>>()
+            .unwrap();
+        let (cities, matrix) = data.distance_matrix();
+        assert_eq!(cities, vec!["Belfast", "Dublin", "London"]);
+        assert_eq!(matrix, vec![vec![0, 141, 518], vec![141, 0, 464], vec![518, 464, 0]]);
+        for (row, distances) in matrix.iter().enumerate() {
+            for (col, &distance) in distances.iter().enumerate() {
+                assert_eq!(distance, matrix[col][row]);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_edges() {
+        let mut data = Data::default();
+        for point in SAMPLE.lines().chain(["Dublin to Paris = 781", "London to Paris = 344"]) {
+            let point = point.parse::<DataPoint>().unwrap();
+            data.locations.insert(point.location_a.clone());
+            data.locations.insert(point.location_b.clone());
+            data.distances
+                .insert((point.location_a, point.location_b), point.distance);
+        }
+        assert!(!data.is_complete());
+        assert_eq!(data.missing_edges(), vec![("Belfast".to_string(), "Paris".to_string())]);
+
+        data.distances
+            .insert(("Paris".to_string(), "Belfast".to_string()), 1053);
+        assert!(data.is_complete());
+        assert!(data.missing_edges().is_empty());
+    }
+
+    #[test]
+    fn incomplete_input() {
+        let input = indoc::indoc! {"
+            London to Dublin = 464
+            London to Belfast = 518
+            Paris to Dublin = 781
+        "};
+        let err = input
+            .lines()
+            .map(|line| DPResult(line.parse::<DataPoint>()))
+            .collect::<Result<Data, anyhow::Error>>()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No distance found between Belfast and Dublin, Belfast and Paris, London and Paris"
+        );
+    }
+
+    #[test]
+    fn inconsistent_input() {
+        let input = indoc::indoc! {"
+            London to Dublin = 464
+            Dublin to London = 465
+            London to Belfast = 518
+            Belfast to London = 518
+            Dublin to Belfast = 141
+            Paris to Dublin = 781
+            Dublin to Paris = 782
+            Paris to Belfast = 1053
+            Belfast to Paris = 1054
+            London to Paris = 344
+        "};
+        let err = input
+            .lines()
+            .map(|line| DPResult(line.parse::<DataPoint>()))
+            .collect::<Result<Data, anyhow::Error>>()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Inconsistent distances between Belfast and Paris, Dublin and London, Dublin and Paris"
+        );
+    }
+
     #[test]
     fn part2_sample() {
         assert_eq!(part2(SAMPLE).unwrap(), 982);
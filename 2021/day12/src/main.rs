@@ -8,6 +8,7 @@ use ahash::AHashSet;
 use anyhow::{self, Context};
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::collections::VecDeque;
 use std::io::{self, BufRead};
 
 /// Marker for cavern size
@@ -140,6 +141,9 @@ impl Network {
     ///
     /// Some things can only be checked after the network is built, after the input has been processed. (Like: do we
     /// have start and end nodes?) Those checks happen here.
+    ///
+    /// Two big caverns joined directly to each other are also rejected: a path could bounce between them forever,
+    /// so there would be infinitely many paths (and `figure_paths` would never finish counting them).
     fn validate(&self) -> anyhow::Result<()> {
         if !self.nodes.contains_key(&"start".into()) {
             return Err(anyhow::anyhow!("start node missing from caverns"));
@@ -147,9 +151,65 @@ impl Network {
         if !self.nodes.contains_key(&"end".into()) {
             return Err(anyhow::anyhow!("end node missing from caverns"));
         }
+        // Each link is stored in both directions, so keeping only the ordered pairs reports each link just once.
+        // (A big cavern linked to itself shows up as a pair with itself, and is just as bad.)
+        // Taking the minimum keeps the report stable no matter how the hash map is ordered.
+        let big_link = self
+            .nodes
+            .values()
+            .filter(|node| node.id.size() == NodeSize::Big)
+            .flat_map(|node| node.connections.iter().map(move |other| (&node.id, other)))
+            .filter(|(id, other)| other.size() == NodeSize::Big && id.0 <= other.0)
+            .map(|(id, other)| (&id.0, &other.0))
+            .min();
+        if let Some((id, other)) = big_link {
+            if id == other {
+                return Err(anyhow::anyhow!(
+                    "big cavern {id} is connected to itself, so there are infinitely many paths"
+                ));
+            }
+            return Err(anyhow::anyhow!(
+                "big caverns {id} and {other} are directly connected, so there are infinitely many paths"
+            ));
+        }
         Ok(())
     }
 
+    /// The caverns directly connected to the cavern `id`
+    ///
+    /// An unknown `id` simply has no neighbors.
+    pub fn neighbors(&self, id: &Identifier) -> impl Iterator<Item = &Identifier> {
+        self.nodes.get(id).into_iter().flat_map(|node| node.connections.iter())
+    }
+
+    /// The number of caverns in the network
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Whether any route at all leads from start to end
+    ///
+    /// This is a plain breadth-first search, so it ignores the rules about revisiting small caverns; if this returns
+    /// `false`, though, there are certainly no paths for `figure_paths` to find.
+    pub fn is_connected(&self) -> bool {
+        let start = Identifier::from("start");
+        let end = Identifier::from("end");
+        let mut seen = AHashSet::with_capacity(self.node_count());
+        let mut queue = VecDeque::from([&start]);
+        seen.insert(&start);
+        while let Some(id) = queue.pop_front() {
+            if *id == end {
+                return true;
+            }
+            for neighbor in self.neighbors(id) {
+                if seen.insert(neighbor) {
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        false
+    }
+
     #[doc(hidden)]
     /// The workhorse of the path calculator
     ///
@@ -231,6 +291,10 @@ impl Network {
     /// restriction broke `small_limit` for values greater than 2.
     fn figure_paths(&self, small_limit: usize, collect: bool) -> (Vec<Vec<Identifier>>, usize) {
         // This is the top of a recursive routine, so really all we do is set up the initial path and then let it go.
+        // (Unless end can't be reached at all, in which case there's no point in looking.)
+        if !self.is_connected() {
+            return (vec![], 0);
+        }
         let initial_path = vec![Identifier::from("start")];
         self.continue_path(initial_path, small_limit, collect)
     }
@@ -288,3 +352,72 @@ fn main() -> Result<(), anyhow::Error> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    static SAMPLE: &[&str] = &["start-A", "start-b", "A-c", "A-b", "b-d", "A-end", "b-end"];
+
+    fn network(lines: &[&str]) -> Network {
+        lines
+            .iter()
+            .map(|&line| parse(line.to_string()))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap()
+            .into_iter()
+            .collect::<anyhow::Result<Network>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn node_count() {
+        assert_eq!(network(SAMPLE).node_count(), 6);
+    }
+
+    #[test_case("start" => vec!["A", "b"]; "start")]
+    #[test_case("A" => vec!["b", "c", "end", "start"]; "big cavern")]
+    #[test_case("d" => vec!["b"]; "dead end")]
+    #[test_case("zz" => Vec::<String>::new(); "unknown")]
+    fn neighbors(id: &str) -> Vec<String> {
+        let network = network(SAMPLE);
+        let mut result = network.neighbors(&id.into()).map(|id| id.0.clone()).collect::<Vec<_>>();
+        result.sort();
+        result
+    }
+
+    #[test_case(SAMPLE => true; "sample")]
+    #[test_case(&["start-a", "a-B", "end-c", "c-D"] => false; "split")]
+    fn is_connected(lines: &[&str]) -> bool {
+        network(lines).is_connected()
+    }
+
+    #[test_case(&["start-A", "end-A"] => None; "no big links")]
+    #[test_case(&["start-AA", "AA-BB", "BB-end"] => Some(String::from("big caverns AA and BB are directly connected, so there are infinitely many paths")); "big link")]
+    #[test_case(&["start-X", "X-end", "C-B", "B-A"] => Some(String::from("big caverns A and B are directly connected, so there are infinitely many paths")); "unreachable big link")]
+    #[test_case(&["start-A", "A-A", "A-end"] => Some(String::from("big cavern A is connected to itself, so there are infinitely many paths")); "big self link")]
+    #[test_case(&["start-A", "A-b"] => Some(String::from("end node missing from caverns")); "no end")]
+    fn validate(lines: &[&str]) -> Option<String> {
+        lines
+            .iter()
+            .map(|&line| parse(line.to_string()).unwrap())
+            .collect::<anyhow::Result<Network>>()
+            .err()
+            .map(|err| err.to_string())
+    }
+
+    #[test]
+    fn no_paths_when_split() {
+        let network = network(&["start-a", "a-B", "end-c", "c-D"]);
+        assert_eq!(network.figure_paths(1, true), (vec![], 0));
+        assert_eq!(network.figure_paths(2, false), (vec![], 0));
+    }
+
+    #[test]
+    fn sample_paths() {
+        let network = network(SAMPLE);
+        assert_eq!(network.figure_paths(1, false).1, 10);
+        assert_eq!(network.figure_paths(2, false).1, 36);
+    }
+}
@@ -9,23 +9,61 @@ use std::io::{self, BufRead};
 use std::iter::Peekable;
 use std::str::Chars;
 
+/// A character stream that keeps track of how far into the source string it has read, so that parse errors can
+/// say where they happened.
+struct Cursor<'a> {
+    chars: Peekable<Chars<'a>>,
+    offset: usize,
+}
+impl<'a> Cursor<'a> {
+    fn new(src: &'a str) -> Self {
+        Cursor {
+            chars: src.chars().peekable(),
+            offset: 0,
+        }
+    }
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+    fn next(&mut self) -> Option<char> {
+        let ch = self.chars.next()?;
+        self.offset += ch.len_utf8();
+        Some(ch)
+    }
+    fn expect(&mut self, expected: char) -> anyhow::Result<()> {
+        let offset = self.offset;
+        match self.next() {
+            Some(ch) if ch == expected => Ok(()),
+            Some(ch) => Err(anyhow::anyhow!("expected ‘{expected}’ at offset {offset} (saw ‘{ch}’)")),
+            None => Err(anyhow::anyhow!(
+                "expected ‘{expected}’ at offset {offset}, not end-of-string"
+            )),
+        }
+    }
+}
+
 #[derive(Debug)]
 enum PairValue {
     Number(i32),
     Pair(Pair),
 }
 impl PairValue {
-    fn parse(iter: &mut Peekable<Chars>) -> anyhow::Result<PairValue> {
+    fn parse(iter: &mut Cursor) -> anyhow::Result<PairValue> {
         let ch = iter
             .peek()
-            .ok_or_else(|| anyhow::anyhow!("Expected a value, not end-of-string"))?;
-        match *ch {
+            .ok_or_else(|| anyhow::anyhow!("expected a value at offset {}, not end-of-string", iter.offset))?;
+        match ch {
             '[' => Ok(PairValue::Pair(Pair::parse(iter)?)),
             '0'..='9' => Ok(PairValue::Number(PairValue::parse_number(iter)?)),
-            _ => Err(anyhow::anyhow!("Invalid character for pair: {}", *ch)),
+            _ => Err(anyhow::anyhow!(
+                "expected a value at offset {} (saw ‘{}’)",
+                iter.offset,
+                ch
+            )),
         }
     }
-    fn parse_number(iter: &mut Peekable<Chars>) -> anyhow::Result<i32> {
+    fn parse_number(iter: &mut Cursor) -> anyhow::Result<i32> {
+        let start = iter.offset;
         let mut value: i32 = 0;
         while let Some(ch) = iter.peek() {
             match ch.to_digit(10) {
@@ -33,7 +71,7 @@ impl PairValue {
                     value = value
                         .checked_mul(10)
                         .and_then(|v| v.checked_add(i32::try_from(val).unwrap()))
-                        .ok_or_else(|| anyhow::anyhow!("Integer overflow"))?;
+                        .ok_or_else(|| anyhow::anyhow!("Integer overflow in number at offset {start}"))?;
                     iter.next();
                 }
                 None => {
@@ -43,12 +81,6 @@ impl PairValue {
         }
         Ok(value)
     }
-    fn magnitude(&self) -> i64 {
-        match self {
-            PairValue::Number(x) => *x as i64,
-            PairValue::Pair(p) => p.magnitude(),
-        }
-    }
 }
 impl fmt::Display for PairValue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -70,38 +102,26 @@ impl TryFrom<&PairValue> for i32 {
 #[derive(Debug)]
 struct Pair(Box<[PairValue; 2]>);
 impl Pair {
-    fn parse(iter: &mut Peekable<Chars>) -> anyhow::Result<Pair> {
-        let ch = iter.next().ok_or_else(|| anyhow::anyhow!("Empty string"))?;
-        if ch != '[' {
-            anyhow::bail!("Pairs must start with the ‘[’ character (saw ‘{}’)", ch);
-        }
-
+    fn parse(iter: &mut Cursor) -> anyhow::Result<Pair> {
+        iter.expect('[')?;
         let left = PairValue::parse(iter)?;
-
-        let separator = iter.next().ok_or_else(|| anyhow::anyhow!("Unterminated pair"))?;
-        if separator != ',' {
-            anyhow::bail!("Values are separated by the ‘,’ character (saw ‘{}’)", separator);
-        }
-
+        iter.expect(',')?;
         let right = PairValue::parse(iter)?;
-
-        let terminator = iter.next().ok_or_else(|| anyhow::anyhow!("Unterminated pair"))?;
-        if terminator != ']' {
-            anyhow::bail!("Pairs must end with the ‘]’ character (saw ‘{}’)", terminator);
-        }
+        iter.expect(']')?;
 
         Ok(Pair(Box::new([left, right])))
     }
-
-    fn magnitude(&self) -> i64 {
-        3 * self.0[0].magnitude() + 2 * self.0[1].magnitude()
-    }
 }
 impl TryFrom<&str> for Pair {
     type Error = anyhow::Error;
     fn try_from(src: &str) -> anyhow::Result<Self> {
-        let mut iter = src.chars().peekable();
-        Pair::parse(&mut iter)
+        let mut iter = Cursor::new(src);
+        let pair = Pair::parse(&mut iter)?;
+        let offset = iter.offset;
+        match iter.next() {
+            None => Ok(pair),
+            Some(ch) => Err(anyhow::anyhow!("unexpected ‘{ch}’ at offset {offset} after the number")),
+        }
     }
 }
 impl From<Pair2> for Pair {
@@ -136,6 +156,13 @@ impl fmt::Display for PairPart {
         }
     }
 }
+/// One step of snailfish reduction, along with the position (in a [Pair2]'s sequence) of the part it acted on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ReduceAction {
+    Explode(usize),
+    Split(usize),
+}
+
 #[derive(Debug, Clone)]
 struct Pair2(Vec<PairPart>);
 impl TryFrom<&str> for Pair2 {
@@ -177,19 +204,28 @@ impl Pair2 {
     }
 
     fn reduce(&mut self) {
-        loop {
-            let changes_made = self.explode();
-            if changes_made {
-                continue;
-            }
-            let changes_made = self.split();
-            if !changes_made {
-                break;
-            }
+        self.reduce_counted();
+    }
+
+    /// Reduce to a fixed point, returning the number of explodes and splits it took.
+    fn reduce_counted(&mut self) -> usize {
+        let mut steps = 0;
+        while self.reduce_step().is_some() {
+            steps += 1;
         }
+        steps
     }
 
-    fn explode(&mut self) -> bool {
+    /// Take a single reduction step (an explode if there is one, otherwise a split), reporting what was done, or
+    /// `None` if the number is already fully reduced.
+    fn reduce_step(&mut self) -> Option<ReduceAction> {
+        self.explode()
+            .map(ReduceAction::Explode)
+            .or_else(|| self.split().map(ReduceAction::Split))
+    }
+
+    // Explode the leftmost pair nested inside four others, returning its position in the sequence.
+    fn explode(&mut self) -> Option<usize> {
         let mut depth = 0;
         let mut explode_location = None;
         for (idx, item) in self.0.iter().enumerate() {
@@ -251,13 +287,14 @@ impl Pair2 {
             self.0.copy_within(idx + 4.., idx + 1);
             self.0.truncate(self.0.len() - 3);
 
-            true
+            Some(idx)
         } else {
-            false
+            None
         }
     }
 
-    fn split(&mut self) -> bool {
+    // Split the leftmost number of 10 or more, returning its position in the sequence.
+    fn split(&mut self) -> Option<usize> {
         let split_data = self.0.iter().enumerate().find_map(|(a, b)| {
             if let PairPart::Number(x) = b {
                 if *x >= 10 {
@@ -276,14 +313,29 @@ impl Pair2 {
                 self.0.insert(idx + 1, PairPart::Number((val + 1) / 2));
                 self.0.insert(idx + 1, PairPart::Number(val / 2));
                 self.0[idx] = PairPart::Open;
-                true
+                Some(idx)
             }
-            None => false,
+            None => None,
         }
     }
 
     fn magnitude(&self) -> i64 {
-        Pair::from(self).magnitude()
+        // Numbers go on a stack as they're reached; each close bracket combines the two values on top (the pair it
+        // closes) into one. What's left at the end is the magnitude of the whole thing.
+        let mut stack = Vec::new();
+        for item in self.0.iter() {
+            match item {
+                PairPart::Open => {}
+                PairPart::Number(n) => stack.push(*n as i64),
+                PairPart::Close => {
+                    let right = stack.pop().expect("a pair has two values");
+                    let left = stack.pop().expect("a pair has two values");
+                    stack.push(3 * left + 2 * right);
+                }
+            }
+        }
+        assert_eq!(stack.len(), 1, "a snailfish number is a single pair");
+        stack[0]
     }
 }
 
@@ -367,6 +419,74 @@ mod tests {
         format!("{input}")
     }
 
+    #[test]
+    fn reduce_steps() {
+        let mut sn = Pair2::try_from("[[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]").unwrap();
+        let mut steps = vec![];
+        while let Some(action) = sn.reduce_step() {
+            steps.push((action, sn.to_string()));
+        }
+        assert_eq!(
+            steps,
+            vec![
+                (
+                    ReduceAction::Explode(4),
+                    "[[[[0,7],4],[7,[[8,4],9]]],[1,1]]".to_string()
+                ),
+                (ReduceAction::Explode(12), "[[[[0,7],4],[15,[0,13]]],[1,1]]".to_string()),
+                (
+                    ReduceAction::Split(10),
+                    "[[[[0,7],4],[[7,8],[0,13]]],[1,1]]".to_string()
+                ),
+                (
+                    ReduceAction::Split(16),
+                    "[[[[0,7],4],[[7,8],[0,[6,7]]]],[1,1]]".to_string()
+                ),
+                (
+                    ReduceAction::Explode(16),
+                    "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test_case("[[[[[9,8],1],2],3],4]" => (Some(4), "[[[[0,9],2],3],4]".to_string()); "leftmost")]
+    #[test_case("[7,[6,[5,[4,[3,2]]]]]" => (Some(8), "[7,[6,[5,[7,0]]]]".to_string()); "rightmost")]
+    #[test_case("[[6,[5,[4,[3,2]]]],1]" => (Some(7), "[[6,[5,[7,0]]],3]".to_string()); "middle")]
+    #[test_case("[[3,[2,[1,[7,3]]]],[6,[5,[4,[3,2]]]]]" => (Some(7), "[[3,[2,[8,0]]],[9,[5,[4,[3,2]]]]]".to_string()); "first of two")]
+    #[test_case("[[[[0,9],2],3],4]" => (None, "[[[[0,9],2],3],4]".to_string()); "nothing to explode")]
+    fn explode(src: &str) -> (Option<usize>, String) {
+        let mut sn = Pair2::try_from(src).unwrap();
+        let before = sn.0.len();
+        let location = sn.explode();
+        // The exploded pair's four parts collapse into a single number, and everything after it shifts down
+        // without leaving anything stale at the end.
+        let expected_len = if location.is_some() { before - 3 } else { before };
+        assert_eq!(sn.0.len(), expected_len);
+        let rendered = sn.to_string();
+        assert_eq!(sn.0, Pair2::try_from(rendered.as_str()).unwrap().0);
+        (location, rendered)
+    }
+
+    #[test_case("[[[[[9,8],1],2],3],4]" => 1; "one explode")]
+    #[test_case("[[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]" => 5; "explode+split example")]
+    #[test_case("[1,2]" => 0; "already reduced")]
+    fn reduce_counted(src: &str) -> usize {
+        Pair2::try_from(src).unwrap().reduce_counted()
+    }
+
+    #[test_case("[1,2" => "expected ‘]’ at offset 4, not end-of-string"; "unterminated")]
+    #[test_case("[1 2]" => "expected ‘,’ at offset 2 (saw ‘ ’)"; "missing comma")]
+    #[test_case("[[1,2],]" => "expected a value at offset 7 (saw ‘]’)"; "missing value")]
+    #[test_case("" => "expected ‘[’ at offset 0, not end-of-string"; "empty")]
+    #[test_case("7" => "expected ‘[’ at offset 0 (saw ‘7’)"; "bare number")]
+    #[test_case("[1,[2,3]" => "expected ‘]’ at offset 8, not end-of-string"; "unterminated outer")]
+    #[test_case("[1,2]]" => "unexpected ‘]’ at offset 5 after the number"; "trailing junk")]
+    #[test_case("[1,99999999999]" => "Integer overflow in number at offset 3"; "overflow")]
+    fn parse_error(src: &str) -> String {
+        Pair2::try_from(src).unwrap_err().to_string()
+    }
+
     #[test_case(&["[1,1]","[2,2]","[3,3]","[4,4]"] => "[[[[1,1],[2,2]],[3,3]],[4,4]]"; "add example 1")]
     #[test_case(&["[1,1]","[2,2]","[3,3]","[4,4]","[5,5]"] => "[[[[3,0],[5,3]],[4,4]],[5,5]]"; "add example 2")]
     #[test_case(&["[1,1]","[2,2]","[3,3]","[4,4]","[5,5]","[6,6]"] => "[[[[5,0],[7,4]],[5,5]],[6,6]]"; "add example 3")]
@@ -401,4 +521,15 @@ mod tests {
         }
         format!("{sn}")
     }
+
+    #[test_case("[9,1]" => 29; "simple pair")]
+    #[test_case("[[1,2],[[3,4],5]]" => 143; "example 1")]
+    #[test_case("[[[[0,7],4],[[7,8],[6,0]]],[8,1]]" => 1384; "example 2")]
+    #[test_case("[[[[1,1],[2,2]],[3,3]],[4,4]]" => 445; "example 3")]
+    #[test_case("[[[[3,0],[5,3]],[4,4]],[5,5]]" => 791; "example 4")]
+    #[test_case("[[[[5,0],[7,4]],[5,5]],[6,6]]" => 1137; "example 5")]
+    #[test_case("[[[[8,7],[7,7]],[[8,6],[7,7]]],[[[0,7],[6,6]],[8,7]]]" => 3488; "example 6")]
+    fn magnitude(src: &str) -> i64 {
+        Pair2::try_from(src).unwrap().magnitude()
+    }
 }
@@ -1,3 +1,521 @@
-fn main() {
-    println!("Hello world");
+//! # Solution for Advent of Code 2021 Day 19
+//!
+//! Ref: [Advent of Code 2021 Day 19](https://adventofcode.com/2021/day/19)
+//!
+
+use ahash::{AHashMap, AHashSet};
+use anyhow::{self, Context};
+use combinations::pairs;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use std::ops::{Add, Sub};
+
+/// The number of beacons two scanners must have in common before we believe they overlap.
+const OVERLAP_THRESHOLD: usize = 12;
+/// The number of beacon-to-beacon distances two overlapping scanners must have in common (that is, the number of
+/// pairs of their shared beacons).
+const OVERLAP_DISTANCES: usize = OVERLAP_THRESHOLD * (OVERLAP_THRESHOLD - 1) / 2;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+struct Coords {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+impl TryFrom<&str> for Coords {
+    type Error = anyhow::Error;
+    fn try_from(src: &str) -> anyhow::Result<Self> {
+        lazy_static! {
+            static ref COORDS_PATTERN: Regex =
+                Regex::new("^(?P<x>0|-?[1-9][0-9]*),(?P<y>0|-?[1-9][0-9]*),(?P<z>0|-?[1-9][0-9]*)$").unwrap();
+        }
+        let captures = COORDS_PATTERN
+            .captures(src)
+            .ok_or_else(|| anyhow::anyhow!("“{}” is not a valid beacon location", src))?;
+        let x = captures.name("x").unwrap().as_str().parse::<i32>()?;
+        let y = captures.name("y").unwrap().as_str().parse::<i32>()?;
+        let z = captures.name("z").unwrap().as_str().parse::<i32>()?;
+        Ok(Coords { x, y, z })
+    }
+}
+impl Add for Coords {
+    type Output = Coords;
+    fn add(self, rhs: Self) -> Self::Output {
+        Coords {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+impl Sub for Coords {
+    type Output = Coords;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Coords {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
+        }
+    }
+}
+impl Coords {
+    /// Returns this point as seen from one of the 24 possible scanner orientations.
+    ///
+    /// Orientations `0..24` are built as one of 6 facings (which way the scanner's x-axis points), followed by one
+    /// of 4 quarter-turns around that axis. Orientation 0 is the identity.
+    fn rotate(&self, orientation: usize) -> Coords {
+        let Coords { x, y, z } = *self;
+        let (x, y, z) = match orientation / 4 {
+            0 => (x, y, z),
+            1 => (-x, -y, z),
+            2 => (y, -x, z),
+            3 => (-y, x, z),
+            4 => (z, y, -x),
+            _ => (-z, y, x),
+        };
+        let (y, z) = match orientation % 4 {
+            0 => (y, z),
+            1 => (-z, y),
+            2 => (-y, -z),
+            _ => (z, -y),
+        };
+        Coords { x, y, z }
+    }
+
+    fn manhattan(&self, other: &Coords) -> i32 {
+        (self.x - other.x).abs() + (self.y - other.y).abs() + (self.z - other.z).abs()
+    }
+
+    /// The square of the straight-line distance between two points. (Unlike the points themselves, this is the
+    /// same no matter which way a scanner is facing.)
+    fn distance_squared(&self, other: &Coords) -> i64 {
+        let Coords { x, y, z } = *self - *other;
+        [x, y, z]
+            .into_iter()
+            .map(|delta| i64::from(delta) * i64::from(delta))
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Scanner {
+    id: usize,
+    beacons: Vec<Coords>,
+    // The squared distances between every pair of beacons, sorted.
+    fingerprint: Vec<i64>,
+}
+impl Scanner {
+    fn parse(lines: &mut impl Iterator<Item = String>) -> anyhow::Result<Option<Scanner>> {
+        lazy_static! {
+            static ref HEADER_PATTERN: Regex = Regex::new("^--- scanner (?P<id>0|[1-9][0-9]*) ---$").unwrap();
+        }
+        let header = match lines.find(|line| !line.is_empty()) {
+            None => return Ok(None),
+            Some(line) => line,
+        };
+        let captures = HEADER_PATTERN
+            .captures(&header)
+            .ok_or_else(|| anyhow::anyhow!("Expected a scanner header; saw “{}”", header))?;
+        let id = captures.name("id").unwrap().as_str().parse::<usize>()?;
+        let beacons = lines
+            .take_while(|line| !line.is_empty())
+            .map(|line| Coords::try_from(line.as_str()))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(|err| anyhow::anyhow!("scanner {id}: {err}"))?;
+        Ok(Some(Scanner::from_beacons(id, beacons)))
+    }
+
+    /// Makes a scanner directly from its id and the beacon positions it reports (relative to itself).
+    fn from_beacons(id: usize, beacons: impl IntoIterator<Item = Coords>) -> Scanner {
+        let beacons = beacons.into_iter().collect::<Vec<_>>();
+        let mut fingerprint = pairs(&beacons)
+            .map(|(first, second)| first.distance_squared(second))
+            .collect::<Vec<_>>();
+        fingerprint.sort_unstable();
+        Scanner {
+            id,
+            beacons,
+            fingerprint,
+        }
+    }
+
+    /// The number of beacon-to-beacon distances the two scanners both see (counting repeats as often as both see
+    /// them).
+    ///
+    /// Distances don't depend on where a scanner is or which way it faces, so this is a cheap check that can be
+    /// made before trying to line the scanners up: if they share 12 beacons, they share at least the 66 distances
+    /// between those beacons.
+    fn overlap_score(&self, other: &Scanner) -> usize {
+        let (mut mine, mut theirs) = (self.fingerprint.iter().peekable(), other.fingerprint.iter().peekable());
+        let mut score = 0;
+        while let (Some(a), Some(b)) = (mine.peek(), theirs.peek()) {
+            match a.cmp(b) {
+                Ordering::Less => {
+                    mine.next();
+                }
+                Ordering::Greater => {
+                    theirs.next();
+                }
+                Ordering::Equal => {
+                    score += 1;
+                    mine.next();
+                    theirs.next();
+                }
+            }
+        }
+        score
+    }
+
+    /// Tries to line this scanner up against a set of beacons with known (absolute) positions. On success, returns
+    /// the scanner's absolute position along with its beacons translated into absolute coordinates.
+    fn align(&self, known: &[Coords]) -> Option<(Coords, Vec<Coords>)> {
+        (0..24).find_map(|orientation| {
+            let rotated = self.beacons.iter().map(|b| b.rotate(orientation)).collect::<Vec<_>>();
+            let mut offset_counts: AHashMap<Coords, usize> = AHashMap::new();
+            for k in known {
+                for r in rotated.iter() {
+                    *offset_counts.entry(*k - *r).or_default() += 1;
+                }
+            }
+            offset_counts
+                .into_iter()
+                .find(|&(_, count)| count >= OVERLAP_THRESHOLD)
+                .map(|(offset, _)| (offset, rotated.into_iter().map(|r| r + offset).collect::<Vec<_>>()))
+        })
+    }
+}
+
+#[derive(Debug)]
+struct UnderSea {
+    scanner_positions: Vec<Coords>,
+    beacons: AHashSet<Coords>,
+}
+impl UnderSea {
+    /// Places every scanner relative to the first one, collecting the full set of beacons along the way.
+    fn assemble(scanners: &[Scanner]) -> anyhow::Result<UnderSea> {
+        let first = scanners
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("Must have at least one scanner"))?;
+        let mut placed: Vec<Option<(Coords, Vec<Coords>)>> = vec![None; scanners.len()];
+        placed[0] = Some((Coords::default(), first.beacons.clone()));
+        let mut to_check = VecDeque::from([0]);
+        while let Some(anchor) = to_check.pop_front() {
+            let known = placed[anchor].as_ref().unwrap().1.clone();
+            for (idx, scanner) in scanners.iter().enumerate() {
+                if placed[idx].is_none() && scanners[anchor].overlap_score(scanner) >= OVERLAP_DISTANCES {
+                    if let Some(alignment) = scanner.align(&known) {
+                        placed[idx] = Some(alignment);
+                        to_check.push_back(idx);
+                    }
+                }
+            }
+        }
+
+        let mut scanner_positions = vec![];
+        let mut beacons = AHashSet::new();
+        for (idx, placement) in placed.into_iter().enumerate() {
+            let (position, absolute_beacons) =
+                placement.ok_or_else(|| anyhow::anyhow!("Scanner {} overlaps no others", scanners[idx].id))?;
+            scanner_positions.push(position);
+            beacons.extend(absolute_beacons);
+        }
+        Ok(UnderSea {
+            scanner_positions,
+            beacons,
+        })
+    }
+
+    fn beacon_count(&self) -> usize {
+        self.beacons.len()
+    }
+
+    fn max_scanner_distance(&self) -> i32 {
+        pairs(&self.scanner_positions)
+            .map(|(first, second)| first.manhattan(second))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+fn parse_scanners(lines: impl Iterator<Item = String>) -> anyhow::Result<Vec<Scanner>> {
+    let mut lines = lines;
+    let mut scanners = vec![];
+    while let Some(scanner) = Scanner::parse(&mut lines)? {
+        scanners.push(scanner);
+    }
+    Ok(scanners)
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    let stdin = io::stdin();
+
+    let lines = stdin
+        .lock()
+        .lines()
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read puzzle input from stdin")?;
+    let scanners = parse_scanners(lines.into_iter()).context("Failed to parse puzzle input from stdin")?;
+
+    let sea = UnderSea::assemble(&scanners)?;
+    println!("Part 1: {} beacons", sea.beacon_count());
+    println!(
+        "Part 2: largest distance between scanners is {}",
+        sea.max_scanner_distance()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    static SAMPLE: &[&str] = &[
+        "--- scanner 0 ---",
+        "404,-588,-901",
+        "528,-643,409",
+        "-838,591,734",
+        "390,-675,-793",
+        "-537,-823,-458",
+        "-485,-357,347",
+        "-345,-311,381",
+        "-661,-816,-575",
+        "-876,649,763",
+        "-618,-824,-621",
+        "553,345,-567",
+        "474,580,667",
+        "-447,-329,318",
+        "-584,868,-557",
+        "544,-627,-890",
+        "564,392,-477",
+        "455,729,728",
+        "-892,524,684",
+        "-689,845,-530",
+        "423,-701,434",
+        "7,-33,-71",
+        "630,319,-379",
+        "443,580,662",
+        "-789,900,-551",
+        "459,-707,401",
+        "",
+        "--- scanner 1 ---",
+        "686,422,578",
+        "605,423,415",
+        "515,917,-361",
+        "-336,658,858",
+        "95,138,22",
+        "-476,619,847",
+        "-340,-569,-846",
+        "567,-361,727",
+        "-460,603,-452",
+        "669,-402,600",
+        "729,430,532",
+        "-500,-761,534",
+        "-322,571,750",
+        "-466,-666,-811",
+        "-429,-592,574",
+        "-355,545,-477",
+        "703,-491,-529",
+        "-328,-685,520",
+        "413,935,-424",
+        "-391,539,-444",
+        "586,-435,557",
+        "-364,-763,-893",
+        "807,-499,-711",
+        "755,-354,-619",
+        "553,889,-390",
+        "",
+        "--- scanner 2 ---",
+        "649,640,665",
+        "682,-795,504",
+        "-784,533,-524",
+        "-644,584,-595",
+        "-588,-843,648",
+        "-30,6,44",
+        "-674,560,763",
+        "500,723,-460",
+        "609,671,-379",
+        "-555,-800,653",
+        "-675,-892,-343",
+        "697,-426,-610",
+        "578,704,681",
+        "493,664,-388",
+        "-671,-858,530",
+        "-667,343,800",
+        "571,-461,-707",
+        "-138,-166,112",
+        "-889,563,-600",
+        "646,-828,498",
+        "640,759,510",
+        "-630,509,768",
+        "-681,-892,-333",
+        "673,-379,-804",
+        "-742,-814,-386",
+        "577,-820,562",
+        "",
+        "--- scanner 3 ---",
+        "-589,542,597",
+        "605,-692,669",
+        "-500,565,-823",
+        "-660,373,557",
+        "-458,-679,-417",
+        "-488,449,543",
+        "-626,468,-788",
+        "338,-750,-386",
+        "528,-832,-391",
+        "562,-778,733",
+        "-938,-730,414",
+        "543,643,-506",
+        "-524,371,-870",
+        "407,773,750",
+        "-104,29,83",
+        "378,-903,-323",
+        "-778,-728,485",
+        "426,699,580",
+        "-438,-605,-362",
+        "-469,-447,-387",
+        "509,732,623",
+        "647,635,-688",
+        "-868,-804,481",
+        "614,-800,639",
+        "595,780,-596",
+        "",
+        "--- scanner 4 ---",
+        "727,592,562",
+        "-293,-554,779",
+        "441,611,-461",
+        "-714,465,-776",
+        "-743,427,-804",
+        "-660,-479,-426",
+        "832,-632,460",
+        "927,-485,-438",
+        "408,393,-506",
+        "466,436,-512",
+        "110,16,151",
+        "-258,-428,682",
+        "-393,719,612",
+        "-211,-452,876",
+        "808,-476,-593",
+        "-575,615,604",
+        "-485,667,467",
+        "-680,325,-822",
+        "-627,-443,-432",
+        "872,-547,-609",
+        "833,512,582",
+        "807,604,487",
+        "839,-516,451",
+        "891,-625,532",
+        "-652,-548,-490",
+        "30,-46,-14",
+    ];
+
+    fn sample_scanners() -> Vec<Scanner> {
+        parse_scanners(SAMPLE.iter().map(|s| String::from(*s))).unwrap()
+    }
+
+    #[test_case(&["--- scanner 0 ---", "1,2,3", "", "--- scanner 3 ---", "4,5"] => "scanner 3: “4,5” is not a valid beacon location"; "count error")]
+    #[test_case(&["--- scanner 7 ---", "1,99999999999,3"] => "scanner 7: number too large to fit in target type"; "integer error")]
+    #[test_case(&["--- scanner x ---"] => "Expected a scanner header; saw “--- scanner x ---”"; "bad header")]
+    fn parse_error(lines: &[&str]) -> String {
+        parse_scanners(lines.iter().map(|s| String::from(*s)))
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn rotations_are_distinct() {
+        let pt = Coords { x: 1, y: 2, z: 3 };
+        let rotated = (0..24).map(|o| pt.rotate(o)).collect::<AHashSet<_>>();
+        assert_eq!(rotated.len(), 24);
+    }
+
+    #[test_case((0, 0, 0), (0, 0, 0) => 0; "same point")]
+    #[test_case((1105, -1205, 1229), (-92, -2380, -20) => 3621; "sample scanners 2 and 3")]
+    #[test_case((-92, -2380, -20), (1105, -1205, 1229) => 3621; "symmetric")]
+    fn manhattan(a: (i32, i32, i32), b: (i32, i32, i32)) -> i32 {
+        let a = Coords { x: a.0, y: a.1, z: a.2 };
+        let b = Coords { x: b.0, y: b.1, z: b.2 };
+        a.manhattan(&b)
+    }
+
+    #[test]
+    fn align() {
+        let local = (0..12)
+            .map(|n| Coords {
+                x: n * n,
+                y: 3 * n - 7,
+                z: 100 - n * n * n,
+            })
+            .collect::<Vec<_>>();
+        let offset = Coords { x: 10, y: -20, z: 30 };
+        let known = local.iter().map(|pt| pt.rotate(9) + offset).collect::<Vec<_>>();
+
+        let scanner = Scanner::from_beacons(4, local.clone());
+        assert_eq!(scanner.id, 4);
+        assert_eq!(scanner.align(&known), Some((offset, known.clone())));
+
+        // One beacon short of the threshold isn't enough to believe the scanners overlap.
+        let scanner = Scanner::from_beacons(5, local[1..].iter().copied());
+        assert_eq!(scanner.align(&known), None);
+    }
+
+    #[test]
+    fn overlap_score() {
+        let scanners = sample_scanners();
+        let score = |a: usize, b: usize| scanners[a].overlap_score(&scanners[b]);
+        assert_eq!(scanners[0].fingerprint.len(), 25 * 24 / 2);
+        assert_eq!(score(0, 0), 25 * 24 / 2);
+        // Scanners 0 and 1 share 12 beacons, and so exactly the distances between them.
+        assert_eq!(score(0, 1), OVERLAP_DISTANCES);
+        assert_eq!(score(1, 0), OVERLAP_DISTANCES);
+        // Scanners 0 and 2 don't overlap, and only a few distances match by chance.
+        assert_eq!(score(0, 2), 3);
+    }
+
+    #[test]
+    fn scanner_positions() {
+        let sea = UnderSea::assemble(&sample_scanners()).unwrap();
+        assert_eq!(
+            sea.scanner_positions,
+            vec![
+                Coords { x: 0, y: 0, z: 0 },
+                Coords {
+                    x: 68,
+                    y: -1246,
+                    z: -43
+                },
+                Coords {
+                    x: 1105,
+                    y: -1205,
+                    z: 1229
+                },
+                Coords {
+                    x: -92,
+                    y: -2380,
+                    z: -20
+                },
+                Coords {
+                    x: -20,
+                    y: -1133,
+                    z: 1061
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn part1_sample() {
+        let sea = UnderSea::assemble(&sample_scanners()).unwrap();
+        assert_eq!(sea.beacon_count(), 79);
+    }
+
+    #[test]
+    fn max_scanner_distance() {
+        let sea = UnderSea::assemble(&sample_scanners()).unwrap();
+        assert_eq!(sea.max_scanner_distance(), 3621);
+    }
 }
@@ -3,11 +3,15 @@
 //! Ref: [Advent of Code 2022 Day 11](https://adventofcode.com/2022/day/11)
 //!
 use ahash::AHashMap;
+use num::PrimInt;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use std::error::Error;
-use std::io::{self, BufRead};
+use std::fmt::Debug;
+use std::io::{self, Read};
 use std::iter::Iterator;
+use std::num::ParseIntError;
+use std::str::FromStr;
 
 static MONKEY_PATTERN: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"^Monkey (?P<id>[0-9]+):$").expect("Hand-rolled regex is valid"));
@@ -19,16 +23,23 @@ static TEST_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"^  Test: divisible
 static REACTION_PATTERN: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"^    If (?P<state>true|false): throw to monkey (?P<id>[0-9]+)$").unwrap());
 
+/// The integer type used to track worry levels.
+///
+/// The puzzle's own inputs fit comfortably in an `i64`, but without part 2's modular reduction, repeated
+/// squaring can outgrow even that; a `u128` buys a lot more headroom.
+trait Worry: PrimInt + FromStr<Err = ParseIntError> + Debug {}
+impl<T> Worry for T where T: PrimInt + FromStr<Err = ParseIntError> + Debug {}
+
 #[derive(Debug)]
-enum Operand {
+enum Operand<W> {
     Old,
-    Number(i64),
+    Number(W),
 }
 
 #[derive(Debug)]
-enum Operation {
-    Add(Operand),
-    Multiply(Operand),
+enum Operation<W> {
+    Add(Operand<W>),
+    Multiply(Operand<W>),
 }
 
 #[derive(Debug)]
@@ -38,17 +49,17 @@ struct Reaction {
 }
 
 #[derive(Debug)]
-struct Monkey {
+struct Monkey<W = i64> {
     id: i64,
-    initial_items: Vec<i64>,
-    items: Vec<i64>,
-    operation: Operation,
-    test_divisor: i64,
+    initial_items: Vec<W>,
+    items: Vec<W>,
+    operation: Operation<W>,
+    test_divisor: W,
     reaction: Reaction,
     inspection_count: usize,
 }
 
-impl Monkey {
+impl<W: Worry> Monkey<W> {
     fn reset(&mut self) {
         self.inspection_count = 0;
         self.items = self.initial_items.clone();
@@ -70,14 +81,44 @@ impl From<&str> for RString {
     }
 }
 
-fn parse_monkey(input: &mut impl Iterator<Item = RString>) -> anyhow::Result<Option<Monkey>> {
+/// Wraps an error so that it reports the (1-based) input line that caused it.
+fn at_line(line_number: usize) -> impl FnOnce(anyhow::Error) -> anyhow::Error {
+    move |err| anyhow::anyhow!("line {line_number}: {err}")
+}
+
+/// Fetches the next line of the current chunk, keeping `line_number` in sync with the lines consumed.
+fn next_line(
+    input: &mut impl Iterator<Item = RString>,
+    line_number: &mut usize,
+    expected: &str,
+) -> anyhow::Result<String> {
+    let line = input
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("{expected} expected; saw end-of-chunk"))
+        .map_err(at_line(*line_number + 1))?;
+    *line_number += 1;
+    line.0.map_err(at_line(*line_number))
+}
+
+fn parse_reaction(reaction_line: &str) -> anyhow::Result<(bool, i64)> {
+    let caps = REACTION_PATTERN
+        .captures(reaction_line)
+        .ok_or_else(|| anyhow::anyhow!("Reaction expected; saw \"{reaction_line}\""))?;
+    Ok((&caps["state"] == "true", caps["id"].parse::<i64>()?))
+}
+
+fn parse_monkey<W: Worry>(
+    input: &mut impl Iterator<Item = RString>,
+    line_number: &mut usize,
+) -> anyhow::Result<Option<Monkey<W>>> {
     // Swallow any blank lines
     let first_line = loop {
         let maybe_line = input.next();
         match maybe_line {
             None => return Ok(None),
             Some(rstr) => {
-                let line = rstr.0?;
+                *line_number += 1;
+                let line = rstr.0.map_err(at_line(*line_number))?;
                 if !line.is_empty() {
                     break line;
                 }
@@ -87,71 +128,58 @@ fn parse_monkey(input: &mut impl Iterator<Item = RString>) -> anyhow::Result<Opt
     // Monkey Identifier
     let monkey_id = MONKEY_PATTERN
         .captures(&first_line)
-        .ok_or_else(|| anyhow::anyhow!("Not a monkey ID marker: \"{first_line}\""))?["id"]
-        .parse::<i64>()?;
+        .ok_or_else(|| anyhow::anyhow!("Not a monkey ID marker: \"{first_line}\""))
+        .and_then(|caps| Ok(caps["id"].parse::<i64>()?))
+        .map_err(at_line(*line_number))?;
 
     // Starting Items
-    let item_line = input
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("Item list expected: saw end-of-chunk"))?
-        .0?;
-    let item_string = &ITEMS_PATTERN
+    let item_line = next_line(input, line_number, "Item list")?;
+    let items = ITEMS_PATTERN
         .captures(&item_line)
-        .ok_or_else(|| anyhow::anyhow!("Item list expected: \"{item_line}\""))?["items"];
-    let items = item_string
-        .split(", ")
-        .map(|num| num.parse::<i64>().map_err(anyhow::Error::from))
-        .collect::<anyhow::Result<Vec<i64>>>()?;
+        .ok_or_else(|| anyhow::anyhow!("Item list expected: \"{item_line}\""))
+        .and_then(|caps| {
+            caps["items"]
+                .split(", ")
+                .map(|num| num.parse::<W>().map_err(anyhow::Error::from))
+                .collect::<anyhow::Result<Vec<W>>>()
+        })
+        .map_err(at_line(*line_number))?;
     // Operation
-    let operation_line = input
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("Operation expected; saw end-of-chunk"))?
-        .0?;
-    let caps = OPS_PATTERN
+    let operation_line = next_line(input, line_number, "Operation")?;
+    let operation = OPS_PATTERN
         .captures(&operation_line)
-        .ok_or_else(|| anyhow::anyhow!("Operation expected; saw \"{operation_line}\""))?;
-    let value_str = &caps["val"];
-    let operand = if value_str == "old" {
-        Operand::Old
-    } else {
-        Operand::Number(value_str.parse::<i64>()?)
-    };
-    let operation = match &caps["op"] {
-        "*" => Operation::Multiply(operand),
-        _ => Operation::Add(operand),
-    };
+        .ok_or_else(|| anyhow::anyhow!("Operation expected; saw \"{operation_line}\""))
+        .and_then(|caps| {
+            let value_str = &caps["val"];
+            let operand = if value_str == "old" {
+                Operand::Old
+            } else {
+                Operand::Number(value_str.parse::<W>()?)
+            };
+            Ok(match &caps["op"] {
+                "*" => Operation::Multiply(operand),
+                _ => Operation::Add(operand),
+            })
+        })
+        .map_err(at_line(*line_number))?;
     // Test
-    let test_line = input
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("Test expected; saw end-of-chunk"))?
-        .0?;
+    let test_line = next_line(input, line_number, "Test")?;
     let test_divisor = TEST_PATTERN
         .captures(&test_line)
-        .ok_or_else(|| anyhow::anyhow!("Test expected; saw \"{test_line}\""))?["val"]
-        .parse::<i64>()?;
+        .ok_or_else(|| anyhow::anyhow!("Test expected; saw \"{test_line}\""))
+        .and_then(|caps| Ok(caps["val"].parse::<W>()?))
+        .map_err(at_line(*line_number))?;
     // First Reaction
-    let reaction_line = input
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("Reaction expected; saw end-of-chunk"))?
-        .0?;
-    let caps = REACTION_PATTERN
-        .captures(&reaction_line)
-        .ok_or_else(|| anyhow::anyhow!("Reaction expected; saw \"{reaction_line}\""))?;
-    let first_state_truish = &caps["state"] == "true";
-    let first_target = caps["id"].parse::<i64>()?;
+    let reaction_line = next_line(input, line_number, "Reaction")?;
+    let (first_state_truish, first_target) = parse_reaction(&reaction_line).map_err(at_line(*line_number))?;
     // Second Reaction
-    let reaction_line = input
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("Reaction expected; saw end-of-chunk"))?
-        .0?;
-    let caps = REACTION_PATTERN
-        .captures(&reaction_line)
-        .ok_or_else(|| anyhow::anyhow!("Reaction expected; saw \"{reaction_line}\""))?;
-    let second_state_truish = &caps["state"] == "true";
-    let second_target = caps["id"].parse::<i64>()?;
+    let reaction_line = next_line(input, line_number, "Reaction")?;
+    let (second_state_truish, second_target) = parse_reaction(&reaction_line).map_err(at_line(*line_number))?;
 
     if first_state_truish == second_state_truish {
-        anyhow::bail!("Reactions must have different true/false markers");
+        return Err(at_line(*line_number)(anyhow::anyhow!(
+            "Reactions must have different true/false markers"
+        )));
     }
 
     let reaction = if first_state_truish {
@@ -177,49 +205,76 @@ fn parse_monkey(input: &mut impl Iterator<Item = RString>) -> anyhow::Result<Opt
     }))
 }
 
-struct Barrel {
+struct Barrel<W = i64> {
     // Because the term for a collection of monkeys is _obviously_ a barrel.
-    monkeys: AHashMap<i64, Monkey>,
+    monkeys: AHashMap<i64, Monkey<W>>,
     ids: Vec<i64>, // the sorted list of ids
-    lcm: i64,      // Least common multiple of all the divisors.
+    lcm: W,        // Least common multiple of all the divisors.
 }
 
-fn parse_monkeys(iter: &mut impl Iterator<Item = RString>) -> anyhow::Result<Barrel> {
+fn parse_monkeys<W: Worry>(iter: &mut impl Iterator<Item = RString>) -> anyhow::Result<Barrel<W>> {
     let mut monkeys = AHashMap::new();
+    let mut line_number = 0;
     loop {
-        let monkey = parse_monkey(iter)?;
+        let monkey = parse_monkey(iter, &mut line_number)?;
         match monkey {
             None => break,
             Some(monkey) => {
-                monkeys.insert(monkey.id, monkey);
+                let id = monkey.id;
+                if monkeys.insert(id, monkey).is_some() {
+                    anyhow::bail!("Monkey {id} is described more than once");
+                }
             }
         }
     }
     let mut ids = monkeys.keys().copied().collect::<Vec<_>>();
     ids.sort();
-    let lcm = monkeys.values().map(|monkey| monkey.test_divisor).product();
+    // The monkeys should be numbered 0, 1, 2, and so on; and they can only throw to each other.
+    if let Some(missing) = (0..)
+        .zip(&ids)
+        .find_map(|(expected, &id)| (expected != id).then_some(expected))
+    {
+        anyhow::bail!("Monkey ids should count up from 0 with no gaps, but there's no monkey {missing}");
+    }
+    for id in ids.iter() {
+        let reaction = &monkeys[id].reaction;
+        for target in [reaction.truish, reaction.falsish] {
+            if !monkeys.contains_key(&target) {
+                anyhow::bail!("Monkey {id} throws to monkey {target}, which doesn't exist");
+            }
+        }
+    }
+    let lcm = monkeys.values().fold(W::one(), |acc, monkey| acc * monkey.test_divisor);
     Ok(Barrel { monkeys, ids, lcm })
 }
 
-impl Barrel {
-    fn round(&mut self, worry_divisor: Option<i64>) {
+impl<W: Worry> FromStr for Barrel<W> {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_monkeys(&mut s.lines().map(RString::from))
+    }
+}
+
+impl<W: Worry> Barrel<W> {
+    fn round(&mut self, worry_divisor: Option<W>) {
+        // Thrown items are gathered here, and handed over to their targets once per monkey rather than once per
+        // item. (The vectors are reused from monkey to monkey, so their allocations are too.)
+        let mut to_truish = Vec::new();
+        let mut to_falsish = Vec::new();
         for monkey_id in self.ids.iter() {
-            let monkey = self.monkeys.get(monkey_id).unwrap();
-            let items = monkey.items.clone(); // This gets cloned so we can keep it and let the monkey ref get dropped.
-            for item in items {
-                // For Rust mutability/ownership reasons, we need to get the monkey from the hash table each
-                // iteration. (We get a mutable ref to our target monkey at the bottom of the loop; in order to do
-                // that, all immutable refs need to be out of scope, which means we can't hold the current monkey
-                // between iterations.)
-                let monkey = self.monkeys.get(monkey_id).unwrap();
+            let monkey = self.monkeys.get_mut(monkey_id).unwrap();
+            let mut items = std::mem::take(&mut monkey.items);
+            monkey.inspection_count += items.len();
+            for item in items.drain(..) {
                 let worry_level = match &monkey.operation {
                     Operation::Add(operand) => match operand {
                         Operand::Old => item + item,
-                        Operand::Number(v) => item + v,
+                        Operand::Number(v) => item + *v,
                     },
                     Operation::Multiply(operand) => match operand {
                         Operand::Old => item * item,
-                        Operand::Number(v) => item * v,
+                        Operand::Number(v) => item * *v,
                     },
                 };
                 let adjusted_worry = match worry_divisor {
@@ -227,17 +282,18 @@ impl Barrel {
                     None => worry_level % self.lcm,
                 };
 
-                let target = if adjusted_worry % monkey.test_divisor == 0 {
-                    monkey.reaction.truish
+                if (adjusted_worry % monkey.test_divisor).is_zero() {
+                    to_truish.push(adjusted_worry);
                 } else {
-                    monkey.reaction.falsish
-                };
-                let target = self.monkeys.get_mut(&target).unwrap();
-                target.items.push(adjusted_worry);
+                    to_falsish.push(adjusted_worry);
+                }
             }
-            let monkey = self.monkeys.get_mut(monkey_id).unwrap();
-            monkey.inspection_count += monkey.items.len();
-            monkey.items.clear();
+            // Give the monkey back its (now empty) list, so that its allocation gets reused in later rounds.
+            monkey.items = items;
+
+            let Reaction { truish, falsish } = monkey.reaction;
+            self.monkeys.get_mut(&truish).unwrap().items.append(&mut to_truish);
+            self.monkeys.get_mut(&falsish).unwrap().items.append(&mut to_falsish);
         }
     }
 
@@ -256,15 +312,16 @@ impl Barrel {
     }
 }
 
-fn part1(input: &mut Barrel) -> usize {
+fn part1<W: Worry>(input: &mut Barrel<W>) -> usize {
     // 20 rounds
+    let relief = W::from(3).expect("every worry type can hold a 3");
     for _ in 0..20 {
-        input.round(Some(3));
+        input.round(Some(relief));
     }
     input.monkey_business()
 }
 
-fn part2(barrel: &mut Barrel) -> usize {
+fn part2<W: Worry>(barrel: &mut Barrel<W>) -> usize {
     // 10,000 rounds
     for _ in 0..10000 {
         barrel.round(None);
@@ -275,8 +332,9 @@ fn part2(barrel: &mut Barrel) -> usize {
 fn main() -> anyhow::Result<()> {
     let stdin = io::stdin();
 
-    let mut input_iter = stdin.lock().lines().map(RString::from);
-    let mut barrel = parse_monkeys(&mut input_iter)?;
+    let mut input = String::new();
+    stdin.lock().read_to_string(&mut input)?;
+    let mut barrel: Barrel = input.parse()?;
 
     println!("Part1: {}", part1(&mut barrel));
     barrel.reset();
@@ -319,17 +377,101 @@ mod tests {
             If false: throw to monkey 1
     "};
 
+    #[test]
+    fn parse_sample() {
+        let barrel = SAMPLE.parse::<Barrel>().unwrap();
+        assert_eq!(barrel.monkeys.len(), 4);
+        assert_eq!(barrel.ids, vec![0, 1, 2, 3]);
+        assert_eq!(barrel.lcm, 23 * 19 * 13 * 17);
+    }
+
     #[test]
     fn part1_sample() {
-        let mut iter = SAMPLE.lines().map(RString::from);
-        let mut monkeys = parse_monkeys(&mut iter).unwrap();
+        let mut monkeys: Barrel = SAMPLE.parse().unwrap();
         assert_eq!(part1(&mut monkeys), 10605);
     }
 
     #[test]
     fn part2_sample() {
-        let mut iter = SAMPLE.lines().map(RString::from);
-        let mut barrel = parse_monkeys(&mut iter).unwrap();
+        let mut barrel: Barrel = SAMPLE.parse().unwrap();
         assert_eq!(part2(&mut barrel), 2713310158);
     }
+
+    #[test]
+    fn wide_worry() {
+        // Squaring 10^10 overflows an i64, but not a u128.
+        let src = indoc::indoc! {"
+            Monkey 0:
+              Starting items: 10000000000
+              Operation: new = old * old
+              Test: divisible by 7
+                If true: throw to monkey 1
+                If false: throw to monkey 1
+
+            Monkey 1:
+              Starting items: 1
+              Operation: new = old * 1
+              Test: divisible by 11
+                If true: throw to monkey 0
+                If false: throw to monkey 0
+        "};
+        let mut barrel: Barrel<u128> = src.parse().unwrap();
+        barrel.round(Some(3));
+        assert_eq!(barrel.monkeys[&0].items, vec![0, 11_111_111_111_111_111_111]);
+        assert_eq!(barrel.lcm, 77);
+    }
+
+    #[test]
+    fn malformed_operation() {
+        let bad_sample = SAMPLE.replace("new = old + 6", "new = old ^ 6");
+        let err = bad_sample.parse::<Barrel>().err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "line 10: Operation expected; saw \"  Operation: new = old ^ 6\""
+        );
+    }
+
+    #[test]
+    fn malformed_reaction() {
+        let bad_sample = SAMPLE.replace(
+            "If false: throw to monkey 3\n\nMonkey 1",
+            "If true: throw to monkey 3\n\nMonkey 1",
+        );
+        let err = bad_sample.parse::<Barrel>().err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "line 6: Reactions must have different true/false markers"
+        );
+    }
+
+    #[test]
+    fn missing_throw_target() {
+        let bad_sample = SAMPLE.replace("If false: throw to monkey 3", "If false: throw to monkey 7");
+        let err = bad_sample.parse::<Barrel>().err().unwrap();
+        assert_eq!(err.to_string(), "Monkey 0 throws to monkey 7, which doesn't exist");
+    }
+
+    #[test]
+    fn monkey_id_gap() {
+        let bad_sample = SAMPLE.replace("Monkey 1:", "Monkey 5:");
+        let err = bad_sample.parse::<Barrel>().err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Monkey ids should count up from 0 with no gaps, but there's no monkey 1"
+        );
+    }
+
+    #[test]
+    fn duplicate_monkey() {
+        let bad_sample = SAMPLE.replace("Monkey 3:", "Monkey 2:");
+        let err = bad_sample.parse::<Barrel>().err().unwrap();
+        assert_eq!(err.to_string(), "Monkey 2 is described more than once");
+    }
+
+    #[test]
+    fn truncated_monkey() {
+        let truncated = SAMPLE.lines().take(26).collect::<Vec<_>>().join("\n");
+        let err = truncated.parse::<Barrel>().err().unwrap();
+        assert_eq!(err.to_string(), "line 27: Reaction expected; saw end-of-chunk");
+    }
 }
@@ -2,10 +2,11 @@
 //!
 //! Ref: [Advent of Code 2022 Day 16](https://adventofcode.com/2022/day/16)
 //!
-use ahash::AHashMap;
 use bimap::BiMap;
+use memo::Memo;
 use once_cell::sync::Lazy;
 use regex::Regex;
+use std::collections::VecDeque;
 use std::io::{self, Read};
 use std::iter::Iterator;
 use std::str::FromStr;
@@ -58,7 +59,8 @@ impl FromStr for InputData {
             };
         }
 
-        for line in s.lines() {
+        // Blank lines, and whitespace around the lines (including CRLF line endings), are ignored.
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
             let vd = line.parse::<ValveDescription>()?;
             if !ids.contains_right(&vd.id) {
                 ids.insert(next_id, vd.id.clone());
@@ -84,8 +86,51 @@ impl FromStr for InputData {
 }
 
 impl InputData {
-    fn valve_id(&self, letters: &str) -> u32 {
-        *self.ids.get_by_right(letters).unwrap()
+    fn valve_id(&self, letters: &str) -> anyhow::Result<u32> {
+        self.ids
+            .get_by_right(letters)
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("No valve named {letters}"))
+    }
+
+    /// The flow rate of the valve called `name`, if there is one.
+    #[cfg(test)]
+    fn rate_of(&self, name: &str) -> Option<i32> {
+        self.valve_id(name).ok().map(|id| self.rates[id as usize])
+    }
+
+    /// The ids of the valves with some flow, in increasing order. (Those are the only ones worth opening, or
+    /// worth travelling to.)
+    fn useful_valves(&self) -> Vec<u32> {
+        (0..)
+            .zip(self.rates.iter())
+            .filter_map(|(id, &rate)| (rate > 0).then_some(id))
+            .collect()
+    }
+
+    /// The number of valves with some flow.
+    fn useful_count(&self) -> usize {
+        self.rates.iter().filter(|&&rate| rate > 0).count()
+    }
+
+    /// The names of the valves reachable through the tunnels from the valve called `name`, in the order the input
+    /// listed them. (An unknown valve has no tunnels.)
+    #[cfg(test)]
+    fn tunnels_of(&self, name: &str) -> Vec<String> {
+        self.valve_id(name).map_or_else(
+            |_| vec![],
+            |id| {
+                self.tunnels[id as usize]
+                    .iter()
+                    .map(|tunnel| {
+                        self.ids
+                            .get_by_left(tunnel)
+                            .expect("tunnels lead to known valves")
+                            .clone()
+                    })
+                    .collect()
+            },
+        )
     }
 }
 
@@ -120,18 +165,17 @@ struct Chambers {
 // calculation. The problem is that the recursive descent revisits so many states. The solution to that issue
 // is memoization. A cache is maintained to avoid re-working the same problem over and over.
 //
-// Against my AoC input, the cache grows to 1,006,005 entries for part 1, and 20,380,766 entries for part 2.
-// Run times (for me) are about 0.76 seconds for part 1, and 22 seconds for part 2.
+// Against my AoC input, the cache grows to 1,006,005 entries for part 1, and 20,380,766 entries for the
+// two-player run of part 2. Run times (for me) are about 0.76 seconds for part 1, and 22 seconds for that
+// two-player run.
 //
-// A note about the additional player for part 2: This solution runs player 2 _after_ player 1 has completed
-// his run entirely (resetting the clock, but not the valves). I don't know why this works. It seems to me
-// there should be patterns where the interactions between the players would help each other, but the internet
-// is completely convinced that's not the case. Clearly the AoC validator agrees with the Internet. I'd still
-// like to see a real proof, though.
+// A note about the additional player for part 2: `score` can run player 2 _after_ player 1 has completed his
+// run entirely (resetting the clock, but not the valves). That works, but it's slow, and it's not obvious why
+// it works. Part 2 now uses `best_disjoint_pair` instead, below.
 
 fn score(
     data: &Chambers,
-    cache: &mut AHashMap<(u32, i32, ValveData, u32), usize>,
+    cache: &mut Memo<(u32, i32, ValveData, u32), usize>,
     location: u32,
     time_left: i32,
     valves: &ValveData,
@@ -159,54 +203,87 @@ fn score(
         return 0;
     }
 
-    // Check the cache. If we have a hit, don't actually do any new work.
-    let valves = valves.clone();
-    let maybe_score = cache.get(&(location, time_left, valves.clone(), extra_particpants));
-    if let Some(&previous_calculation) = maybe_score {
-        return previous_calculation;
-    }
+    // Check the cache. If we have a hit, don't actually do any new work; otherwise the best score computed here
+    // is added to the cache.
+    cache.get_or_compute((location, time_left, valves.clone(), extra_particpants), |cache| {
+        let location = location as usize;
+        // Run through all the possibilities I have in this chamber: opening a valve (if it's not already open and
+        // if it has a positive flow rate); or travelling down one of this chamber's tunnels.
+        let mut best_score = 0;
+        if valves.0[location] == ValveState::Closed && data.rates[location] > 0 {
+            // Make a new "valves" vector with this valve marked open
+            let mut new_valves = valves.clone();
+            new_valves.0[location] = ValveState::Open;
+            // And then try again: our score is now the sum of
+            // * this value open for the remaining time
+            // * the best score from here given this new valve state
+            best_score = best_score.max(
+                ((time_left - 1) * data.rates[location]) as usize
+                    + score(
+                        data,
+                        cache,
+                        location as u32,
+                        time_left - 1,
+                        &new_valves,
+                        extra_particpants,
+                    ),
+            );
+        }
+        for next_loc in data.tunnels[location].iter() {
+            // All the travelling. The best score from here is the best score from the connected location, but
+            // with a bit less time
+            best_score = best_score.max(score(data, cache, *next_loc, time_left - 1, valves, extra_particpants));
+        }
+        best_score
+    })
+}
 
-    let location = location as usize;
-    // Run through all the possibilities I have in this chamber: opening a valve (if it's not already open and
-    // if it has a positive flow rate); or travelling down one of this chamber's tunnels.
-    let mut best_score = 0;
-    if valves.0[location] == ValveState::Closed && data.rates[location] > 0 {
-        // Make a new "valves" vector with this valve marked open
-        let mut new_valves = valves.clone();
-        new_valves.0[location] = ValveState::Open;
-        // And then try again: our score is now the sum of
-        // * this value open for the remaining time
-        // * the best score from here given this new valve state
-        best_score = best_score.max(
-            ((time_left - 1) * data.rates[location]) as usize
-                + score(
-                    data,
-                    cache,
-                    location as u32,
-                    time_left - 1,
-                    &new_valves,
-                    extra_particpants,
-                ),
-        );
-    }
-    for next_loc in data.tunnels[location].iter() {
-        // All the travelling. The best score from here is the best score from the connected location, but
-        // with a bit less time
-        best_score = best_score.max(score(data, cache, *next_loc, time_left - 1, &valves, extra_particpants));
+// Once the scores are all in the cache, the path that produced the best score can be recovered by walking forward
+// from the start: at each step, take whichever choice yields the score we're expecting. (All the scores needed for
+// that walk are already cached, so this is cheap.) The result is the list of valves opened, along with the minute
+// (counted from 1, as in the puzzle description) each was opened.
+fn schedule(
+    data: &Chambers,
+    cache: &mut Memo<(u32, i32, ValveData, u32), usize>,
+    initial_valves: &ValveData,
+) -> Vec<(u32, i32)> {
+    let mut result = vec![];
+    let mut location = data.location;
+    let mut time_left = data.time;
+    let mut valves = initial_valves.clone();
+    while time_left > 0 {
+        let best = score(data, cache, location, time_left, &valves, 0);
+        if best == 0 {
+            break;
+        }
+        let loc = location as usize;
+        if valves.0[loc] == ValveState::Closed && data.rates[loc] > 0 {
+            let mut new_valves = valves.clone();
+            new_valves.0[loc] = ValveState::Open;
+            let opened_score = ((time_left - 1) * data.rates[loc]) as usize
+                + score(data, cache, location, time_left - 1, &new_valves, 0);
+            if opened_score == best {
+                result.push((location, data.time - time_left + 1));
+                valves = new_valves;
+                time_left -= 1;
+                continue;
+            }
+        }
+        location = *data.tunnels[loc]
+            .iter()
+            .find(|&&next_loc| score(data, cache, next_loc, time_left - 1, &valves, 0) == best)
+            .expect("some choice should produce the best score");
+        time_left -= 1;
     }
-
-    // Add our new best score into the cache
-    cache.insert((location as u32, time_left, valves, extra_particpants), best_score);
-    // And done.
-    best_score
+    result
 }
 
-fn score_part1(data: &InputData) -> usize {
+fn score_part1(data: &InputData, start: &str) -> anyhow::Result<(usize, Vec<(u32 /*valve*/, i32 /*minute*/)>)> {
     let initial_valves = ValveData::from(data);
-    let mut cache = AHashMap::new();
+    let mut cache = Memo::new();
     let scoring_run_setup = Chambers {
         time: 30,
-        location: data.valve_id("AA"),
+        location: data.valve_id(start)?,
         rates: data.rates.clone(),
         tunnels: data.tunnels.clone(),
     };
@@ -221,36 +298,100 @@ fn score_part1(data: &InputData) -> usize {
 
     println!("Cache had {} entries", cache.len());
 
-    result
+    let openings = schedule(&scoring_run_setup, &mut cache, &initial_valves);
+    Ok((result, openings))
 }
 
+// Part 2, by a different route. Two agents working at once never open the same valve, and otherwise they
+// don't interact at all: each one's contribution depends only on his own path. So the best team score is the best
+// single-agent score using only the valves in some set S, plus the best single-agent score using only the valves
+// outside S, maximized over all S.
+//
+// To get those single-agent scores, walk every possible sequence of valve openings (travelling between useful
+// valves by their shortest distances, so there are no wasted moves), recording the best pressure released for
+// each exact set of opened valves. Then fold each set's best into all of its supersets, so that `best[S]` becomes
+// the best score for an agent restricted to S.
+//
+// The table has an entry for every subset of the useful valves, so it doubles in size with each one; past
+// `MAX_USEFUL_VALVES` (the puzzle inputs have 15) it would take more memory than is reasonable.
+fn best_disjoint_pair(data: &InputData, start: &str) -> anyhow::Result<usize> {
+    const TIME: i32 = 26;
+    const MAX_USEFUL_VALVES: usize = 20;
+    let start = data.valve_id(start)? as usize;
+    let useful_count = data.useful_count();
+    anyhow::ensure!(
+        useful_count <= MAX_USEFUL_VALVES,
+        "Too many working valves ({useful_count}) to split between two agents; the limit is {MAX_USEFUL_VALVES}"
+    );
+    let useful = data
+        .useful_valves()
+        .into_iter()
+        .map(|id| id as usize)
+        .collect::<Vec<_>>();
+
+    // Shortest distances from each valve to every other, by breadth-first search through the tunnels.
+    let distances_from = |origin: usize| -> Vec<i32> {
+        let mut dist = vec![i32::MAX; data.rates.len()];
+        dist[origin] = 0;
+        let mut queue = VecDeque::from([origin]);
+        while let Some(here) = queue.pop_front() {
+            for &next in data.tunnels[here].iter() {
+                let next = next as usize;
+                if dist[next] == i32::MAX {
+                    dist[next] = dist[here] + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+        dist
+    };
+    let from_start = distances_from(start);
+    let between = useful.iter().map(|&id| distances_from(id)).collect::<Vec<_>>();
+
+    let mut best = vec![0; 1 << useful.len()];
+    // Each stack entry: (index into `useful` of where we are, or None for the start; time left; opened set; score)
+    let mut stack = vec![(None, TIME, 0_usize, 0_usize)];
+    while let Some((here, time_left, opened, pressure)) = stack.pop() {
+        best[opened] = best[opened].max(pressure);
+        let dist = here.map_or(&from_start, |idx: usize| &between[idx]);
+        for (idx, &id) in useful.iter().enumerate() {
+            if opened & (1 << idx) != 0 || dist[id] == i32::MAX {
+                continue;
+            }
+            let remaining = time_left - dist[id] - 1;
+            if remaining > 0 {
+                let released = (remaining * data.rates[id]) as usize;
+                stack.push((Some(idx), remaining, opened | (1 << idx), pressure + released));
+            }
+        }
+    }
+
+    for bit in 0..useful.len() {
+        for set in 0..best.len() {
+            if set & (1 << bit) != 0 {
+                best[set] = best[set].max(best[set ^ (1 << bit)]);
+            }
+        }
+    }
+
+    let everything = best.len() - 1;
+    Ok((0..best.len())
+        .map(|mine| best[mine] + best[everything ^ mine])
+        .max()
+        .unwrap_or(0))
+}
+
+/// Where the puzzle says we start.
+const START_VALVE: &str = "AA";
+
 fn part1(input: &str) -> anyhow::Result<usize> {
     let data = input.parse::<InputData>()?;
-    Ok(score_part1(&data))
+    Ok(score_part1(&data, START_VALVE)?.0)
 }
 
 fn part2(input: &str) -> anyhow::Result<usize> {
     let data = input.parse::<InputData>()?;
-    let initial_valves = ValveData::from(&data);
-    let mut cache = AHashMap::new();
-    let scoring_run_setup = Chambers {
-        time: 26,
-        location: data.valve_id("AA"),
-        rates: data.rates,
-        tunnels: data.tunnels,
-    };
-    let result = Ok(score(
-        &scoring_run_setup,
-        &mut cache,
-        scoring_run_setup.location,
-        scoring_run_setup.time,
-        &initial_valves,
-        1,
-    ));
-
-    println!("Cache had {} entries", cache.len());
-
-    result
+    best_disjoint_pair(&data, START_VALVE)
 }
 
 fn main() -> anyhow::Result<()> {
@@ -287,8 +428,141 @@ mod tests {
         assert_eq!(part1(SAMPLE).unwrap(), 1651);
     }
 
+    #[test]
+    fn part1_schedule() {
+        let data = SAMPLE.parse::<InputData>().unwrap();
+        let (score, openings) = score_part1(&data, "AA").unwrap();
+        assert_eq!(score, 1651);
+        let named = openings
+            .iter()
+            .map(|(valve, minute)| (data.ids.get_by_left(valve).unwrap().as_str(), *minute))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            named,
+            vec![("DD", 2), ("BB", 5), ("JJ", 9), ("HH", 17), ("EE", 21), ("CC", 24)]
+        );
+        let total = openings
+            .iter()
+            .map(|&(valve, minute)| data.rates[valve as usize] as usize * (30 - minute) as usize)
+            .sum::<usize>();
+        assert_eq!(total, score);
+    }
+
+    #[test]
+    fn other_start() {
+        let data = SAMPLE.parse::<InputData>().unwrap();
+        let (best, openings) = score_part1(&data, "JJ").unwrap();
+        assert_eq!(best, 1807);
+        // Standing at JJ, the first thing to do is open it.
+        assert_eq!(openings[0], (data.valve_id("JJ").unwrap(), 1));
+
+        let setup = Chambers {
+            time: 26,
+            location: data.valve_id("JJ").unwrap(),
+            rates: data.rates.clone(),
+            tunnels: data.tunnels.clone(),
+        };
+        let sequential = score(
+            &setup,
+            &mut Memo::new(),
+            setup.location,
+            setup.time,
+            &ValveData::from(&data),
+            1,
+        );
+        assert_eq!(best_disjoint_pair(&data, "JJ").unwrap(), sequential);
+    }
+
+    #[test]
+    fn lookup_by_name() {
+        let data = SAMPLE.parse::<InputData>().unwrap();
+        assert_eq!(data.rate_of("DD"), Some(20));
+        assert_eq!(data.rate_of("AA"), Some(0));
+        assert_eq!(data.rate_of("ZZ"), None);
+        assert_eq!(data.tunnels_of("AA"), vec!["DD", "II", "BB"]);
+        assert_eq!(data.tunnels_of("HH"), vec!["GG"]);
+        assert!(data.tunnels_of("ZZ").is_empty());
+    }
+
+    #[test]
+    fn padded_input() {
+        let padded = format!(
+            "\n{}\n  \n",
+            SAMPLE.lines().map(|line| format!("  {line}\t\r\n")).collect::<String>()
+        );
+        let data = padded.parse::<InputData>().unwrap();
+        let expected = SAMPLE.parse::<InputData>().unwrap();
+        assert_eq!(data.ids, expected.ids);
+        assert_eq!(data.rates, expected.rates);
+        assert_eq!(data.tunnels, expected.tunnels);
+        assert_eq!(part1(&format!("{SAMPLE}\n")).unwrap(), 1651);
+    }
+
+    #[test]
+    fn useful_valves() {
+        let data = SAMPLE.parse::<InputData>().unwrap();
+        assert_eq!(data.useful_count(), 6);
+        let mut names = data
+            .useful_valves()
+            .into_iter()
+            .map(|id| data.ids.get_by_left(&id).unwrap().as_str())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(names, ["BB", "CC", "DD", "EE", "HH", "JJ"]);
+    }
+
+    #[test]
+    fn too_many_useful_valves() {
+        let names = (0..21)
+            .map(|idx| format!("A{}", char::from(b'A' + idx)))
+            .collect::<Vec<_>>();
+        let input = names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                let next = &names[(idx + 1) % names.len()];
+                format!("Valve {name} has flow rate=1; tunnel leads to valve {next}\n")
+            })
+            .collect::<String>();
+        let data = input.parse::<InputData>().unwrap();
+        assert_eq!(
+            best_disjoint_pair(&data, "AA").unwrap_err().to_string(),
+            "Too many working valves (21) to split between two agents; the limit is 20"
+        );
+    }
+
+    #[test]
+    fn unknown_start() {
+        let data = SAMPLE.parse::<InputData>().unwrap();
+        assert_eq!(score_part1(&data, "ZZ").unwrap_err().to_string(), "No valve named ZZ");
+        assert_eq!(
+            best_disjoint_pair(&data, "ZZ").unwrap_err().to_string(),
+            "No valve named ZZ"
+        );
+    }
+
     #[test]
     fn part2_sample() {
         assert_eq!(part2(SAMPLE).unwrap(), 1707);
     }
+
+    #[test]
+    fn best_disjoint_pair_matches_sequential() {
+        let data = SAMPLE.parse::<InputData>().unwrap();
+        let setup = Chambers {
+            time: 26,
+            location: data.valve_id("AA").unwrap(),
+            rates: data.rates.clone(),
+            tunnels: data.tunnels.clone(),
+        };
+        let sequential = score(
+            &setup,
+            &mut Memo::new(),
+            setup.location,
+            setup.time,
+            &ValveData::from(&data),
+            1,
+        );
+        assert_eq!(best_disjoint_pair(&data, "AA").unwrap(), sequential);
+    }
 }
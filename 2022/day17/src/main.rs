@@ -81,11 +81,17 @@ impl FromStr for AirJets {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(AirJets(
-            s.chars()
-                .map(AirJet::try_from)
-                .collect::<anyhow::Result<Vec<AirJet>>>()?,
-        ))
+        // Whitespace (like the newline at the end of an input file, or a pattern split over several lines) isn't
+        // part of the pattern.
+        let jets = s
+            .chars()
+            .filter(|ch| !ch.is_whitespace())
+            .map(AirJet::try_from)
+            .collect::<anyhow::Result<Vec<AirJet>>>()?;
+        if jets.is_empty() {
+            anyhow::bail!("No jets in the pattern");
+        }
+        Ok(AirJets(jets))
     }
 }
 
@@ -101,26 +107,25 @@ struct Point {
     row: isize,
 }
 
+/// The visible surface of the tower: for each column, the height of its topmost rock relative to the current floor
+/// (or -1 for a column with no rock at all).
+type SurfaceProfile = [i8; CANVAS_WIDTH as usize];
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct CacheKey {
     rock_idx: u32,
     jet_idx: u32,
-    rocks: Vec<Point>,
+    surface: SurfaceProfile,
 }
 impl CacheKey {
-    fn new(value: &Canvas) -> Self {
-        let mut rocks = value.spots.keys().cloned().collect::<Vec<_>>();
-        rocks.sort_by(|a, b| match a.row.cmp(&b.row) {
-            Ordering::Less => Ordering::Less,
-            Ordering::Equal => a.col.cmp(&b.col),
-            Ordering::Greater => Ordering::Greater,
-        });
-
-        CacheKey {
+    /// The key for the canvas's current state, or `None` if its surface is too tall to describe (in which case
+    /// the state can't be cached).
+    fn new(value: &Canvas) -> Option<Self> {
+        Some(CacheKey {
             rock_idx: u32::try_from(value.rock_idx).expect("rock_idx should fit in a u32"),
             jet_idx: u32::try_from(value.jet_idx).expect("jet_idx should fit in a u32"),
-            rocks,
-        }
+            surface: value.surface_profile()?,
+        })
     }
 }
 
@@ -141,28 +146,45 @@ struct Canvas {
     state: RocksAre,
     floor_offset: usize,
     cache: AHashMap<CacheKey, CacheEntry>,
+    left_gap: isize,
+    height_gap: isize,
 }
 impl Display for Canvas {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(highest_nonempty_row) = self.highest_nonempty_row() {
-            for row in (0..=highest_nonempty_row).rev() {
-                for col in 0..CANVAS_WIDTH {
-                    match self.at_spot(&Point { col, row }) {
-                        Space::Falling => write!(f, "@")?,
-                        Space::Stuck => write!(f, "#")?,
-                        Space::Air => write!(f, ".")?,
-                    }
-                }
-                writeln!(f)?
-            }
+            let floor = isize::try_from(self.floor_offset).expect("floor should fit into an isize");
+            write!(f, "{}", self.render_window(floor, floor + highest_nonempty_row))?;
         }
         Ok(())
     }
 }
 const CANVAS_WIDTH: isize = 7;
+/// How many empty columns the puzzle leaves between the left wall and a newly appearing rock.
+const DEFAULT_LEFT_GAP: isize = 2;
+/// How many empty rows the puzzle leaves between the top of the tower (or the floor) and a newly appearing rock.
+const DEFAULT_HEIGHT_GAP: isize = 3;
 impl Canvas {
     fn new(jets: AirJets) -> Self {
-        Canvas {
+        Canvas::with_spawn_gaps(jets, DEFAULT_LEFT_GAP, DEFAULT_HEIGHT_GAP).expect("the puzzle's gaps should fit")
+    }
+
+    /// Make a canvas whose rocks appear `left_gap` columns from the left wall, with `height_gap` empty rows
+    /// beneath them.
+    ///
+    /// Neither gap may be negative, and every rock must fit between the left gap and the right wall.
+    fn with_spawn_gaps(jets: AirJets, left_gap: isize, height_gap: isize) -> anyhow::Result<Self> {
+        let widest = PATTERNS
+            .iter()
+            .flat_map(|pattern| pattern.iter().map(|&(x, _)| x as isize + 1))
+            .max()
+            .unwrap_or(0);
+        if left_gap < 0 || left_gap + widest > CANVAS_WIDTH {
+            anyhow::bail!("A left gap of {left_gap} doesn't leave room for every rock");
+        }
+        if height_gap < 0 {
+            anyhow::bail!("The height gap may not be negative (was {height_gap})");
+        }
+        Ok(Canvas {
             spots: AHashMap::new(),
             jets,
             jet_idx: 0,
@@ -170,7 +192,19 @@ impl Canvas {
             state: RocksAre::Stopped,
             floor_offset: 0,
             cache: AHashMap::new(),
-        }
+            left_gap,
+            height_gap,
+        })
+    }
+
+    /// The number of jets in the pattern, after which it repeats.
+    fn jet_count(&self) -> usize {
+        self.jets.0.len()
+    }
+
+    /// The number of rock shapes, after which they repeat.
+    fn pattern_count(&self) -> usize {
+        PATTERNS.len()
     }
 
     fn at_spot(&self, key: &Point) -> Space {
@@ -181,11 +215,22 @@ impl Canvas {
         self.spots.keys().max_by_key(|&pt| pt.row).map(|pt| pt.row)
     }
 
+    /// The surface of the tower, or `None` if some column stands taller than a [SurfaceProfile] can hold.
+    fn surface_profile(&self) -> Option<SurfaceProfile> {
+        let mut profile = [-1; CANVAS_WIDTH as usize];
+        for pt in self.spots.keys() {
+            let col = usize::try_from(pt.col).expect("columns should not be negative");
+            let height = i8::try_from(pt.row).ok()?;
+            profile[col] = profile[col].max(height);
+        }
+        Some(profile)
+    }
+
     fn add_new_rock(&mut self) {
-        let row_offset = self.highest_nonempty_row().unwrap_or(-1) + 4;
-        let col_offset = 2;
+        let row_offset = self.highest_nonempty_row().unwrap_or(-1) + self.height_gap + 1;
+        let col_offset = self.left_gap;
         let pattern = &PATTERNS[self.rock_idx];
-        self.rock_idx = (self.rock_idx + 1) % PATTERNS.len();
+        self.rock_idx = (self.rock_idx + 1) % self.pattern_count();
         for &(x, y) in pattern.iter() {
             let col = x as isize;
             let row = y as isize;
@@ -207,7 +252,7 @@ impl Canvas {
 
     fn blow(&mut self) {
         let blow_direction = self.jets.0[self.jet_idx];
-        self.jet_idx = (self.jet_idx + 1) % self.jets.0.len();
+        self.jet_idx = (self.jet_idx + 1) % self.jet_count();
         let delta_x = match blow_direction {
             AirJet::Left => -1,
             AirJet::Right => 1,
@@ -324,11 +369,11 @@ impl Canvas {
 
     fn drop_rock(&mut self, iteration_num: usize) {
         let key = CacheKey::new(self);
-        match self.cache.get(&key) {
+        match key.as_ref().and_then(|key| self.cache.get(key)) {
             Some(entry) => {
                 self.floor_offset += entry.height_delta as usize;
-                self.jet_idx = (self.jet_idx + entry.jet_idx_delta as usize) % self.jets.0.len();
-                self.rock_idx = (self.rock_idx + 1) % PATTERNS.len();
+                self.jet_idx = (self.jet_idx + entry.jet_idx_delta as usize) % self.jet_count();
+                self.rock_idx = (self.rock_idx + 1) % self.pattern_count();
                 self.spots = AHashMap::from_iter(entry.rocks.iter().map(|&point| (point, Rock::Stuck)))
             }
             None => {
@@ -355,34 +400,68 @@ impl Canvas {
                     height_delta: u32::try_from(self.floor_offset - starting_offset)
                         .expect("Height deltas should fit in a u32"),
                     jet_idx_delta: u32::try_from(
-                        (self.jets.0.len() + self.jet_idx - starting_jet_index) % self.jets.0.len(),
+                        (self.jet_count() + self.jet_idx - starting_jet_index) % self.jet_count(),
                     )
                     .expect("Jet index should fit in a u32"),
                     rocks,
                     iter_number: iteration_num,
                     original_floor_offset: starting_offset,
                 };
-                self.cache.insert(key, entry);
+                if let Some(key) = key {
+                    self.cache.insert(key, entry);
+                }
             }
         }
     }
 
+    /// Draw the rows from `bottom` to `top` (inclusive, topmost first), one line per row.
+    ///
+    /// Rows are absolute heights above the original floor, so they stay meaningful after [Canvas::raise_floor]
+    /// has discarded the bottom of the tower. Any discarded rows in the window are drawn as `~`.
+    fn render_window(&self, bottom: isize, top: isize) -> String {
+        let floor = isize::try_from(self.floor_offset).expect("floor should fit into an isize");
+        let mut result = String::new();
+        for row in (bottom..=top).rev().map(|row| row - floor) {
+            for col in 0..CANVAS_WIDTH {
+                result.push(if row < 0 {
+                    '~'
+                } else {
+                    match self.at_spot(&Point { col, row }) {
+                        Space::Falling => '@',
+                        Space::Stuck => '#',
+                        Space::Air => '.',
+                    }
+                });
+            }
+            result.push('\n');
+        }
+        result
+    }
+
     fn height(&self) -> isize {
         self.highest_nonempty_row().unwrap_or(-1)
             + 1
             + isize::try_from(self.floor_offset).expect("floor should fit into an isize")
     }
 
+    /// If the current state has been seen before, returns the number of rocks dropped since then, along with how
+    /// much the floor rose during those drops.
+    fn detect_cycle(&self, iteration_num: usize) -> Option<(usize, usize)> {
+        self.cache.get(&CacheKey::new(self)?).map(|entry| {
+            (
+                iteration_num - entry.iter_number,
+                self.floor_offset - entry.original_floor_offset,
+            )
+        })
+    }
+
     fn height_after(&mut self, iterations: usize) -> isize {
         let mut max_step = iterations;
         let mut num = 0;
         let mut might_repeat = true;
         while num < max_step {
             if might_repeat {
-                let key = CacheKey::new(self);
-                if let Some(entry) = self.cache.get(&key) {
-                    let cycle_repetitions = num - entry.iter_number;
-                    let cycle_height = self.floor_offset - entry.original_floor_offset;
+                if let Some((cycle_repetitions, cycle_height)) = self.detect_cycle(num) {
                     let instantly_consumed = (max_step - num) / cycle_repetitions;
                     self.floor_offset += cycle_height * instantly_consumed;
                     max_step -= instantly_consumed * cycle_repetitions;
@@ -441,4 +520,135 @@ mod tests {
     fn part2_sample() {
         assert_eq!(part2(SAMPLE).unwrap(), 1514285714288);
     }
+
+    #[test]
+    fn padded_input() {
+        let split = format!("  {}\r\n{}\n\n", &SAMPLE[..20], &SAMPLE[20..]);
+        for text in [format!("{SAMPLE}\n"), split] {
+            assert_eq!(text.parse::<AirJets>().unwrap().0.len(), SAMPLE.len());
+            assert_eq!(part1(&text).unwrap(), 3068);
+        }
+    }
+
+    #[test]
+    fn bad_input() {
+        assert_eq!(
+            "<<>x>".parse::<AirJets>().err().unwrap().to_string(),
+            "Character 'x' does not describe a valid jet"
+        );
+        for empty in ["", " \n"] {
+            assert_eq!(
+                empty.parse::<AirJets>().err().unwrap().to_string(),
+                "No jets in the pattern"
+            );
+        }
+    }
+
+    #[test]
+    fn counts() {
+        let canvas = Canvas::new(SAMPLE.parse::<AirJets>().unwrap());
+        assert_eq!(canvas.jet_count(), 40);
+        assert_eq!(canvas.pattern_count(), 5);
+        let canvas = Canvas::new("<".parse::<AirJets>().unwrap());
+        assert_eq!(canvas.jet_count(), 1);
+    }
+
+    #[test]
+    fn surface_profile() {
+        let mut canvas = Canvas::new(SAMPLE.parse::<AirJets>().unwrap());
+        assert_eq!(canvas.surface_profile(), Some([-1; 7]));
+        canvas.drop_rock(0);
+        assert_eq!(canvas.surface_profile(), Some([-1, -1, 0, 0, 0, 0, -1]));
+        canvas.drop_rock(1);
+        assert_eq!(canvas.surface_profile(), Some([-1, -1, 2, 3, 2, 0, -1]));
+    }
+
+    #[test]
+    fn tall_residual() {
+        // Blown hard left, no rock ever reaches the rightmost columns, so the floor never rises, and the surface
+        // soon stands well over 127 rows tall.
+        let mut uncached = Canvas::new("<".parse::<AirJets>().unwrap());
+        let mut canvas = Canvas::new("<".parse::<AirJets>().unwrap());
+        for num in 0..200 {
+            uncached.add_new_rock();
+            while uncached.rock_in_motion() {
+                uncached.blow();
+                uncached.fall();
+            }
+            canvas.drop_rock(num);
+        }
+        assert!(uncached.height() > 400);
+        assert_eq!(canvas.height(), uncached.height());
+    }
+
+    #[test]
+    fn spawn_gaps() {
+        // With the usual gaps, the bar gets blown left four times before landing, which pins it to the wall.
+        let mut canvas = Canvas::new("<".parse::<AirJets>().unwrap());
+        canvas.drop_rock(0);
+        assert_eq!(canvas.to_string(), "####...\n");
+
+        // Starting further right, and with less room to fall, it only gets blown left twice.
+        let mut canvas = Canvas::with_spawn_gaps("<".parse::<AirJets>().unwrap(), 3, 1).unwrap();
+        canvas.drop_rock(0);
+        assert_eq!(canvas.to_string(), ".####..\n");
+        assert_eq!(canvas.surface_profile(), Some([-1, 0, 0, 0, 0, -1, -1]));
+
+        // The bar needs all of the room that a left gap of 3 leaves it, and neither gap may be negative.
+        let gaps_error = |left_gap, height_gap| {
+            Canvas::with_spawn_gaps("<".parse::<AirJets>().unwrap(), left_gap, height_gap)
+                .err()
+                .map(|err| err.to_string())
+        };
+        assert_eq!(gaps_error(0, 0), None);
+        assert_eq!(
+            gaps_error(4, 3).unwrap(),
+            "A left gap of 4 doesn't leave room for every rock"
+        );
+        assert_eq!(
+            gaps_error(-1, 3).unwrap(),
+            "A left gap of -1 doesn't leave room for every rock"
+        );
+        assert_eq!(
+            gaps_error(2, -1).unwrap(),
+            "The height gap may not be negative (was -1)"
+        );
+    }
+
+    #[test]
+    fn cycle_length() {
+        let mut canvas = Canvas::new(SAMPLE.parse::<AirJets>().unwrap());
+        let mut num = 0;
+        let cycle = loop {
+            if let Some(cycle) = canvas.detect_cycle(num) {
+                break cycle;
+            }
+            canvas.drop_rock(num);
+            num += 1;
+        };
+        assert_eq!(cycle, (35, 53));
+    }
+
+    #[test]
+    fn render_window() {
+        let mut canvas = Canvas::new(SAMPLE.parse::<AirJets>().unwrap());
+        for num in 0..3 {
+            canvas.drop_rock(num);
+        }
+        assert_eq!(canvas.render_window(1, 3), "####...\n..###..\n...#...\n");
+        assert_eq!(canvas.render_window(6, 7), ".......\n.......\n");
+        assert_eq!(canvas.to_string(), canvas.render_window(0, 5));
+
+        // Once the floor has risen, the window still uses absolute rows.
+        for num in 3..30 {
+            canvas.drop_rock(num);
+        }
+        let floor = isize::try_from(canvas.floor_offset).unwrap();
+        assert!(floor > 0);
+        let top = canvas.height() - 1;
+        assert_eq!(canvas.to_string(), canvas.render_window(floor, top));
+        let window = canvas.render_window(floor - 2, floor);
+        assert_eq!(window.lines().collect::<Vec<_>>()[1..], ["~~~~~~~", "~~~~~~~"]);
+        assert_eq!(window.lines().next(), canvas.to_string().lines().last());
+    }
 }
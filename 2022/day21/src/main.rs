@@ -3,11 +3,11 @@
 //! Ref: [Advent of Code 2022 Day 21](https://adventofcode.com/2022/day/21)
 //!
 use ahash::AHashMap;
-use std::collections::VecDeque;
 use std::fmt::{Debug, Display};
 use std::io::{self, Read};
 use std::iter::Iterator;
 use std::str::FromStr;
+use toposort::{toposort, Cycle};
 
 #[derive(Debug, Copy, Clone)]
 enum Op {
@@ -29,6 +29,16 @@ impl FromStr for Op {
         }
     }
 }
+impl Op {
+    fn apply(self, left: i64, right: i64) -> i64 {
+        match self {
+            Op::Add => left + right,
+            Op::Subtract => left - right,
+            Op::Multiply => left * right,
+            Op::Divide => left / right,
+        }
+    }
+}
 impl Display for Op {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -150,7 +160,6 @@ impl Display for Insn {
 struct Monkey {
     value: Option<i64>,
     instruction: InsnOp,
-    watchers: Vec<MonkeyId>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -162,125 +171,96 @@ enum Style {
 #[derive(Default)]
 struct Machine {
     monkeys: AHashMap<MonkeyId, Monkey>,
-    notify_queue: VecDeque<MonkeyId>,
 }
 impl Machine {
     fn new() -> Self {
         Self::default()
     }
 
-    fn run(&mut self, instructions: &[Insn], style: Style) -> Option<i64> {
+    /// Have every monkey yell, each one after the monkeys it listens to, and return what the root monkey yells.
+    ///
+    /// In [Style::Human], the "humn" monkey keeps quiet, and so does every monkey that depends on it. Monkeys that
+    /// listen to each other in a loop are an error.
+    fn run(&mut self, instructions: &[Insn], style: Style) -> Result<Option<i64>, Cycle<MonkeyId>> {
         let root_id = "root".parse::<MonkeyId>().unwrap();
+        let humn_id = "humn".parse::<MonkeyId>().unwrap();
         for insn in instructions {
-            self.execute(insn, style);
+            self.monkeys.entry(insn.id).or_default().instruction = insn.op;
         }
-        self.value(root_id)
-    }
-
-    fn value(&self, id: MonkeyId) -> Option<i64> {
-        self.monkeys.get(&id).and_then(|x| x.value)
-    }
-
-    fn handle_notifications(&mut self) {
-        while let Some(listener) = self.notify_queue.pop_front() {
-            let insn = self.monkeys.get(&listener).unwrap();
-            if let InsnOp::Op(op, left, right) = insn.instruction {
-                let left = self.value(left);
-                let right = self.value(right);
-                if let (Some(vl), Some(vr)) = (left, right) {
-                    // we have enough info to give our monkey a value
-                    let val = match op {
-                        Op::Add => vl + vr,
-                        Op::Subtract => vl - vr,
-                        Op::Multiply => vl * vr,
-                        Op::Divide => vl / vr,
-                    };
-                    self.monkeys.entry(listener).and_modify(|monkey| {
-                        println!("Monkey {listener} yells {val}!");
-                        monkey.value = Some(val);
-                        for watcher in monkey.watchers.iter() {
-                            self.notify_queue.push_back(*watcher);
-                        }
-                    });
-                }
+        let order = toposort(self.monkeys.keys().copied(), |id| {
+            match self.monkeys.get(id).map(|m| m.instruction) {
+                Some(InsnOp::Op(_, left, right)) => vec![left, right],
+                _ => vec![],
             }
+        })?;
+        for id in order {
+            let instruction = self.monkeys.get(&id).map_or(InsnOp::Bogus, |monkey| monkey.instruction);
+            let value = match instruction {
+                InsnOp::Yell(_) if style == Style::Human && id == humn_id => None,
+                InsnOp::Yell(val) => Some(val),
+                InsnOp::Op(op, left, right) => self.value(left).zip(self.value(right)).map(|(vl, vr)| op.apply(vl, vr)),
+                // A monkey that was listened to, but never given an instruction
+                InsnOp::Bogus => None,
+            };
+            if let Some(val) = value {
+                println!("Monkey {id} yells {val}!");
+            }
+            self.monkeys.entry(id).or_default().value = value;
         }
+        Ok(self.value(root_id))
     }
 
-    fn execute(&mut self, insn: &Insn, style: Style) {
-        println!("Executing \"{insn}\"");
-        let id = insn.id;
-        match insn.op {
-            InsnOp::Yell(val) => {
-                if !(style == Style::Human && id == "humn".parse::<MonkeyId>().unwrap()) {
-                    let monkey = self.monkeys.entry(id).or_default();
-                    println!("Monkey {id} yells {val}!");
-
-                    monkey.value = Some(val);
-                    for watcher in monkey.watchers.iter() {
-                        self.notify_queue.push_back(*watcher);
-                    }
-                }
-            }
-            InsnOp::Op(_, left, right) => {
-                let monkey = self.monkeys.entry(id).or_default();
-                monkey.instruction = insn.op;
-                let left_monkey = self.monkeys.entry(left).or_default();
-                left_monkey.watchers.push(id);
-                let right_monkey = self.monkeys.entry(right).or_default();
-                right_monkey.watchers.push(id);
-                // Put the new monkey in the notifier list; our dependencies might already be resolved.
-                self.notify_queue.push_back(id);
-            }
-            InsnOp::Bogus => unreachable!(),
-        }
-        self.handle_notifications();
+    fn value(&self, id: MonkeyId) -> Option<i64> {
+        self.monkeys.get(&id).and_then(|x| x.value)
     }
 
-    fn humanity(&self) -> i64 {
+    /// The number the human needs to yell so that root's two operands match.
+    ///
+    /// This works back from root toward humn, undoing one operation at a time, so it relies on humn feeding into
+    /// just one of root's operands (and just one operand of each monkey along the way). Anything else is an
+    /// error.
+    fn humanity(&self) -> anyhow::Result<i64> {
         let root = "root".parse::<MonkeyId>().unwrap();
         let humn = "humn".parse::<MonkeyId>().unwrap();
-        let root_instruction = self.monkeys.get(&root).unwrap().instruction;
-        assert!(matches!(root_instruction, InsnOp::Op(..)));
-        if let InsnOp::Op(_, left, right) = root_instruction {
-            let (mut known_value, mut sought_id) = match (self.value(left), self.value(right)) {
+        let mut sought_id = root;
+        let mut known_value = None;
+        while sought_id != humn {
+            let instruction = self.monkeys.get(&sought_id).map(|monkey| monkey.instruction);
+            let Some(InsnOp::Op(op, left, right)) = instruction else {
+                anyhow::bail!("Monkey {sought_id} has no value, but doesn't depend on humn");
+            };
+            let (val, unknown) = match (self.value(left), self.value(right)) {
                 (Some(val), None) => (val, right),
                 (None, Some(val)) => (val, left),
-                _ => unreachable!(),
+                (Some(_), Some(_)) => anyhow::bail!("humn does not influence {sought_id}"),
+                (None, None) => anyhow::bail!("humn influences both sides of {sought_id}"),
+            };
+            let Some(target) = known_value else {
+                // Root just needs the unknown side to match the known one.
+                (known_value, sought_id) = (Some(val), unknown);
+                continue;
             };
-            while sought_id != humn {
-                let instruction = self.monkeys.get(&sought_id).unwrap().instruction;
-                assert!(matches!(instruction, InsnOp::Op(..)));
-                if let InsnOp::Op(op, left, right) = instruction {
-                    (known_value, sought_id) = match (self.value(left), self.value(right)) {
-                        (Some(val), None) => match op {
-                            Op::Add => (known_value - val, right),
-                            Op::Subtract => (val - known_value, right),
-                            Op::Multiply => {
-                                assert_eq!(known_value % val, 0);
-                                (known_value / val, right)
-                            }
-                            Op::Divide => {
-                                assert_eq!(val % known_value, 0);
-                                (val / known_value, right)
-                            }
-                        },
-                        (None, Some(val)) => match op {
-                            Op::Add => (known_value - val, left),
-                            Op::Subtract => (known_value + val, left),
-                            Op::Multiply => {
-                                assert_eq!(known_value % val, 0);
-                                (known_value / val, left)
-                            }
-                            Op::Divide => (known_value * val, left),
-                        },
-                        _ => unreachable!(),
-                    };
+            let next = match (op, unknown == right) {
+                (Op::Add, _) => target - val,
+                (Op::Subtract, true) => val - target,
+                (Op::Subtract, false) => target + val,
+                (Op::Multiply, _) => {
+                    if val == 0 || target % val != 0 {
+                        anyhow::bail!("No whole number times {val} gives {target} at {sought_id}");
+                    }
+                    target / val
                 }
-            }
-            return known_value;
+                (Op::Divide, true) => {
+                    if target == 0 || val % target != 0 {
+                        anyhow::bail!("No whole number divides {val} to give {target} at {sought_id}");
+                    }
+                    val / target
+                }
+                (Op::Divide, false) => target * val,
+            };
+            (known_value, sought_id) = (Some(next), unknown);
         }
-        unreachable!()
+        known_value.ok_or_else(|| anyhow::anyhow!("humn can't be root"))
     }
 }
 
@@ -290,7 +270,7 @@ fn part1(input: &str) -> anyhow::Result<i64> {
         .map(|line| line.parse::<Insn>())
         .collect::<anyhow::Result<Vec<Insn>>>()?;
     let mut machine = Machine::new();
-    match machine.run(&instructions, Style::Monkey) {
+    match machine.run(&instructions, Style::Monkey)? {
         Some(val) => Ok(val),
         None => {
             anyhow::bail!("No value for root monkey")
@@ -304,9 +284,8 @@ fn part2(input: &str) -> anyhow::Result<i64> {
         .map(|line| line.parse::<Insn>())
         .collect::<anyhow::Result<Vec<Insn>>>()?;
     let mut machine = Machine::new();
-    let rcode = machine.run(&instructions, Style::Human);
-    assert!(rcode.is_none());
-    Ok(machine.humanity())
+    machine.run(&instructions, Style::Human)?;
+    machine.humanity()
 }
 
 fn main() -> anyhow::Result<()> {
@@ -354,6 +333,47 @@ mod tests {
         assert_eq!(part2(SAMPLE).unwrap(), 301);
     }
 
+    #[test_case("root: abcd + wxyz\nabcd: 3\nwxyz: 4\nhumn: 5\n" => "humn does not influence root"; "humn unused")]
+    #[test_case("root: efgh + wxyz\nwxyz: 4\nhumn: 5\n" => "Monkey efgh has no value, but doesn't depend on humn"; "missing monkey")]
+    #[test_case("root: abcd + wxyz\nabcd: humn * humn\nwxyz: 4\nhumn: 5\n" => "humn influences both sides of abcd"; "humn squared")]
+    #[test_case("root: humn + wxyz\nwxyz: humn - abcd\nabcd: 4\nhumn: 5\n" => "humn influences both sides of root"; "humn on both sides")]
+    #[test_case("root: abcd + wxyz\nabcd: humn * efgh\nefgh: 2\nwxyz: 5\nhumn: 1\n" => "No whole number times 2 gives 5 at abcd"; "inexact product")]
+    #[test_case("root: abcd + wxyz\nabcd: humn * efgh\nefgh: 0\nwxyz: 5\nhumn: 1\n" => "No whole number times 0 gives 5 at abcd"; "zero product")]
+    #[test_case("root: abcd + wxyz\nabcd: efgh / humn\nefgh: 7\nwxyz: 2\nhumn: 1\n" => "No whole number divides 7 to give 2 at abcd"; "inexact quotient")]
+    #[test_case("root: abcd + wxyz\nabcd: efgh / humn\nefgh: 7\nwxyz: 0\nhumn: 1\n" => "No whole number divides 7 to give 0 at abcd"; "zero quotient")]
+    fn unsolvable(input: &str) -> String {
+        part2(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn undefined_monkey() {
+        let input = indoc::indoc! {"
+            root: abcd + wxyz
+            abcd: 3
+        "};
+        assert_eq!(part1(input).unwrap_err().to_string(), "No value for root monkey");
+    }
+
+    #[test]
+    fn looped() {
+        let input = indoc::indoc! {"
+            root: abcd + wxyz
+            abcd: 3
+            wxyz: abcd * efgh
+            efgh: wxyz - abcd
+        "};
+        // Which monkey the loop is reported as starting with depends on hash order.
+        let message = part1(input).unwrap_err().to_string();
+        assert!(
+            [
+                "dependency cycle: wxyz -> efgh -> wxyz",
+                "dependency cycle: efgh -> wxyz -> efgh"
+            ]
+            .contains(&message.as_str()),
+            "{message}"
+        );
+    }
+
     #[test_case("root" => 0x726f6f74)]
     fn monkey_id(text: &str) -> u32 {
         let mid = text.parse::<MonkeyId>().unwrap();
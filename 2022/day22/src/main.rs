@@ -4,12 +4,11 @@
 //!
 use ahash::AHashMap;
 use anyhow::{anyhow, bail, Error, Result};
-use once_cell::sync::Lazy;
 use std::io::{self, Read};
 use std::iter::Iterator;
 use std::str::FromStr;
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum OneOfSix {
     One,
     Two,
@@ -34,6 +33,33 @@ enum FoldingStyle {
     Sample,
     Actual,
 }
+// Where each face of the cube sits in the unfolded map (in units of whole faces), for each of the folds we know
+// about.
+const FACE_LAYOUTS: [(FoldingStyle, [(OneOfSix, Point); 6]); 2] = [
+    (
+        FoldingStyle::Sample,
+        [
+            (OneOfSix::One, Point { row: 0, col: 2 }),
+            (OneOfSix::Two, Point { row: 1, col: 0 }),
+            (OneOfSix::Three, Point { row: 1, col: 1 }),
+            (OneOfSix::Four, Point { row: 1, col: 2 }),
+            (OneOfSix::Five, Point { row: 2, col: 2 }),
+            (OneOfSix::Six, Point { row: 2, col: 3 }),
+        ],
+    ),
+    (
+        FoldingStyle::Actual,
+        [
+            (OneOfSix::One, Point { row: 0, col: 1 }),
+            (OneOfSix::Two, Point { row: 0, col: 2 }),
+            (OneOfSix::Three, Point { row: 1, col: 1 }),
+            (OneOfSix::Four, Point { row: 2, col: 1 }),
+            (OneOfSix::Five, Point { row: 2, col: 0 }),
+            (OneOfSix::Six, Point { row: 3, col: 0 }),
+        ],
+    ),
+];
+
 #[derive(Debug)]
 struct Map {
     points: AHashMap<Point, Constraint>,
@@ -44,6 +70,17 @@ impl FromStr for Map {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Map::from_str_with_face_size(s, None)
+    }
+}
+
+impl Map {
+    /// Parse a map, optionally insisting on a particular cube face size.
+    ///
+    /// With `None`, the face size is inferred from the map (as the shortest run of cells in any row); that's right
+    /// for real puzzle inputs, but small hand-drawn maps can fool it. Either way, the map must then be made of whole
+    /// faces of that size.
+    fn from_str_with_face_size(s: &str, face_size: Option<i64>) -> Result<Self> {
         let map = s
             .lines()
             .enumerate()
@@ -86,19 +123,26 @@ impl FromStr for Map {
             },
         );
 
-        // Calculate "face" size (for the cube problem). This is the shortest run of continuous characters in
-        // either the rows or columns. All other run lengths should be multiples of this.
-        let face_size = (top..=bottom)
-            .map(|row| {
-                map.keys()
-                    .filter_map(|key| (key.row == row).then_some(key.col))
-                    .fold((i64::MAX, i64::MIN), |(leftmost, rightmost), column| {
-                        (leftmost.min(column), rightmost.max(column))
-                    })
-            })
-            .map(|(smallest, largest)| 1 + largest - smallest)
-            .min()
-            .expect("The map should not be an empty string");
+        // Calculate "face" size (for the cube problem), unless we were told what it is. This is the shortest run
+        // of continuous characters in either the rows or columns. All other run lengths should be multiples of this.
+        if map.is_empty() {
+            bail!("The map should not be an empty string");
+        }
+        let face_size = match face_size {
+            Some(size) if size <= 0 => bail!("Face size must be positive (was {size})"),
+            Some(size) => size,
+            None => (top..=bottom)
+                .map(|row| {
+                    map.keys()
+                        .filter_map(|key| (key.row == row).then_some(key.col))
+                        .fold((i64::MAX, i64::MIN), |(leftmost, rightmost), column| {
+                            (leftmost.min(column), rightmost.max(column))
+                        })
+                })
+                .map(|(smallest, largest)| 1 + largest - smallest)
+                .min()
+                .expect("The map is not empty"),
+        };
 
         // Validate that all rows have values between min & max and that their widths are multiples.
         for row in top..=bottom {
@@ -144,41 +188,21 @@ impl FromStr for Map {
         // confirm that's the case, but we only actually need to deal with the pattern from the sample, and
         // the pattern in my input, so I'm not gonna bother. We do need to figure out which of those patterns
         // it is, though.
-        const SAMPLE_PROBES: [Point; 6] = [
-            Point { row: 0, col: 2 },
-            Point { row: 1, col: 0 },
-            Point { row: 1, col: 1 },
-            Point { row: 1, col: 2 },
-            Point { row: 2, col: 2 },
-            Point { row: 2, col: 3 },
-        ];
-        const ACTUAL_PROBES: [Point; 6] = [
-            Point { row: 0, col: 1 },
-            Point { row: 0, col: 2 },
-            Point { row: 1, col: 1 },
-            Point { row: 2, col: 0 },
-            Point { row: 2, col: 1 },
-            Point { row: 3, col: 0 },
-        ];
-
-        let folding_style = [
-            (&SAMPLE_PROBES, FoldingStyle::Sample),
-            (&ACTUAL_PROBES, FoldingStyle::Actual),
-        ]
-        .into_iter()
-        .filter_map(|(probes, tag)| {
-            probes
-                .iter()
-                .all(|pt| {
-                    map.contains_key(&Point {
-                        row: (face_size * pt.row) + face_size / 2,
-                        col: (face_size * pt.col) + face_size / 2,
+        let folding_style = FACE_LAYOUTS
+            .iter()
+            .filter_map(|(tag, layout)| {
+                layout
+                    .iter()
+                    .all(|(_, pt)| {
+                        map.contains_key(&Point {
+                            row: (face_size * pt.row) + face_size / 2,
+                            col: (face_size * pt.col) + face_size / 2,
+                        })
                     })
-                })
-                .then_some(tag)
-        })
-        .next()
-        .ok_or_else(|| anyhow!("Map doesn't have a known fold"))?;
+                    .then_some(*tag)
+            })
+            .next()
+            .ok_or_else(|| anyhow!("Map doesn't have a known fold"))?;
 
         Ok(Map {
             points: map,
@@ -186,16 +210,16 @@ impl FromStr for Map {
             folding_style,
         })
     }
-}
 
-impl Map {
-    fn start_location(&self) -> Option<Point> {
-        let lowest_row = 0;
+    /// The leftmost spot of the top row.
+    ///
+    /// Parsing refuses empty maps, so there's always such a spot.
+    fn start_location(&self) -> Point {
         self.points
             .keys()
-            .filter(|&item| item.row == lowest_row)
-            .min_by_key(|&item| item.col)
+            .min_by_key(|&item| (item.row, item.col))
             .copied()
+            .expect("maps should never be empty")
     }
 
     fn next_spot(&self, from: Point, facing: Facing, is_cube: bool) -> (Point, Facing) {
@@ -557,47 +581,65 @@ impl Map {
         }
     }
 
-    fn cube_face(&self, pt: Point) -> OneOfSix {
-        let normalized = Point {
-            row: pt.row / self.face_size,
-            col: pt.col / self.face_size,
-        };
+    /// Every face of the cube, along with the location of its top-left corner on the map.
+    fn faces(&self) -> impl Iterator<Item = (OneOfSix, Point /*offset*/)> + '_ {
+        let (_, layout) = FACE_LAYOUTS
+            .iter()
+            .find(|(style, _)| *style == self.folding_style)
+            .expect("Every folding style has a layout");
+        layout.iter().map(|&(face, pt)| {
+            (
+                face,
+                Point {
+                    row: pt.row * self.face_size,
+                    col: pt.col * self.face_size,
+                },
+            )
+        })
+    }
 
-        static FACE_DEFINITIONS: Lazy<AHashMap<(Point, FoldingStyle), OneOfSix>> = Lazy::new(|| {
-            AHashMap::from_iter([
-                ((Point { row: 0, col: 2 }, FoldingStyle::Sample), OneOfSix::One),
-                ((Point { row: 1, col: 0 }, FoldingStyle::Sample), OneOfSix::Two),
-                ((Point { row: 1, col: 1 }, FoldingStyle::Sample), OneOfSix::Three),
-                ((Point { row: 1, col: 2 }, FoldingStyle::Sample), OneOfSix::Four),
-                ((Point { row: 2, col: 2 }, FoldingStyle::Sample), OneOfSix::Five),
-                ((Point { row: 2, col: 3 }, FoldingStyle::Sample), OneOfSix::Six),
-                ((Point { row: 0, col: 1 }, FoldingStyle::Actual), OneOfSix::One),
-                ((Point { row: 0, col: 2 }, FoldingStyle::Actual), OneOfSix::Two),
-                ((Point { row: 1, col: 1 }, FoldingStyle::Actual), OneOfSix::Three),
-                ((Point { row: 2, col: 0 }, FoldingStyle::Actual), OneOfSix::Five),
-                ((Point { row: 2, col: 1 }, FoldingStyle::Actual), OneOfSix::Four),
-                ((Point { row: 3, col: 0 }, FoldingStyle::Actual), OneOfSix::Six),
-            ])
-        });
-        *FACE_DEFINITIONS
-            .get(&(normalized, self.folding_style))
+    fn cube_face(&self, pt: Point) -> OneOfSix {
+        self.faces()
+            .find(|(_, offset)| {
+                (offset.row..offset.row + self.face_size).contains(&pt.row)
+                    && (offset.col..offset.col + self.face_size).contains(&pt.col)
+            })
+            .map(|(face, _)| face)
             .expect("Point should be in cube")
     }
 
-    fn do_motion(&self, motions: &Motions, is_cube: bool) -> Option<(Point, Facing)> {
-        let mut location = self.start_location()?;
+    fn do_motion(&self, motions: &Motions, is_cube: bool) -> (Point, Facing) {
+        *self
+            .trail(motions, is_cube)
+            .last()
+            .expect("the trail always holds at least the start")
+    }
+
+    /// Every position and facing along the route, in order.
+    ///
+    /// The route starts at the start location, facing right. A turn adds an entry for the new facing at the same
+    /// spot; each step forward adds an entry for the spot stepped to. (Steps blocked by a wall add nothing.)
+    fn trail(&self, motions: &Motions, is_cube: bool) -> Vec<(Point, Facing)> {
+        let mut location = self.start_location();
         let mut facing = Facing::Right;
+        let mut trail = vec![(location, facing)];
         for instruction in motions.motions.iter() {
             match instruction {
-                Motion::Right => facing = facing.turn_right(),
-                Motion::Left => facing = facing.turn_left(),
+                Motion::Right => {
+                    facing = facing.turn_right();
+                    trail.push((location, facing));
+                }
+                Motion::Left => {
+                    facing = facing.turn_left();
+                    trail.push((location, facing));
+                }
                 Motion::Forward(steps) => {
                     for _ in 0..*steps {
                         let (in_front, new_facing) = self.next_spot(location, facing, is_cube);
                         if self.points[&in_front] == Constraint::Free {
-                            //println!("Moved to {in_front:?}");
                             location = in_front;
                             facing = new_facing;
+                            trail.push((location, facing));
                         } else {
                             break;
                         }
@@ -605,7 +647,41 @@ impl Map {
                 }
             }
         }
-        Some((location, facing))
+        trail
+    }
+
+    /// Draw the map with a trail laid over it, like the pictures in the puzzle description.
+    ///
+    /// Each spot on the trail shows the last facing it had there, as one of `>`, `v`, `<`, or `^`. The rest of the
+    /// map is drawn as it was in the input, except that trailing spaces are left off.
+    #[cfg(test)]
+    fn trail_string(&self, trail: &[(Point, Facing)]) -> String {
+        let glyphs = trail
+            .iter()
+            .map(|&(pt, facing)| (pt, facing.glyph()))
+            .collect::<AHashMap<_, _>>();
+        let height = self.points.keys().map(|pt| pt.row).max().map_or(0, |row| row + 1);
+        let mut result = String::new();
+        for row in 0..height {
+            let width = self
+                .points
+                .keys()
+                .filter(|pt| pt.row == row)
+                .map(|pt| pt.col)
+                .max()
+                .map_or(0, |col| col + 1);
+            for col in 0..width {
+                let pt = Point { row, col };
+                result.push(match (glyphs.get(&pt), self.points.get(&pt)) {
+                    (Some(&glyph), _) => glyph,
+                    (None, Some(Constraint::Free)) => '.',
+                    (None, Some(Constraint::Wall)) => '#',
+                    (None, None) => ' ',
+                });
+            }
+            result.push('\n');
+        }
+        result
     }
 }
 
@@ -691,24 +767,36 @@ impl Facing {
             Facing::Right => 0,
         }
     }
+    #[cfg(test)]
+    fn glyph(self) -> char {
+        match self {
+            Facing::Up => '^',
+            Facing::Down => 'v',
+            Facing::Left => '<',
+            Facing::Right => '>',
+        }
+    }
+}
+
+/// The pieces of the final password: the 1-indexed row, the 1-indexed column, and the facing value.
+fn score_components(p: Point, f: Facing) -> (i64, i64, i64) {
+    (p.row + 1, p.col + 1, f.score())
 }
 
 fn score(p: Point, f: Facing) -> i64 {
-    let row = p.row + 1;
-    let col = p.col + 1;
-    let facing = f.score();
+    let (row, col, facing) = score_components(p, f);
     1000 * row + 4 * col + facing
 }
 
 fn part1(input_str: &str) -> Result<i64> {
     let Input { map, motions } = input_str.parse::<Input>()?;
-    let (end_point, end_facing) = map.do_motion(&motions, false).ok_or_else(|| anyhow!("Empty map?"))?;
+    let (end_point, end_facing) = map.do_motion(&motions, false);
     Ok(score(end_point, end_facing))
 }
 
 fn part2(input_str: &str) -> Result<i64> {
     let Input { map, motions } = input_str.parse::<Input>()?;
-    let (end_point, end_facing) = map.do_motion(&motions, true).ok_or_else(|| anyhow!("Empty map?"))?;
+    let (end_point, end_facing) = map.do_motion(&motions, true);
     Ok(score(end_point, end_facing))
 }
 
@@ -751,11 +839,66 @@ mod tests {
         assert_eq!(part1(SAMPLE).unwrap(), 6032);
     }
 
+    #[test]
+    fn faces() {
+        let Input { map, motions: _ } = SAMPLE.parse::<Input>().unwrap();
+        assert_eq!(
+            map.faces().collect::<Vec<_>>(),
+            vec![
+                (OneOfSix::One, Point { row: 0, col: 8 }),
+                (OneOfSix::Two, Point { row: 4, col: 0 }),
+                (OneOfSix::Three, Point { row: 4, col: 4 }),
+                (OneOfSix::Four, Point { row: 4, col: 8 }),
+                (OneOfSix::Five, Point { row: 8, col: 8 }),
+                (OneOfSix::Six, Point { row: 8, col: 12 }),
+            ]
+        );
+        for (face, offset) in map.faces() {
+            assert_eq!(map.cube_face(offset), face);
+        }
+    }
+
+    #[test_case(false => (6, 8, 0); "flat")]
+    #[test_case(true => (5, 7, 3); "cube")]
+    fn score_components(is_cube: bool) -> (i64, i64, i64) {
+        let Input { map, motions } = SAMPLE.parse::<Input>().unwrap();
+        let (end_point, end_facing) = map.do_motion(&motions, is_cube);
+        super::score_components(end_point, end_facing)
+    }
+
+    #[test]
+    fn trail() {
+        let Input { map, motions } = SAMPLE.parse::<Input>().unwrap();
+        let trail = map.trail(&motions, false);
+        assert_eq!(trail[0], (Point { row: 0, col: 8 }, Facing::Right));
+        // The first turn happens in place, after two steps (the third is blocked by a wall).
+        assert_eq!(trail[2], (Point { row: 0, col: 10 }, Facing::Right));
+        assert_eq!(trail[3], (Point { row: 0, col: 10 }, Facing::Down));
+        assert_eq!(trail.last(), Some(&(Point { row: 5, col: 7 }, Facing::Right)));
+
+        // This is the picture from the puzzle description.
+        let expected = indoc::indoc! {"
+                    >>v#
+                    .#v.
+                    #.v.
+                    ..v.
+            ...#...v..v#
+            >>>v...>#.>>
+            ..#v...#....
+            ...>>>>v..#.
+                    ...#....
+                    .....#..
+                    .#......
+                    ......#.
+        "};
+        assert_eq!(map.trail_string(&trail), expected);
+    }
+
     #[test_case("3R3R3" => (Point{row: 3, col: 11}, Facing::Left))]
     fn wraparound(motion: &str) -> (Point, Facing) {
         let Input { map, motions: _ } = SAMPLE.parse::<Input>().unwrap();
         let motions = motion.parse::<Motions>().unwrap();
-        map.do_motion(&motions, false).unwrap()
+        map.do_motion(&motions, false)
     }
 
     #[test_case(Point{col: 8, row: 0}, Facing::Up => (Point{col: 3, row: 4}, Facing::Down); "up from face 1 (on left)")]
@@ -816,11 +959,42 @@ mod tests {
     #[test_case(Point{col: 4, row: 3}, Facing::Left => (Point {col: 0, row: 8}, Facing::Right); "left from face 1")]
     #[test_case(Point{col: 11, row: 0}, Facing::Up => (Point {col: 3, row: 15}, Facing::Up); "up from face 2")]
     fn other_fold_next(location: Point, facing: Facing) -> (Point, Facing) {
-        let map = OTHER_FOLD.parse::<Map>().unwrap();
+        let map = Map::from_str_with_face_size(OTHER_FOLD, Some(4)).unwrap();
 
         map.next_spot(location, facing, true)
     }
 
+    #[test_case(None => Ok(4); "inferred")]
+    #[test_case(Some(4) => Ok(4); "explicit")]
+    #[test_case(Some(3) => Err("Map is ill-sized (face_size is 3, row has width 8)".to_string()); "not a divisor")]
+    #[test_case(Some(2) => Err("Map doesn't have a known fold".to_string()); "too small")]
+    #[test_case(Some(0) => Err("Face size must be positive (was 0)".to_string()); "zero")]
+    fn explicit_face_size(face_size: Option<i64>) -> Result<i64, String> {
+        Map::from_str_with_face_size(OTHER_FOLD, face_size)
+            .map(|map| map.face_size)
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn start_location() {
+        let Input { map, motions } = SAMPLE.parse::<Input>().unwrap();
+        assert_eq!(map.start_location(), Point { row: 0, col: 8 });
+        let other = Map::from_str_with_face_size(OTHER_FOLD, Some(4)).unwrap();
+        assert_eq!(other.start_location(), Point { row: 0, col: 4 });
+        // With no moves at all, the route ends where it started.
+        assert_eq!(
+            other.do_motion(&"0".parse::<Motions>().unwrap(), true),
+            (Point { row: 0, col: 4 }, Facing::Right)
+        );
+        assert_eq!(map.trail(&motions, true)[0], (Point { row: 0, col: 8 }, Facing::Right));
+
+        // The one map that would have no start is refused up front.
+        assert_eq!(
+            "".parse::<Map>().unwrap_err().to_string(),
+            "The map should not be an empty string"
+        );
+    }
+
     #[test]
     fn part2_sample() {
         assert_eq!(part2(SAMPLE).unwrap(), 5031);
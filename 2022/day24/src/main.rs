@@ -2,13 +2,13 @@
 //!
 //! Ref: [Advent of Code 2022 Day 24](https://adventofcode.com/2022/day/24)
 //!
-use ahash::{AHashMap, AHashSet};
+use ahash::AHashSet;
 use anyhow::{anyhow, bail, Error, Result};
 use astar::{search_astar, AStarNode};
+use grid::Point;
 use num::traits::Zero;
 use once_cell::sync::Lazy;
 use regex::Regex;
-use std::cell::RefCell;
 use std::hash::Hash;
 use std::io::{self, Read};
 use std::ops::{Div, Mul, Rem};
@@ -108,12 +108,6 @@ impl FromStr for Input {
     }
 }
 
-#[derive(Copy, Clone, Hash, PartialEq, Eq, Debug)]
-struct Point {
-    col: i64,
-    row: i64,
-}
-
 fn gcd<T>(a: T, b: T) -> T
 where
     T: Rem<Output = T> + PartialEq + Zero + Copy,
@@ -145,22 +139,47 @@ struct TraversalState {
 
 struct TraversalSharedInfo {
     cycle_modulo: usize,
-    blizzards: Vec<Blizzard>,
     width: i64,
     height: i64,
-    cache: RefCell<AHashMap<usize, AHashSet<Point>>>,
+    // The blizzard pattern repeats every `cycle_modulo` minutes, so every configuration is worked out up front,
+    // indexed by cycle. (The neighbor search looks these up constantly; a plain index is much cheaper than a
+    // lazily-filled map behind a RefCell.)
+    blizzard_spots: Vec<AHashSet<Point>>,
+    // Whether reaching the goal also means arriving at the goal's exact point in the blizzard cycle, rather than just
+    // at its location.
+    exact_goal: bool,
 }
-impl Input {
-    fn start(&self, starting_cycle: usize) -> TraversalState {
-        TraversalState {
-            cycle: starting_cycle,
-            row: -1,
-            col: 0,
+impl TraversalSharedInfo {
+    /// The cell inside the valley that leads to `spot`, if `spot` is one of the gaps in the wall (the entrance or
+    /// the exit).
+    fn door(&self, spot: Point) -> Option<Point> {
+        if spot.row == -1 {
+            Some(Point { row: 0, col: spot.col })
+        } else if spot.row == self.height {
+            Some(Point {
+                row: self.height - 1,
+                col: spot.col,
+            })
+        } else {
+            None
         }
     }
-    fn goal(&self, starting_cycle: usize) -> TraversalState {
-        TraversalState {
-            cycle: starting_cycle,
+
+    /// The soonest time, no earlier than `earliest` minutes after `cycle`, that `spot` is free of blizzards.
+    ///
+    /// If the spot is never free, this is a full blizzard cycle past `earliest`.
+    fn first_clear(&self, spot: Point, cycle: usize, earliest: i64) -> i64 {
+        (earliest..earliest + self.cycle_modulo as i64)
+            .find(|&minutes| !self.blizzard_spots[(cycle + minutes as usize) % self.cycle_modulo].contains(&spot))
+            .unwrap_or(earliest + self.cycle_modulo as i64)
+    }
+}
+impl Input {
+    fn entrance(&self) -> Point {
+        Point { row: -1, col: 0 }
+    }
+    fn exit(&self) -> Point {
+        Point {
             row: self.height,
             col: self.width - 1,
         }
@@ -168,19 +187,13 @@ impl Input {
     fn info(&self) -> TraversalSharedInfo {
         TraversalSharedInfo {
             cycle_modulo: self.cycle_modulo,
-            blizzards: self.blizzards.clone(),
             width: self.width,
             height: self.height,
-            cache: RefCell::new(AHashMap::new()),
+            blizzard_spots: (0..self.cycle_modulo).map(|cycle| self.blizzard_spots(cycle)).collect(),
+            exact_goal: false,
         }
     }
-}
-impl TraversalSharedInfo {
     fn blizzard_spots(&self, cycle: usize) -> AHashSet<Point> {
-        let cached_item = self.cache.borrow().get(&cycle).cloned();
-        if let Some(item) = cached_item {
-            return item;
-        }
         let mut snowy = AHashSet::new();
         for blizzard in self.blizzards.iter() {
             let pt_to_add = match &blizzard.direction {
@@ -203,7 +216,6 @@ impl TraversalSharedInfo {
             };
             snowy.insert(pt_to_add);
         }
-        self.cache.borrow_mut().insert(cycle, snowy.clone());
         snowy
     }
 }
@@ -240,19 +252,42 @@ impl AStarNode for TraversalState {
 
     type AssociatedState = TraversalSharedInfo;
 
-    fn heuristic(&self, goal: &Self, _: &Self::AssociatedState) -> Self::Cost {
+    fn heuristic(&self, goal: &Self, state: &Self::AssociatedState) -> Self::Cost {
         // This is an optimistic assessment of the cost to reach the goal. In the case of the blizzard
-        // simulation, it's just the Manhattan distance between the current location and the goal location.
-        let dx = (goal.col - self.col).abs();
-        let dy = (goal.row - self.row).abs();
-        dx + dy
+        // simulation, it starts from the Manhattan distance between the current location and the goal location.
+        // But the gaps in the wall can only be passed through their doors (the cells just inside), and a door
+        // may be snowed in when we'd first like to step onto it. Since there's no way around that wait, it can be
+        // added on without ever overestimating.
+        let here = Point::new(self.row, self.col);
+        let there = Point::new(goal.row, goal.col);
+        if here == there {
+            return 0;
+        }
+        let (mut minutes, mut spot) = (0, here);
+        if let Some(door) = state.door(here) {
+            // Outside the valley, the only way in is to step onto the door.
+            minutes = state.first_clear(door, self.cycle, 1);
+            spot = door;
+        }
+        match state.door(there) {
+            Some(door) => {
+                // And the only way out is from the door, once we've walked there and it's clear.
+                let arrival = if spot == door {
+                    minutes
+                } else {
+                    state.first_clear(door, self.cycle, minutes + spot.manhattan(&door))
+                };
+                arrival + 1
+            }
+            None => minutes + spot.manhattan(&there),
+        }
     }
 
     fn neighbors(&self, state: &Self::AssociatedState) -> impl Iterator<Item = (Self, Self::Cost)> {
         // Remember that a "neighbor" is "a new state we could transition to". So, "don't move" is also a
         // valid neighbor. This is the routine where we actually need to check the blizzard conditions.
         let next_cycle = (self.cycle + 1) % state.cycle_modulo;
-        let next_blizzard_locations = state.blizzard_spots(next_cycle);
+        let next_blizzard_locations = &state.blizzard_spots[next_cycle];
         let center = Point {
             col: self.col,
             row: self.row,
@@ -320,30 +355,56 @@ impl AStarNode for TraversalState {
         }
     }
 
-    fn goal_match(&self, goal: &Self, _: &Self::AssociatedState) -> bool {
-        // Have we reached the goal? Equality isn't precisely what we want for the blizzard, as we don't
+    fn goal_match(&self, goal: &Self, state: &Self::AssociatedState) -> bool {
+        // Have we reached the goal? Usually equality isn't precisely what we want for the blizzard, as we don't
         // actually care what spot we're at in the blizzard cycle when we hit the exit. (And we couldn't
-        // predict it anyway.) All we care about is the map location.
-        self.col == goal.col && self.row == goal.row
+        // predict it anyway.) All we care about is the map location. Unless we've been asked to care.
+        if state.exact_goal {
+            self == goal
+        } else {
+            self.col == goal.col && self.row == goal.row
+        }
     }
 }
 
+/// Find the quickest way from `start` to `goal`, leaving `start` at minute `start_minute`.
+///
+/// The returned path includes both endpoints, one state per minute, so a crossing that takes `n` minutes
+/// has `n + 1` entries.
+pub(crate) fn shortest_crossing(
+    info: &TraversalSharedInfo,
+    start: Point,
+    goal: Point,
+    start_minute: usize,
+) -> anyhow::Result<Vec<TraversalState>> {
+    let start = TraversalState {
+        cycle: start_minute % info.cycle_modulo,
+        row: start.row,
+        col: start.col,
+    };
+    let goal = TraversalState {
+        cycle: 0,
+        row: goal.row,
+        col: goal.col,
+    };
+    search_astar(start, goal, info).ok_or_else(|| anyhow!("No way through the blizzards"))
+}
+
 fn part1(input: &str) -> anyhow::Result<usize> {
     let input = input.parse::<Input>()?;
-    let info = input.info();
-    let path = search_astar(input.start(0), input.goal(0), &info).unwrap();
+    let path = shortest_crossing(&input.info(), input.entrance(), input.exit(), 0)?;
     Ok(path.len() - 1)
 }
 
 fn part2(input: &str) -> anyhow::Result<usize> {
     let input = input.parse::<Input>()?;
     let info = input.info();
-    let first_path = search_astar(input.start(0), input.goal(0), &info).unwrap();
+    let first_path = shortest_crossing(&info, input.entrance(), input.exit(), 0)?;
     let second_start_time = first_path.len() - 1;
-    let second_path = search_astar(input.goal(second_start_time), input.start(0), &info).unwrap();
-    let third_start_time = first_path.len() + second_path.len() - 2;
-    let third_path = search_astar(input.start(third_start_time), input.goal(0), &info).unwrap();
-    Ok(first_path.len() + second_path.len() + third_path.len() - 3)
+    let second_path = shortest_crossing(&info, input.exit(), input.entrance(), second_start_time)?;
+    let third_start_time = second_start_time + second_path.len() - 1;
+    let third_path = shortest_crossing(&info, input.entrance(), input.exit(), third_start_time)?;
+    Ok(third_start_time + third_path.len() - 1)
 }
 
 fn main() -> anyhow::Result<()> {
@@ -361,6 +422,7 @@ fn main() -> anyhow::Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use astar::search_astar_instrumented;
 
     static SAMPLE: &str = indoc::indoc! {"
         #.######
@@ -402,6 +464,135 @@ mod tests {
         assert_eq!(part2(map).unwrap(), 30);
     }
 
+    #[test]
+    fn shortest_crossing_sample() {
+        let input = SAMPLE.parse::<Input>().unwrap();
+        let path = shortest_crossing(&input.info(), input.entrance(), input.exit(), 0).unwrap();
+        let positions = path.iter().map(|state| (state.row, state.col)).collect::<Vec<_>>();
+        assert_eq!(positions.len(), 19);
+        assert_eq!(positions[..4], [(-1, 0), (0, 0), (1, 0), (1, 0)]);
+        assert_eq!(positions[18], (4, 5));
+    }
+
+    #[test]
+    fn exact_goal() {
+        let input = SAMPLE.parse::<Input>().unwrap();
+        let mut info = input.info();
+        info.exact_goal = true;
+        let start = TraversalState {
+            cycle: 0,
+            row: -1,
+            col: 0,
+        };
+        let exit = input.exit();
+        // The quickest crossing arrives at minute 18, which is cycle 6.
+        let goal = TraversalState {
+            cycle: 6,
+            row: exit.row,
+            col: exit.col,
+        };
+        assert_eq!(search_astar(start.clone(), goal, &info).unwrap().len(), 19);
+        // Arriving at cycle 5 instead means being a little late; the next chance is at minute 29.
+        let goal = TraversalState {
+            cycle: 5,
+            row: exit.row,
+            col: exit.col,
+        };
+        let path = search_astar(start, goal.clone(), &info).unwrap();
+        assert_eq!(path.last(), Some(&goal));
+        assert_eq!(path.len(), 30);
+    }
+
+    /// The same search node, but with the plain Manhattan distance as its heuristic.
+    #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+    struct Plain(TraversalState);
+    impl AStarNode for Plain {
+        type Cost = i64;
+        type AssociatedState = TraversalSharedInfo;
+        fn heuristic(&self, goal: &Self, _: &TraversalSharedInfo) -> i64 {
+            Point::new(self.0.row, self.0.col).manhattan(&Point::new(goal.0.row, goal.0.col))
+        }
+        fn neighbors(&self, state: &TraversalSharedInfo) -> impl Iterator<Item = (Self, i64)> {
+            self.0.neighbors(state).map(|(node, cost)| (Plain(node), cost))
+        }
+        fn goal_match(&self, goal: &Self, state: &TraversalSharedInfo) -> bool {
+            self.0.goal_match(&goal.0, state)
+        }
+    }
+
+    #[test]
+    fn heuristic_is_admissible() {
+        let input = SAMPLE.parse::<Input>().unwrap();
+        let info = input.info();
+        let mut stronger = 0;
+        for (from, to) in [(input.entrance(), input.exit()), (input.exit(), input.entrance())] {
+            let goal = TraversalState {
+                cycle: 0,
+                row: to.row,
+                col: to.col,
+            };
+            let cells = (0..input.height).flat_map(|row| (0..input.width).map(move |col| Point { row, col }));
+            for spot in cells.chain([from, to]) {
+                for cycle in 0..info.cycle_modulo {
+                    if info.blizzard_spots[cycle].contains(&spot) {
+                        continue;
+                    }
+                    let here = TraversalState {
+                        cycle,
+                        row: spot.row,
+                        col: spot.col,
+                    };
+                    // Some spots are about to be boxed in by blizzards, with no way out at all.
+                    let Some(path) = search_astar(Plain(here.clone()), Plain(goal.clone()), &info) else {
+                        continue;
+                    };
+                    let actual = path.len() as i64 - 1;
+                    let estimate = here.heuristic(&goal, &info);
+                    assert!(
+                        estimate <= actual,
+                        "{here:?}: estimated {estimate}, but it takes {actual}"
+                    );
+                    if estimate > Plain(here).heuristic(&Plain(goal.clone()), &info) {
+                        stronger += 1;
+                    }
+                }
+            }
+        }
+        assert!(stronger > 0);
+    }
+
+    #[test]
+    fn heuristic_saves_expansions() {
+        let input = SAMPLE.parse::<Input>().unwrap();
+        let info = input.info();
+        let legs = [
+            (input.entrance(), input.exit(), 0, 18),
+            (input.exit(), input.entrance(), 18, 23),
+            (input.entrance(), input.exit(), 41, 13),
+        ];
+        let (mut plain_total, mut door_total) = (0, 0);
+        for (from, to, minute, length) in legs {
+            let start = TraversalState {
+                cycle: minute % info.cycle_modulo,
+                row: from.row,
+                col: from.col,
+            };
+            let goal = TraversalState {
+                cycle: 0,
+                row: to.row,
+                col: to.col,
+            };
+            let (plain_path, plain_stats) = search_astar_instrumented(Plain(start.clone()), Plain(goal.clone()), &info);
+            let (path, stats) = search_astar_instrumented(start, goal, &info);
+            assert_eq!(plain_path.unwrap().len(), length + 1);
+            assert_eq!(path.unwrap().len(), length + 1);
+            assert!(stats.expanded <= plain_stats.expanded);
+            plain_total += plain_stats.expanded;
+            door_total += stats.expanded;
+        }
+        assert!(door_total < plain_total, "{door_total} vs {plain_total}");
+    }
+
     #[test]
     fn part2_sample() {
         assert_eq!(part2(SAMPLE).unwrap(), 54);
@@ -2,9 +2,12 @@
 //!
 //! Ref: [Advent of Code 2023 Day 5](https://adventofcode.com/2023/day/5)
 //!
+use ahash::AHashMap;
 use anyhow::{anyhow, bail, Error, Result};
 use once_cell::sync::Lazy;
+use ranges::{simplify, PiecewiseShift};
 use regex::Regex;
+use std::fmt;
 use std::io::{self, Read};
 use std::ops::Range;
 use std::str::{FromStr, Lines};
@@ -32,6 +35,9 @@ impl FromStr for MapRange {
         if items.next().is_some() {
             bail!("Too many items for MapRange");
         }
+        if range_length < 0 {
+            bail!("MapRange length may not be negative (was {range_length})");
+        }
         Ok(MapRange {
             destination_start,
             source_start,
@@ -40,135 +46,159 @@ impl FromStr for MapRange {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq, Eq)]
 struct PlantMap {
     map: Vec<MapRange>,
+    // The same ranges as `map`, as a set of shifted intervals: each range's source span, and how far it moves values.
+    shift: PiecewiseShift<i64>,
 }
 
-fn intersect<T>(left: &Range<T>, right: &Range<T>) -> Option<Range<T>>
-where
-    T: Ord + Copy,
-{
-    let start = left.start.max(right.start);
-    let end = left.end.min(right.end);
-    if start < end {
-        Some(start..end)
-    } else {
-        None
+impl From<Vec<MapRange>> for PlantMap {
+    fn from(map: Vec<MapRange>) -> Self {
+        let shift = map
+            .iter()
+            .map(|rng| {
+                (
+                    rng.source_start..rng.source_start + rng.range_length,
+                    rng.destination_start - rng.source_start,
+                )
+            })
+            .collect();
+        PlantMap { map, shift }
     }
 }
 
-fn simplify<T>(mut items: Vec<Range<T>>) -> Vec<Range<T>>
-where
-    T: Ord + Copy,
-{
-    items.sort_by_key(|item| item.start);
-    let mut output: Vec<Range<T>> = vec![];
-    'outer: for item in items {
-        for outs in output.iter_mut() {
-            if item.start <= outs.end {
-                if item.end > outs.end {
-                    outs.end = item.end;
-                }
-                continue 'outer;
-            }
+impl fmt::Display for PlantMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for rng in &self.map {
+            writeln!(f, "{} {} {}", rng.destination_start, rng.source_start, rng.range_length)?;
         }
-        output.push(item)
+        Ok(())
     }
-    output
 }
 
 impl PlantMap {
     fn transition(&self, incoming: i64) -> i64 {
-        for rng in &self.map {
-            if (rng.source_start..rng.source_start + rng.range_length).contains(&incoming) {
-                return incoming - rng.source_start + rng.destination_start;
+        self.shift.apply(incoming)
+    }
+
+    fn range_transition(&self, incoming: &[Range<i64>]) -> Vec<Range<i64>> {
+        simplify(
+            incoming
+                .iter()
+                .flat_map(|item| self.shift.apply_range(item.clone()))
+                .collect(),
+        )
+    }
+}
+
+/// One link in the almanac's chain: the map converting one category of number into the next.
+#[derive(Debug, PartialEq, Eq)]
+struct Stage {
+    source: String,
+    destination: String,
+    map: PlantMap,
+}
+
+/// The category every almanac chain starts from.
+const CHAIN_START: &str = "seed";
+/// The category every almanac chain ends at.
+const CHAIN_END: &str = "location";
+
+#[derive(Debug, PartialEq, Eq)]
+struct Almanac {
+    // In chain order, so that `stages[0].source` is "seed" and the last destination is "location".
+    stages: Vec<Stage>,
+}
+
+/// Writes the maps back out in the input's format, in chain order, with a blank line between them.
+impl fmt::Display for Almanac {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, stage) in self.stages.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
             }
+            writeln!(f, "{}-to-{} map:", stage.source, stage.destination)?;
+            write!(f, "{}", stage.map)?;
         }
-        incoming
+        Ok(())
     }
+}
 
-    fn one_range(&self, incoming: Range<i64>) -> Vec<Range<i64>> {
-        let mut untransitioned = vec![incoming];
-        let mut transitioned = Vec::<Range<i64>>::new();
-
-        'new_work: while let Some(work_item) = untransitioned.pop() {
-            for rng in self.map.iter() {
-                if let Some(intersection) =
-                    intersect(&work_item, &(rng.source_start..rng.source_start + rng.range_length))
-                {
-                    let delta = rng.destination_start - rng.source_start;
-                    transitioned.push(intersection.start + delta..intersection.end + delta);
-                    if work_item.start < intersection.start {
-                        untransitioned.push(work_item.start..intersection.start);
-                    }
-                    if work_item.end > intersection.end {
-                        untransitioned.push(intersection.end..work_item.end);
-                    }
-                    continue 'new_work;
-                }
+impl Almanac {
+    /// Builds the seed-to-location chain out of a set of maps, which may have been listed in any order.
+    fn from_stages(stages: Vec<Stage>) -> Result<Self> {
+        let mut by_source = AHashMap::new();
+        for stage in stages {
+            if let Some(previous) = by_source.insert(stage.source.clone(), stage) {
+                bail!("Multiple maps convert from {}", previous.source);
             }
-            transitioned.push(work_item);
         }
-        simplify(transitioned)
+        let mut chain = vec![];
+        let mut category = CHAIN_START.to_string();
+        while category != CHAIN_END {
+            let stage = by_source
+                .remove(&category)
+                .ok_or_else(|| anyhow!("Broken chain: no map converts from {category}"))?;
+            category.clone_from(&stage.destination);
+            chain.push(stage);
+        }
+        if let Some(stage) = by_source.values().next() {
+            bail!(
+                "The {}-to-{} map is not part of the {CHAIN_START}-to-{CHAIN_END} chain",
+                stage.source,
+                stage.destination
+            );
+        }
+        Ok(Almanac { stages: chain })
     }
 
-    fn range_transition(&self, incoming: &[Range<i64>]) -> Vec<Range<i64>> {
-        let mut outgoing = vec![];
-        for item in incoming {
-            outgoing.extend(self.one_range(item.clone()));
-        }
-        simplify(outgoing)
+    /// The categories of number the chain passes through, in order, from "seed" to "location".
+    #[cfg(test)]
+    fn categories(&self) -> Vec<&str> {
+        let first = self.stages.first().map(|stage| stage.source.as_str());
+        first
+            .into_iter()
+            .chain(self.stages.iter().map(|stage| stage.destination.as_str()))
+            .collect()
     }
-}
 
-#[derive(Debug)]
-struct Almanac {
-    seed_to_soil: PlantMap,
-    soil_to_fertilizer: PlantMap,
-    fertilizer_to_water: PlantMap,
-    water_to_light: PlantMap,
-    light_to_temperature: PlantMap,
-    temperature_to_humidity: PlantMap,
-    humidity_to_location: PlantMap,
-}
+    /// The number a seed corresponds to in each category, in chain order (so the seed itself comes first, and its
+    /// location last).
+    #[cfg(test)]
+    fn trace(&self, seed: i64) -> Vec<(String, i64)> {
+        let mut value = seed;
+        let mut values = vec![value];
+        for stage in self.stages.iter() {
+            value = stage.map.transition(value);
+            values.push(value);
+        }
+        self.categories().into_iter().map(String::from).zip(values).collect()
+    }
 
-impl Almanac {
     fn seed_to_location(&self, seed: i64) -> i64 {
-        self.humidity_to_location.transition(
-            self.temperature_to_humidity.transition(
-                self.light_to_temperature.transition(
-                    self.water_to_light.transition(
-                        self.fertilizer_to_water
-                            .transition(self.soil_to_fertilizer.transition(self.seed_to_soil.transition(seed))),
-                    ),
-                ),
-            ),
-        )
+        self.stages
+            .iter()
+            .fold(seed, |value, stage| stage.map.transition(value))
     }
 
-    fn seed_range_to_location_range(&self, seed_range: Range<i64>) -> Vec<Range<i64>> {
-        self.humidity_to_location.range_transition(
-            self.temperature_to_humidity
-                .range_transition(
-                    self.light_to_temperature
-                        .range_transition(
-                            self.water_to_light
-                                .range_transition(
-                                    self.fertilizer_to_water
-                                        .range_transition(
-                                            self.soil_to_fertilizer
-                                                .range_transition(self.seed_to_soil.one_range(seed_range).as_slice())
-                                                .as_slice(),
-                                        )
-                                        .as_slice(),
-                                )
-                                .as_slice(),
-                        )
-                        .as_slice(),
-                )
-                .as_slice(),
-        )
+    /// The locations the seeds in `seed_ranges` end up at, as a sorted list of disjoint ranges.
+    ///
+    /// All the ranges go down the chain together. After each stage, ranges that another range already covers are
+    /// dropped and overlapping ones merged, so no span of numbers is ever mapped twice, however much the seed
+    /// ranges (or their images) overlap.
+    fn seed_ranges_to_location_ranges(&self, seed_ranges: &[Range<i64>]) -> Vec<Range<i64>> {
+        let seeds = simplify(seed_ranges.iter().filter(|rng| !rng.is_empty()).cloned().collect());
+        self.stages
+            .iter()
+            .fold(seeds, |ranges, stage| stage.map.range_transition(&ranges))
+    }
+
+    /// The lowest location reachable from any of the seeds in `seeds` (or `i64::MAX`, if there are no seeds).
+    fn min_location_for_ranges(&self, seeds: &[Range<i64>]) -> i64 {
+        let locations = self.seed_ranges_to_location_ranges(seeds);
+        // The ranges come back sorted, so the first one holds the lowest location.
+        locations.first().map_or(i64::MAX, |locations| locations.start)
     }
 }
 
@@ -209,28 +239,34 @@ impl FromStr for Input {
 
         blank_line(lines.next())?;
 
-        fn grab_map(lines: &mut Lines, name: &str) -> Result<PlantMap> {
-            let header = grab(lines.next())?;
-            if header != format!("{name} map:") {
-                bail!("Expected {name} map header: {header}");
-            }
-            let map = PlantMap {
-                map: lines
-                    .map_while(|line| line.parse::<MapRange>().ok())
-                    .collect::<Vec<_>>(),
+        fn grab_map(lines: &mut Lines) -> Result<Option<Stage>> {
+            static HEADER_PATTERN: Lazy<Regex> =
+                Lazy::new(|| Regex::new(r"^(?<source>[a-z]+)-to-(?<destination>[a-z]+) map:$").unwrap());
+            let header = match lines.find(|line| !line.is_empty()) {
+                None => return Ok(None),
+                Some(line) => line,
             };
-            Ok(map)
+            let caps = HEADER_PATTERN
+                .captures(header)
+                .ok_or_else(|| anyhow!("Expected a map header: {header}"))?;
+            let map = PlantMap::from(
+                lines
+                    .take_while(|line| !line.is_empty())
+                    .map(|line| line.parse::<MapRange>())
+                    .collect::<Result<Vec<_>>>()?,
+            );
+            Ok(Some(Stage {
+                source: caps["source"].to_string(),
+                destination: caps["destination"].to_string(),
+                map,
+            }))
         }
 
-        let almanac = Almanac {
-            seed_to_soil: grab_map(&mut lines, "seed-to-soil")?,
-            soil_to_fertilizer: grab_map(&mut lines, "soil-to-fertilizer")?,
-            fertilizer_to_water: grab_map(&mut lines, "fertilizer-to-water")?,
-            water_to_light: grab_map(&mut lines, "water-to-light")?,
-            light_to_temperature: grab_map(&mut lines, "light-to-temperature")?,
-            temperature_to_humidity: grab_map(&mut lines, "temperature-to-humidity")?,
-            humidity_to_location: grab_map(&mut lines, "humidity-to-location")?,
-        };
+        let mut stages = vec![];
+        while let Some(stage) = grab_map(&mut lines)? {
+            stages.push(stage);
+        }
+        let almanac = Almanac::from_stages(stages)?;
 
         Ok(Input { initial_seeds, almanac })
     }
@@ -260,13 +296,7 @@ fn part1(input: &str) -> Result<i64> {
 fn part2(input: &str) -> Result<i64> {
     let my_input = input.parse::<Input>()?;
 
-    let locations = my_input
-        .seeds_as_ranges()
-        .into_iter()
-        .flat_map(|seeds| my_input.almanac.seed_range_to_location_range(seeds))
-        .collect::<Vec<_>>();
-    let locations_simplified = simplify(locations);
-    Ok(locations_simplified[0].start)
+    Ok(my_input.almanac.min_location_for_ranges(&my_input.seeds_as_ranges()))
 }
 
 fn main() -> Result<()> {
@@ -328,11 +358,114 @@ mod tests {
         assert_eq!(part1(SAMPLE).unwrap(), 35);
     }
 
+    #[test]
+    fn trace() {
+        let almanac = SAMPLE.parse::<Input>().unwrap().almanac;
+        assert_eq!(
+            almanac.categories(),
+            [
+                "seed",
+                "soil",
+                "fertilizer",
+                "water",
+                "light",
+                "temperature",
+                "humidity",
+                "location"
+            ]
+        );
+        let trace = almanac.trace(79);
+        let expected = [
+            ("seed", 79),
+            ("soil", 81),
+            ("fertilizer", 81),
+            ("water", 81),
+            ("light", 74),
+            ("temperature", 78),
+            ("humidity", 78),
+            ("location", 82),
+        ];
+        assert_eq!(trace, expected.map(|(category, value)| (category.to_string(), value)));
+        assert_eq!(trace.last().unwrap().1, almanac.seed_to_location(79));
+    }
+
     #[test]
     fn part2_sample() {
         assert_eq!(part2(SAMPLE).unwrap(), 46);
     }
 
+    #[test_case(&[79..93, 55..68] => 46; "sample seeds")]
+    #[test_case(&[82..83] => 46; "single seed")]
+    #[test_case(&[79..93] => 46; "first range")]
+    #[test_case(&[55..68] => 56; "second range")]
+    #[test_case(&[] => i64::MAX; "no seeds")]
+    #[test_case(&[60..60, 82..83] => 46; "empty range")]
+    #[test_case(&[55..68, 79..93, 80..85, 55..60] => 46; "overlapping ranges")]
+    fn min_location_for_ranges(seeds: &[Range<i64>]) -> i64 {
+        let input = SAMPLE.parse::<Input>().unwrap();
+        input.almanac.min_location_for_ranges(seeds)
+    }
+
+    /// The sample, with its maps listed in reverse order, and each map's ranges also reversed.
+    fn shuffled_sample() -> String {
+        let sections = SAMPLE.trim_end().split("\n\n").collect::<Vec<_>>();
+        let seeds = sections[0].to_string();
+        let maps = sections[1..].iter().rev().map(|map| {
+            let mut lines = map.lines().collect::<Vec<_>>();
+            lines[1..].reverse();
+            lines.join("\n")
+        });
+        std::iter::once(seeds).chain(maps).collect::<Vec<_>>().join("\n\n") + "\n"
+    }
+
+    #[test]
+    fn shuffled_sections() {
+        let shuffled = shuffled_sample();
+        assert_ne!(shuffled, SAMPLE);
+        assert!(shuffled.contains("humidity-to-location map:\n56 93 4\n60 56 37\n\ntemperature-to-humidity"));
+        assert_eq!(part1(&shuffled).unwrap(), 35);
+        assert_eq!(part2(&shuffled).unwrap(), 46);
+
+        let stages = shuffled.parse::<Input>().unwrap().almanac.stages;
+        assert_eq!(stages.first().unwrap().source, "seed");
+        assert_eq!(stages.last().unwrap().destination, "location");
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let almanac = SAMPLE.parse::<Input>().unwrap().almanac;
+        let displayed = almanac.to_string();
+        // The sample is already in canonical form, so it comes back unchanged.
+        assert_eq!(format!("seeds: 79 14 55 13\n\n{displayed}"), SAMPLE);
+
+        let reparsed = format!("seeds: 1\n\n{displayed}").parse::<Input>().unwrap().almanac;
+        assert_eq!(reparsed, almanac);
+
+        // Maps listed out of order are written back in chain order.
+        let shuffled = shuffled_sample().parse::<Input>().unwrap().almanac;
+        assert!(shuffled
+            .to_string()
+            .starts_with("seed-to-soil map:\n52 50 48\n50 98 2\n\nsoil-to-fertilizer"));
+    }
+
+    #[test_case("soil-to-fertilizer map:", "soil-to-dirt map:" => "Broken chain: no map converts from dirt"; "missing link")]
+    #[test_case("water-to-light map:", "soil-to-light map:" => "Multiple maps convert from soil"; "duplicate source")]
+    #[test_case("seeds: 79 14 55 13\n", "seeds: 79 14 55 13\n\nrock-to-sand map:\n1 2 3\n" => "The rock-to-sand map is not part of the seed-to-location chain"; "orphan map")]
+    #[test_case("52 50 48", "52 fifty 48" => "invalid digit found in string"; "bad range")]
+    #[test_case("52 50 48", "52 50 -48" => "MapRange length may not be negative (was -48)"; "negative length")]
+    fn broken_chain(from: &str, to: &str) -> String {
+        SAMPLE.replace(from, to).parse::<Input>().unwrap_err().to_string()
+    }
+
+    #[test_case("1 2 0" => Ok(MapRange { destination_start: 1, source_start: 2, range_length: 0 }); "zero length")]
+    #[test_case("-1 -2 3" => Ok(MapRange { destination_start: -1, source_start: -2, range_length: 3 }); "negative starts")]
+    #[test_case("1 2 -3" => Err("MapRange length may not be negative (was -3)".to_string()); "negative length")]
+    #[test_case("1 2" => Err("Not enough elements for MapRange".to_string()); "too short")]
+    #[test_case("1 2 3 4" => Err("Too many items for MapRange".to_string()); "too long")]
+    fn map_range(s: &str) -> Result<MapRange, String> {
+        s.parse::<MapRange>().map_err(|err| err.to_string())
+    }
+
     #[test_case(vec![] => Vec::<Range<i64>>::new(); "empty input")]
     #[test_case(vec![0..22, 56..102] => vec![0..22, 56..102]; "does nothing")]
     #[test_case(vec![0..10, 10..20, 20..30] => vec![0..30]; "collapse on edges")]
@@ -343,32 +476,30 @@ mod tests {
     }
 
     fn map_1() -> PlantMap {
-        PlantMap {
-            map: vec![
-                MapRange {
-                    destination_start: 50,
-                    source_start: 10,
-                    range_length: 10,
-                },
-                MapRange {
-                    destination_start: 40,
-                    source_start: 20,
-                    range_length: 10,
-                },
-                MapRange {
-                    destination_start: 10,
-                    source_start: 40,
-                    range_length: 20,
-                },
-            ],
-        }
+        PlantMap::from(vec![
+            MapRange {
+                destination_start: 50,
+                source_start: 10,
+                range_length: 10,
+            },
+            MapRange {
+                destination_start: 40,
+                source_start: 20,
+                range_length: 10,
+            },
+            MapRange {
+                destination_start: 10,
+                source_start: 40,
+                range_length: 20,
+            },
+        ])
     }
 
     #[test_case(
-        PlantMap { map: vec![
+        PlantMap::from(vec![
                 MapRange { destination_start: 99, source_start: 10, range_length: 30 },
                 MapRange { destination_start: 10, source_start: 99, range_length: 30 },
-            ] },
+            ]),
         &[0..10]
         => vec![0..10];
         "unaltered range"
@@ -2,7 +2,7 @@
 //!
 //! Ref: [Advent of Code 2023 Day 10](https://adventofcode.com/2023/day/10)
 //!
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use anyhow::{anyhow, bail, Error, Result};
 use std::fmt;
 use std::io::{self, Read};
@@ -52,7 +52,7 @@ impl TryFrom<char> for GridContent {
             'J' => NorthWest,
             'F' => SouthEast,
             'S' => StartingPosition,
-            _ => bail!("Invalid character for grid"),
+            _ => bail!("Invalid character for grid: {value:?}"),
         })
     }
 }
@@ -81,11 +81,27 @@ impl FromStr for Grid {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let grid = s
-            .lines()
+        // Tolerate CRLF line endings and trailing whitespace (including blank trailing lines). Lines needn't all be
+        // the same length: the grid is as wide as the longest, and anything missing from the end of a shorter line
+        // is empty ground.
+        let mut lines = s.lines().map(str::trim_end).collect::<Vec<_>>();
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .ok_or_else(|| anyhow!("Empty grid"))?;
+        let height = lines.len();
+
+        let grid = lines
+            .into_iter()
             .enumerate()
             .map(|(row, line)| {
                 line.chars()
+                    .chain(std::iter::repeat('.'))
+                    .take(width)
                     .enumerate()
                     .map(|(col, ch)| Ok::<_, Self::Err>(((row as i64, col as i64), GridContent::try_from(ch)?)))
                     .collect::<Result<Vec<_>, _>>()
@@ -94,8 +110,8 @@ impl FromStr for Grid {
             .into_iter()
             .flatten()
             .collect::<AHashMap<_, _>>();
-        let width = grid.keys().max_by_key(|(_, col)| *col).unwrap().1 + 1;
-        let height = grid.keys().max_by_key(|(row, _)| *row).unwrap().0 + 1;
+        let width = i64::try_from(width)?;
+        let height = i64::try_from(height)?;
         let start = grid
             .iter()
             .find_map(|(key, val)| {
@@ -106,12 +122,14 @@ impl FromStr for Grid {
                 }
             })
             .ok_or_else(|| anyhow!("Missing starting location"))?;
-        Ok(Self {
+        let grid = Self {
             cells: grid,
             width,
             height,
             start,
-        })
+        };
+        grid.start_connections(&start)?;
+        Ok(grid)
     }
 }
 
@@ -176,76 +194,136 @@ impl Grid {
             GridContent::NorthWest => Some((North, West)),
             GridContent::SouthEast => Some((South, East)),
             GridContent::SouthWest => Some((South, West)),
-            GridContent::StartingPosition => {
-                let items = [(-1, 0, North), (1, 0, South), (0, -1, West), (0, 1, East)]
-                    .into_iter()
-                    .filter_map(|(drow, dcol, dir)| {
-                        let probe_location = (location.0 + drow, location.1 + dcol);
-                        if self.cells.get(&probe_location).is_some() {
-                            if let Some((d1, d2)) = self.connections(&probe_location) {
-                                if d1.opposite() == dir || d2.opposite() == dir {
-                                    Some(dir)
-                                } else {
-                                    None
-                                }
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>();
-                if !items.is_empty() {
-                    assert_eq!(items.len(), 2);
-                    Some((items[0], items[1]))
-                } else {
-                    None
-                }
-            }
+            GridContent::StartingPosition => self.start_connections(location).ok(),
+        }
+    }
+
+    /// The two directions the starting position connects in, worked out from the pipes around it.
+    ///
+    /// Neighbors off the edge of the grid simply don't connect; anything other than exactly two connecting
+    /// neighbors means the start isn't on a well-formed loop.
+    fn start_connections(&self, location: &(i64, i64)) -> Result<(Direction, Direction)> {
+        use Direction::*;
+        let items = [(-1, 0, North), (1, 0, South), (0, -1, West), (0, 1, East)]
+            .into_iter()
+            .filter_map(|(drow, dcol, dir)| {
+                let probe_location = (location.0 + drow, location.1 + dcol);
+                let (d1, d2) = self.connections(&probe_location)?;
+                (d1.opposite() == dir || d2.opposite() == dir).then_some(dir)
+            })
+            .collect::<Vec<_>>();
+        match items[..] {
+            [first, second] => Ok((first, second)),
+            _ => bail!(
+                "Starting position at {location:?} connects to {} pipes (it should connect to 2)",
+                items.len()
+            ),
         }
     }
-    fn next_location(&self, prior: Option<(i64, i64)>, current: Option<(i64, i64)>) -> Option<(i64, i64)> {
+    /// The pipe hidden under the starting position, deduced from the pipes it connects to.
+    fn start_pipe(&self) -> GridContent {
+        use Direction::*;
+        let connections = self
+            .start_connections(&self.start)
+            .expect("the start was checked when the grid was parsed");
+        match connections {
+            (North, South) | (South, North) => GridContent::NorthSouth,
+            (East, West) | (West, East) => GridContent::EastWest,
+            (North, East) | (East, North) => GridContent::NorthEast,
+            (North, West) | (West, North) => GridContent::NorthWest,
+            (South, East) | (East, South) => GridContent::SouthEast,
+            (South, West) | (West, South) => GridContent::SouthWest,
+            _ => unreachable!("the start's two connections are in different directions"),
+        }
+    }
+
+    /// The step along the loop after `current`, having come from `prior`; `None` once the loop is back at the start.
+    ///
+    /// It's an error if the pipes don't actually carry on from `current`: if there's no pipe there, or if the pipe
+    /// there doesn't connect back the way we came.
+    fn next_location(&self, prior: Option<(i64, i64)>, current: Option<(i64, i64)>) -> Result<Option<(i64, i64)>> {
         match current {
-            None => Some(self.start),
+            None => Ok(Some(self.start)),
             Some(current) => {
-                let connections = self.connections(&current).unwrap();
+                let connections = self
+                    .connections(&current)
+                    .ok_or_else(|| anyhow!("The loop through the start breaks off at {current:?}"))?;
                 match prior {
-                    Some((prior_row, prior_col)) => {
-                        let probe = go(&current, connections.0);
-                        let next = if probe.0 == prior_row && probe.1 == prior_col {
+                    Some(prior) => {
+                        let next = if go(&current, connections.0) == prior {
                             go(&current, connections.1)
+                        } else if go(&current, connections.1) == prior {
+                            go(&current, connections.0)
                         } else {
-                            probe
+                            bail!("The pipe at {current:?} doesn't connect back to {prior:?}");
                         };
                         if next == self.start {
-                            None
+                            Ok(None)
                         } else {
-                            Some(next)
+                            Ok(Some(next))
                         }
                     }
-                    None => Some(go(&current, connections.0)),
+                    None => Ok(Some(go(&current, connections.0))),
                 }
             }
         }
     }
-    fn path(&self) -> Vec<(i64, i64)> {
+
+    /// The cells of the loop, in order, starting from the start. It's an error if the pipes from the start don't
+    /// lead back around to it.
+    fn path(&self) -> Result<Vec<(i64, i64)>> {
         let mut cursor = None;
         let mut path = vec![];
         let mut prior = None;
-        while let Some(new_loc) = self.next_location(prior, cursor) {
+        while let Some(new_loc) = self.next_location(prior, cursor)? {
             path.push(new_loc);
             prior = cursor;
             cursor = Some(new_loc);
         }
-        path
+        Ok(path)
+    }
+
+    /// The cells making up the loop, for when only membership matters and not the order.
+    fn loop_cells(&self) -> Result<AHashSet<(i64, i64)>> {
+        Ok(self.path()?.into_iter().collect())
     }
 
-    fn inclusions(&self) -> usize {
+    /// The number of steps along the loop to the point farthest from the start.
+    ///
+    /// This walks the loop in both directions at once, stopping where the two walkers meet, so only half the
+    /// loop is visited and nothing is collected along the way.
+    fn farthest_distance(&self) -> Result<usize> {
+        let (first, second) = self.start_connections(&self.start)?;
+        let mut forward = (self.start, go(&self.start, first));
+        let mut backward = (self.start, go(&self.start, second));
+        let mut steps = 1;
+        while forward.1 != backward.1 && forward.0 != backward.1 {
+            let next = self
+                .next_location(Some(forward.0), Some(forward.1))?
+                .expect("the walkers meet before either gets back to the start");
+            forward = (forward.1, next);
+            let next = self
+                .next_location(Some(backward.0), Some(backward.1))?
+                .expect("the walkers meet before either gets back to the start");
+            backward = (backward.1, next);
+            steps += 1;
+        }
+        if forward.1 == backward.1 {
+            // The walkers met on a cell, so make sure the pipe there really joins up the two halves.
+            let onward = self.next_location(Some(forward.0), Some(forward.1))?;
+            if onward != Some(backward.0) {
+                bail!("The pipe at {:?} doesn't connect back to {:?}", forward.1, backward.0);
+            }
+        }
+        Ok(steps)
+    }
+
+    fn inclusions(&self) -> Result<usize> {
         use PathHistory::*;
         use State::*;
 
-        let path = self.path();
+        let path = self.loop_cells()?;
+        let start_pipe = self.start_pipe();
         let mut inclusions = 0;
         for row in 0..self.height {
             let mut state = Outside;
@@ -260,9 +338,14 @@ impl Grid {
                         }
                     }
                 } else {
-                    let item = self.cells.get(&loc).unwrap();
+                    // The start is scanned as whatever pipe it stands in for.
+                    let item = if loc == self.start {
+                        start_pipe
+                    } else {
+                        self.cells[&loc]
+                    };
                     match item {
-                        GridContent::Empty => unreachable!(),
+                        GridContent::Empty | GridContent::StartingPosition => unreachable!(),
                         GridContent::NorthSouth => {
                             state = !state;
                         }
@@ -283,45 +366,19 @@ impl Grid {
                                 state = !state;
                             }
                         }
-                        GridContent::StartingPosition => {
-                            let connections = self.connections(&loc).unwrap();
-                            match connections {
-                                (Direction::North, Direction::South) | (Direction::South, Direction::North) => {
-                                    state = !state;
-                                }
-                                (Direction::North, Direction::East) | (Direction::East, Direction::North) => {
-                                    path_state = WasNorth;
-                                }
-                                (Direction::North, Direction::West) | (Direction::West, Direction::North) => {
-                                    if path_state != WasNorth {
-                                        state = !state;
-                                    }
-                                }
-                                (Direction::South, Direction::East) | (Direction::East, Direction::South) => {
-                                    path_state = WasSouth;
-                                }
-                                (Direction::South, Direction::West) | (Direction::West, Direction::South) => {
-                                    if path_state == WasNorth {
-                                        state = !state;
-                                    }
-                                }
-                                (Direction::East, Direction::West) | (Direction::West, Direction::East) => {}
-                                _ => unreachable!(),
-                            }
-                        }
                     }
                 }
             }
         }
-        inclusions
+        Ok(inclusions)
     }
 }
 
-fn part1(input: &Grid) -> usize {
-    input.path().len() / 2
+fn part1(input: &Grid) -> Result<usize> {
+    input.farthest_distance()
 }
 
-fn part2(input: &Grid) -> usize {
+fn part2(input: &Grid) -> Result<usize> {
     input.inclusions()
 }
 
@@ -333,8 +390,8 @@ fn main() -> Result<()> {
 
     let grid = Grid::from_str(&input)?;
 
-    println!("Part1: {}", part1(&grid));
-    println!("Part2: {}", part2(&grid));
+    println!("Part1: {}", part1(&grid)?);
+    println!("Part2: {}", part2(&grid)?);
 
     Ok(())
 }
@@ -361,10 +418,17 @@ mod tests {
         assert!(dvec.contains(&Direction::South));
     }
 
+    #[test_case(SAMPLE => "┏"; "corner")]
+    #[test_case("F-7\n|.|\nS.|\nL-J\n" => "┃"; "north-south")]
+    #[test_case("F-S-7\n|...|\nL---J\n" => "━"; "east-west")]
+    fn start_pipe(src: &str) -> String {
+        Grid::from_str(src).unwrap().start_pipe().to_string()
+    }
+
     #[test]
     fn get_path() {
         let input = Grid::from_str(SAMPLE).unwrap();
-        let path = input.path();
+        let path = input.path().unwrap();
         assert_eq!(
             path,
             vec![
@@ -388,11 +452,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn loop_cells() {
+        let input = Grid::from_str(SAMPLE).unwrap();
+        let cells = input.loop_cells().unwrap();
+        assert_eq!(cells.len(), input.path().unwrap().len());
+        assert!(cells.contains(&input.start));
+        assert!(!cells.contains(&(0, 0)));
+    }
+
     #[test]
     fn part1_sample() {
         let input = Grid::from_str(SAMPLE).unwrap();
         println!("{input}");
-        assert_eq!(part1(&input), 8);
+        assert_eq!(part1(&input).unwrap(), 8);
+    }
+
+    #[test_case(SAMPLE2)]
+    #[test_case(SAMPLE3)]
+    #[test_case(SAMPLE4)]
+    fn farthest_distance(sample: &str) {
+        let input = Grid::from_str(sample).unwrap();
+        assert_eq!(input.farthest_distance().unwrap(), input.path().unwrap().len() / 2);
     }
 
     static SAMPLE2: &str = indoc::indoc! {"
@@ -433,11 +514,76 @@ mod tests {
         L7JLJL-JLJLJL--JLJ.L
     "};
 
+    #[test]
+    fn crlf_input() {
+        let crlf = SAMPLE.replace('\n', "\r\n");
+        let lf_grid = Grid::from_str(SAMPLE).unwrap();
+        let crlf_grid = Grid::from_str(&crlf).unwrap();
+        assert_eq!(crlf_grid.width, lf_grid.width);
+        assert_eq!(crlf_grid.height, lf_grid.height);
+        assert_eq!(crlf_grid.start, lf_grid.start);
+        assert_eq!(crlf_grid.cells, lf_grid.cells);
+        assert_eq!(crlf_grid.to_string(), lf_grid.to_string());
+    }
+
+    #[test_case(SAMPLE => (8, 1); "sample")]
+    #[test_case(SAMPLE2 => (23, 4); "sample 2")]
+    #[test_case(SAMPLE3 => (70, 8); "sample 3")]
+    fn ragged_grid(sample: &str) -> (usize, usize) {
+        // The same grid, but without the empty ground at the ends of every other line.
+        let ragged = sample
+            .lines()
+            .enumerate()
+            .map(|(row, line)| match row % 2 {
+                0 => format!("{line}\n"),
+                _ => format!("{}\n", line.trim_end_matches('.')),
+            })
+            .collect::<String>();
+        assert_ne!(ragged, sample);
+        let grid = Grid::from_str(&ragged).unwrap();
+        let expected = Grid::from_str(sample).unwrap();
+        assert_eq!(grid.to_string(), expected.to_string());
+        (part1(&grid).unwrap(), part2(&grid).unwrap())
+    }
+
+    #[test]
+    fn trailing_whitespace() {
+        let padded = SAMPLE.lines().map(|line| format!("{line}  \t\n")).collect::<String>() + "\n   \n";
+        let grid = Grid::from_str(&padded).unwrap();
+        assert_eq!(grid.to_string(), Grid::from_str(SAMPLE).unwrap().to_string());
+        assert_eq!(part1(&grid).unwrap(), 8);
+    }
+
+    #[test_case("\n\n" => "Empty grid"; "empty")]
+    #[test_case("S-7\n..|\nL-J\n" => "Starting position at (0, 0) connects to 1 pipes (it should connect to 2)"; "dead end")]
+    #[test_case("S.\n..\n" => "Starting position at (0, 0) connects to 0 pipes (it should connect to 2)"; "no loop")]
+    fn bad_grid(src: &str) -> String {
+        Grid::from_str(src).unwrap_err().to_string()
+    }
+
+    #[test_case("S-7\n|.|\nL-J\n" => (4, 1); "corner")]
+    #[test_case("F-S-7\n|...|\nL---J\n" => (6, 3); "top row")]
+    #[test_case("F-7\n|.|\nS.|\nL-J\n" => (5, 2); "left edge, straight")]
+    fn start_on_border(src: &str) -> (usize, usize) {
+        let grid = Grid::from_str(src).unwrap();
+        (part1(&grid).unwrap(), part2(&grid).unwrap())
+    }
+
+    #[test_case("S-7\n|.|\nL-.\n" => "The loop through the start breaks off at (2, 2)"; "runs into ground")]
+    #[test_case("S-7\n|.|\nL-7\n" => "The loop through the start breaks off at (3, 2)"; "runs off the grid")]
+    #[test_case("S-7\n|.|\nL|J\n" => "The pipe at (2, 1) doesn't connect back to (2, 0)"; "pipe turned the wrong way")]
+    fn no_loop(src: &str) -> String {
+        let grid = Grid::from_str(src).unwrap();
+        assert!(part1(&grid).is_err());
+        assert!(part2(&grid).is_err());
+        grid.path().unwrap_err().to_string()
+    }
+
     #[test_case(SAMPLE2 => 4)]
     #[test_case(SAMPLE3 => 8)]
     #[test_case(SAMPLE4 => 10)]
     fn part2_sample(sample: &str) -> usize {
         let input = Grid::from_str(sample).unwrap();
-        part2(&input)
+        part2(&input).unwrap()
     }
 }
@@ -4,7 +4,7 @@
 //!
 use ahash::AHashSet;
 use anyhow::{anyhow, Error, Result};
-use combinations::Combination;
+use combinations::pairs;
 use std::fmt;
 use std::io::{self, Read};
 use std::str::FromStr;
@@ -124,8 +124,8 @@ impl StarMap {
     fn work_the_puzzle(&self, factor: i64) -> i64 {
         let expanded = self.expand(factor);
         let stars = expanded.stars.iter().copied().collect::<Vec<_>>();
-        Combination::new(&stars, 2)
-            .map(|pair| (pair[0].row - pair[1].row).abs() + (pair[0].col - pair[1].col).abs())
+        pairs(&stars)
+            .map(|(first, second)| (first.row - second.row).abs() + (first.col - second.col).abs())
             .sum::<i64>()
     }
 }
@@ -3,7 +3,7 @@
 //! Ref: [Advent of Code 2024 Day 15](https://adventofcode.com/2024/day/15)
 //!
 use ahash::{AHashMap, AHashSet};
-use anyhow::{anyhow, Error, Result};
+use anyhow::{anyhow, bail, Error, Result};
 use core::fmt;
 use std::collections::VecDeque;
 use std::hash::Hash;
@@ -101,9 +101,13 @@ impl TryFrom<char> for Object {
     }
 }
 
+/// Robots are numbered in reading order (top-to-bottom, then left-to-right), starting from zero.
+type RobotId = usize;
+
 #[derive(Clone)]
 struct Map {
     map: AHashMap<(i64, i64), Object>,
+    robots: Vec<(i64, i64)>, // current location of each robot, indexed by RobotId
 }
 
 impl FromStr for Map {
@@ -127,48 +131,48 @@ impl FromStr for Map {
                 })
             })
             .collect::<Result<AHashMap<_, _>, _>>()?;
-        Ok(Map { map })
+        let mut robots = map
+            .iter()
+            .filter_map(|(position, object)| (*object == Object::Robot).then_some(*position))
+            .collect::<Vec<_>>();
+        robots.sort_unstable();
+        Ok(Map { map, robots })
     }
 }
 
+/// The puzzle's GPS coordinate is `row * GPS_ROW_WEIGHT + col`.
+const GPS_ROW_WEIGHT: i64 = 100;
+
+/// Sums `row * weight + col` for every position in `map` holding `marker`.
+fn weighted_position_sum<T: PartialEq>(map: &AHashMap<(i64, i64), T>, weight: i64, marker: &T) -> i64 {
+    map.iter()
+        .filter_map(|(position, object)| {
+            if object == marker {
+                let (row, col) = *position;
+                Some(row * weight + col)
+            } else {
+                None
+            }
+        })
+        .sum()
+}
+
 impl Map {
     fn gps_sum(&self) -> i64 {
-        self.map
-            .iter()
-            .filter_map(|(position, object)| {
-                if *object == Object::Box {
-                    let (row, col) = *position;
-                    Some(row * 100 + col)
-                } else {
-                    None
-                }
-            })
-            .sum()
+        self.gps_sum_with(GPS_ROW_WEIGHT, Object::Box)
     }
 
-    fn find_robot(&self) -> Option<(i64, i64)> {
-        self.map
-            .iter()
-            .find_map(
-                |(position, object)| {
-                    if *object == Object::Robot {
-                        Some(position)
-                    } else {
-                        None
-                    }
-                },
-            )
-            .copied()
+    fn gps_sum_with(&self, weight: i64, marker: Object) -> i64 {
+        weighted_position_sum(&self.map, weight, &marker)
     }
 
     fn move_robot(&mut self, robot: (i64, i64), d: Direction) -> (i64, i64) {
+        // A robot can push any number of boxes, but it can't push another robot (nor a box into another robot).
+        // Either of those leaves everything where it was.
         let delta = d.delta();
         let new_spot = (robot.0 + delta.0, robot.1 + delta.1);
         match self.map.get(&new_spot) {
-            Some(Object::Wall) => robot,
-            Some(Object::Robot) => {
-                unreachable!()
-            }
+            Some(Object::Wall | Object::Robot) => robot,
             Some(Object::Box) => {
                 let mut stage = 1;
                 loop {
@@ -183,10 +187,9 @@ impl Map {
                             self.map.remove(&robot);
                             break new_spot;
                         }
-                        Some(Object::Wall) => {
+                        Some(Object::Wall | Object::Robot) => {
                             break robot;
                         }
-                        Some(Object::Robot) => unreachable!(),
                         Some(Object::Box) => {}
                     }
                 }
@@ -199,11 +202,76 @@ impl Map {
         }
     }
 
+    fn step_robot(&mut self, id: RobotId, d: Direction) {
+        self.robots[id] = self.move_robot(self.robots[id], d);
+    }
+
     fn run_robot(&mut self, directions: &[Direction]) {
-        let mut robot = self.find_robot().expect("there should be a robot");
-        for &d in directions {
-            robot = self.move_robot(robot, d);
+        self.run_robots(&[(0, directions.to_vec())])
+            .expect("there should be a robot");
+    }
+
+    /// Runs several robots at once, each following its own program.
+    ///
+    /// Moves are interleaved round-robin: every robot with instructions remaining makes one move, in the order the
+    /// programs are given, and then the next round begins. Because moves happen one at a time, contention for a
+    /// cell is settled by that order: whichever robot moves first gets the cell, and a later robot that tries to
+    /// step (or push a box) into it is blocked for that move.
+    fn run_robots(&mut self, programs: &[(RobotId, Vec<Direction>)]) -> Result<()> {
+        if let Some((id, _)) = programs.iter().find(|(id, _)| *id >= self.robots.len()) {
+            bail!("No robot with id {id} (there are {} robots)", self.robots.len());
+        }
+        let rounds = programs
+            .iter()
+            .map(|(_, directions)| directions.len())
+            .max()
+            .unwrap_or(0);
+        for round in 0..rounds {
+            for (id, directions) in programs {
+                if let Some(&d) = directions.get(round) {
+                    self.step_robot(*id, d);
+                }
+            }
         }
+        Ok(())
+    }
+
+    /// The cells on the edge of the map's bounding box that aren't walls. Through any of these, a robot (or a box it
+    /// pushes) could wander off into cells the map knows nothing about.
+    fn leaks(&self) -> Vec<(i64, i64)> {
+        let Some((min_row, max_row, min_col, max_col)) = self.map.keys().fold(None, |acc, &(row, col)| {
+            Some(match acc {
+                None => (row, row, col, col),
+                Some((r0, r1, c0, c1)) => (row.min(r0), row.max(r1), col.min(c0), col.max(c1)),
+            })
+        }) else {
+            return vec![];
+        };
+        let top_and_bottom = (min_col..=max_col).flat_map(|col| [(min_row, col), (max_row, col)]);
+        let sides = (min_row + 1..max_row).flat_map(|row| [(row, min_col), (row, max_col)]);
+        let mut leaks = top_and_bottom
+            .chain(sides)
+            .filter(|spot| self.map.get(spot) != Some(&Object::Wall))
+            .collect::<Vec<_>>();
+        leaks.sort_unstable();
+        leaks.dedup();
+        leaks
+    }
+
+    /// True if the border of the map is solid wall, so nothing inside can ever get out.
+    #[cfg(test)]
+    fn is_enclosed(&self) -> bool {
+        self.leaks().is_empty()
+    }
+
+    /// Errors out, naming the gaps, if the map isn't enclosed. Both parts run this before moving anything, since
+    /// a leaky map would otherwise give a wrong answer rather than an error.
+    fn check_enclosed(&self) -> Result<()> {
+        let leaks = self.leaks();
+        if !leaks.is_empty() {
+            bail!("The map's border has gaps at {leaks:?}");
+        }
+        Ok(())
     }
 }
 
@@ -234,6 +302,8 @@ impl<T: Hash + Eq + Clone> UniqueQueue<T> {
 
 struct WideMap {
     map: AHashMap<(i64, i64), WideObject>,
+    // The box halves that were pushed during the last `run_robot`, at their current locations.
+    moved: AHashSet<(i64, i64)>,
 }
 
 impl From<Map> for WideMap {
@@ -257,17 +327,26 @@ impl From<Map> for WideMap {
                     [Some(left), right].into_iter().flatten()
                 })
                 .collect::<AHashMap<_, _>>(),
+            moved: AHashSet::new(),
         }
     }
 }
 
 impl fmt::Display for WideMap {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_map(f, false)
+    }
+}
+
+impl WideMap {
+    /// Draw the map, optionally showing the boxes pushed during the last `run_robot` as `{}` rather than `[]`.
+    fn write_map(&self, f: &mut impl fmt::Write, highlight_moved: bool) -> fmt::Result {
         let (max_row, max_col) = self.map.iter().fold((i64::MIN, i64::MIN), |acc, ((row, col), _)| {
             (acc.0.max(*row), acc.1.max(*col))
         });
         for row in 0..=max_row {
             for col in 0..=max_col {
+                let moved = highlight_moved && self.moved.contains(&(row, col));
                 let obj = self.map.get(&(row, col));
                 write!(
                     f,
@@ -276,6 +355,8 @@ impl fmt::Display for WideMap {
                         None => '.',
                         Some(WideObject::Robot) => '@',
                         Some(WideObject::Wall) => '#',
+                        Some(WideObject::BoxLeft) if moved => '{',
+                        Some(WideObject::BoxRight) if moved => '}',
                         Some(WideObject::BoxLeft) => '[',
                         Some(WideObject::BoxRight) => ']',
                     }
@@ -285,21 +366,23 @@ impl fmt::Display for WideMap {
         }
         Ok(())
     }
-}
 
-impl WideMap {
+    /// The map, with the boxes that moved during the last `run_robot` drawn as `{}`. (Handy for seeing how far a
+    /// push spread.)
+    #[cfg(test)]
+    fn render_moved(&self) -> String {
+        let mut result = String::new();
+        self.write_map(&mut result, true)
+            .expect("writing to a String should not fail");
+        result
+    }
+
     fn gps_sum(&self) -> i64 {
-        self.map
-            .iter()
-            .filter_map(|(position, object)| {
-                if *object == WideObject::BoxLeft {
-                    let (row, col) = *position;
-                    Some(row * 100 + col)
-                } else {
-                    None
-                }
-            })
-            .sum()
+        self.gps_sum_with(GPS_ROW_WEIGHT, WideObject::BoxLeft)
+    }
+
+    fn gps_sum_with(&self, weight: i64, marker: WideObject) -> i64 {
+        weighted_position_sum(&self.map, weight, &marker)
     }
 
     fn find_robot(&self) -> Option<(i64, i64)> {
@@ -344,6 +427,8 @@ impl WideMap {
                             _ => unreachable!(),
                         };
                     }
+                    self.moved.extend((2..=step).map(|n| (robot.0, robot.1 + delta * n)));
+                    self.moved.remove(&(robot.0, robot.1 + delta));
                     self.map.insert((robot.0, robot.1 + delta), WideObject::Robot);
                     self.map.remove(&robot);
                     break (robot.0, robot.1 + delta);
@@ -385,6 +470,10 @@ impl WideMap {
         // Found only empty space, so we're good to shift all the boxes.
         for b in boxes_to_push.into_iter().rev() {
             let obj = self.map.remove(&b).expect("item should be present");
+            if obj != WideObject::Robot {
+                self.moved.remove(&b);
+                self.moved.insert((b.0 + delta, b.1));
+            }
             self.map.insert((b.0 + delta, b.1), obj);
         }
 
@@ -431,9 +520,32 @@ impl WideMap {
     }
 
     fn run_robot(&mut self, directions: &[Direction]) {
+        self.moved.clear();
         let mut robot = self.find_robot().expect("there should be a robot");
         for &d in directions {
             robot = self.move_robot(robot, d);
+            #[cfg(test)]
+            self.assert_consistent();
+        }
+    }
+
+    /// Panic if any box has come apart: every `BoxLeft` must have a `BoxRight` immediately to its right, and every
+    /// `BoxRight` a `BoxLeft` immediately to its left. (This scans the whole map, so it's only run by the tests,
+    /// after every move.)
+    #[cfg(test)]
+    fn assert_consistent(&self) {
+        for (&(row, col), object) in &self.map {
+            let (partner, expected) = match object {
+                WideObject::BoxLeft => ((row, col + 1), WideObject::BoxRight),
+                WideObject::BoxRight => ((row, col - 1), WideObject::BoxLeft),
+                WideObject::Wall | WideObject::Robot => continue,
+            };
+            assert_eq!(
+                self.map.get(&partner),
+                Some(&expected),
+                "box half at {:?} is missing its partner\n{self}",
+                (row, col)
+            );
         }
     }
 }
@@ -447,28 +559,38 @@ impl FromStr for Input {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        let (map, directions) = s.split_once("\n\n").ok_or_else(|| anyhow!("Bad input"))?;
-        let map = map.parse::<Map>()?;
+        // The map ends at the first blank line; everything after that is moves. (Splitting on lines, rather than
+        // on "\n\n", copes with CRLF line endings and with extra blank lines.)
+        let lines = s.lines().collect::<Vec<_>>();
+        let blank = lines
+            .iter()
+            .position(|line| line.trim().is_empty())
+            .ok_or_else(|| anyhow!("Bad input"))?;
+        let map = lines[..blank].join("\n").parse::<Map>()?;
 
-        let instructions = directions
-            .lines()
-            .flat_map(|line| line.chars().map(Direction::try_from))
+        let instructions = lines[blank..]
+            .iter()
+            .flat_map(|line| line.chars())
+            .filter(|ch| !ch.is_whitespace())
+            .map(Direction::try_from)
             .collect::<Result<Vec<_>>>()?;
 
         Ok(Input { map, instructions })
     }
 }
 
-fn part1(input: &Input) -> i64 {
+fn part1(input: &Input) -> Result<i64> {
+    input.map.check_enclosed()?;
     let mut after = input.map.clone();
     after.run_robot(&input.instructions);
-    after.gps_sum()
+    Ok(after.gps_sum())
 }
 
-fn part2(input: &Input) -> i64 {
+fn part2(input: &Input) -> Result<i64> {
+    input.map.check_enclosed()?;
     let mut after = WideMap::from(input.map.clone());
     after.run_robot(&input.instructions);
-    after.gps_sum()
+    Ok(after.gps_sum())
 }
 
 fn main() -> Result<()> {
@@ -479,8 +601,8 @@ fn main() -> Result<()> {
     let input = input.parse::<Input>()?;
 
     let start_time = std::time::Instant::now();
-    let part1 = part1(&input);
-    let part2 = part2(&input);
+    let part1 = part1(&input)?;
+    let part2 = part2(&input)?;
     let elapsed = start_time.elapsed();
 
     println!("Part1: {part1}");
@@ -535,7 +657,49 @@ mod tests {
     #[test_case(SAMPLE => 10092; "big sample")]
     #[test_case(SAMPLE_SMALL => 2028; "small sample")]
     fn part1_sample(input: &str) -> i64 {
-        part1(&input.parse::<Input>().unwrap())
+        part1(&input.parse::<Input>().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn crlf_input() {
+        let expected = SAMPLE_SMALL.parse::<Input>().unwrap();
+        let crlf = SAMPLE_SMALL.replace('\n', "\r\n");
+        let padded = SAMPLE_SMALL.replace("\n\n", "\n\n \n\n").replace("<^^>", "<^ ^>\t");
+        for text in [crlf, padded] {
+            let input = text.parse::<Input>().unwrap();
+            assert_eq!(input.map.map, expected.map.map);
+            assert_eq!(input.map.robots, expected.map.robots);
+            assert_eq!(input.instructions, expected.instructions);
+        }
+    }
+
+    #[test_case("#@#\n" => "Bad input"; "no moves")]
+    #[test_case("#@#\n\n<x>\n" => "Bad direction"; "bad move")]
+    fn bad_input(text: &str) -> String {
+        text.parse::<Input>().err().unwrap().to_string()
+    }
+
+    #[test_case(SAMPLE_SMALL => Vec::<(i64, i64)>::new(); "enclosed")]
+    #[test_case("#.#\n#@#\n###\n" => vec![(0, 1)]; "gap in the top")]
+    #[test_case("###\n@..\n###\n" => vec![(1, 0), (1, 2)]; "open sides")]
+    #[test_case("####\n#@O#\n##O#\n" => vec![(2, 2)]; "box on the border")]
+    fn leaks(map: &str) -> Vec<(i64, i64)> {
+        let map = map.split("\n\n").next().unwrap().parse::<Map>().unwrap();
+        assert_eq!(map.is_enclosed(), map.leaks().is_empty());
+        map.leaks()
+    }
+
+    #[test]
+    fn leaky_map_is_rejected() {
+        let input = "#.#\n#@#\n###\n\n^^\n".parse::<Input>().unwrap();
+        assert_eq!(
+            part1(&input).unwrap_err().to_string(),
+            "The map's border has gaps at [(0, 1)]"
+        );
+        assert_eq!(
+            part2(&input).unwrap_err().to_string(),
+            "The map's border has gaps at [(0, 1)]"
+        );
     }
 
     static MOVE_UP: &str = indoc::indoc! {"
@@ -548,9 +712,131 @@ mod tests {
         ^
     "};
 
+    #[test_case(GPS_ROW_WEIGHT, Object::Box => 2 * 100 + 1; "puzzle scoring")]
+    #[test_case(1000, Object::Box => 2 * 1000 + 1; "heavier rows")]
+    #[test_case(GPS_ROW_WEIGHT, Object::Robot => 3 * 100 + 1; "count the robot")]
+    #[test_case(GPS_ROW_WEIGHT, Object::Wall => 2412; "count the walls")]
+    fn gps_sum_with(weight: i64, marker: Object) -> i64 {
+        let input = MOVE_UP.parse::<Input>().unwrap();
+        input.map.gps_sum_with(weight, marker)
+    }
+
+    #[test_case(GPS_ROW_WEIGHT, WideObject::BoxLeft => 2 * 100 + 2; "puzzle scoring")]
+    #[test_case(GPS_ROW_WEIGHT, WideObject::BoxRight => 2 * 100 + 3; "right edges")]
+    #[test_case(10, WideObject::BoxLeft => 2 * 10 + 2; "lighter rows")]
+    fn wide_gps_sum_with(weight: i64, marker: WideObject) -> i64 {
+        let input = MOVE_UP.parse::<Input>().unwrap();
+        WideMap::from(input.map).gps_sum_with(weight, marker)
+    }
+
+    #[test]
+    fn robots_in_reading_order() {
+        let map = "#####\n#.@.#\n#@.@#\n#####\n".parse::<Map>().unwrap();
+        assert_eq!(map.robots, vec![(1, 2), (2, 1), (2, 3)]);
+    }
+
+    #[test]
+    fn robots_push_toward_each_other() {
+        let mut map = "#########\n#@.O.O.@#\n#########\n".parse::<Map>().unwrap();
+        let right = ">>>>"
+            .chars()
+            .map(|ch| Direction::try_from(ch).unwrap())
+            .collect::<Vec<_>>();
+        let left = "<<<<"
+            .chars()
+            .map(|ch| Direction::try_from(ch).unwrap())
+            .collect::<Vec<_>>();
+        map.run_robots(&[(0, right), (1, left)]).unwrap();
+        // Robot 0 moves first each round, so it claims the middle; robot 1 then finds its boxes blocked.
+        assert_eq!(map.robots, vec![(1, 3), (1, 6)]);
+        assert_eq!(map.map.get(&(1, 4)), Some(&Object::Box));
+        assert_eq!(map.map.get(&(1, 5)), Some(&Object::Box));
+        assert_eq!(map.map.get(&(1, 3)), Some(&Object::Robot));
+        assert_eq!(map.map.get(&(1, 6)), Some(&Object::Robot));
+        assert_eq!(map.map.len(), 9 + 9 + 2 + 4);
+    }
+
+    #[test]
+    fn robots_contend_for_a_cell() {
+        let moves = |s: &str| s.chars().map(|ch| Direction::try_from(ch).unwrap()).collect::<Vec<_>>();
+
+        let mut map = "#####\n#@.@#\n#####\n".parse::<Map>().unwrap();
+        map.run_robots(&[(0, moves(">")), (1, moves("<"))]).unwrap();
+        assert_eq!(map.robots, vec![(1, 2), (1, 3)]);
+
+        let mut map = "#####\n#@.@#\n#####\n".parse::<Map>().unwrap();
+        map.run_robots(&[(1, moves("<")), (0, moves(">"))]).unwrap();
+        assert_eq!(map.robots, vec![(1, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn run_robots_unknown_id() {
+        let mut map = "###\n#@#\n###\n".parse::<Map>().unwrap();
+        assert_eq!(
+            map.run_robots(&[(1, vec![Direction::Up])]).unwrap_err().to_string(),
+            "No robot with id 1 (there are 1 robots)"
+        );
+    }
+
+    #[test]
+    fn wide_boxes_stay_whole() {
+        let input = SAMPLE.parse::<Input>().unwrap();
+        let mut map = WideMap::from(input.map);
+        map.assert_consistent();
+        let mut robot = map.find_robot().unwrap();
+        for &d in &input.instructions {
+            robot = map.move_robot(robot, d);
+            map.assert_consistent();
+        }
+        assert_eq!(map.gps_sum(), 9021);
+    }
+
+    #[test]
+    #[should_panic(expected = "box half at (2, 2) is missing its partner")]
+    fn split_box_detected() {
+        let mut map = WideMap::from(MOVE_UP.parse::<Input>().unwrap().map);
+        assert_eq!(map.map.remove(&(2, 3)), Some(WideObject::BoxRight));
+        map.assert_consistent();
+    }
+
+    #[test]
+    fn render_moved() {
+        let input = indoc::indoc! {"
+            ########
+            #......#
+            #.OO.O.#
+            #..O...#
+            #..@...#
+            ########
+
+            ^
+        "};
+        let input = input.parse::<Input>().unwrap();
+        let mut map = WideMap::from(input.map);
+        map.run_robot(&input.instructions);
+        let expected = indoc::indoc! {"
+            ################
+            ##....{}......##
+            ##..[]{}..[]..##
+            ##....@.......##
+            ##............##
+            ################
+        "};
+        assert_eq!(map.render_moved(), expected);
+        assert_eq!(map.to_string(), expected.replace('{', "[").replace('}', "]"));
+
+        // Each run starts afresh.
+        map.run_robot(&[Direction::Right, Direction::Right]);
+        assert_eq!(map.render_moved(), map.to_string());
+
+        let mut map = WideMap::from("#####\n#@O.#\n#####\n".parse::<Map>().unwrap());
+        map.run_robot(&[Direction::Right, Direction::Right]);
+        assert_eq!(map.render_moved(), "##########\n##..@{}.##\n##########\n");
+    }
+
     #[test_case(SAMPLE => 9021; "big sample")]
     #[test_case(MOVE_UP => 102; "just push a box up")]
     fn part2_sample(input: &str) -> i64 {
-        part2(&input.parse::<Input>().unwrap())
+        part2(&input.parse::<Input>().unwrap()).unwrap()
     }
 }
@@ -5,8 +5,9 @@
 use ahash::{AHashMap, AHashSet};
 use anyhow::{anyhow, bail, Error, Result};
 use astar::{search_astar, AStarNode};
+use grid::Point;
 use std::cmp::{Ordering, Reverse};
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, VecDeque};
 use std::io::{self, Read};
 use std::str::FromStr;
 
@@ -94,6 +95,10 @@ struct Node {
 }
 
 impl Node {
+    fn location(&self) -> Point {
+        Point::new(self.row, self.col)
+    }
+
     fn needed_facing(&self, new_spot: (i64, i64)) -> Facing {
         let (row, col) = new_spot;
         match (row - self.row, col - self.col) {
@@ -111,7 +116,25 @@ impl AStarNode for Node {
     type AssociatedState = Input;
 
     fn heuristic(&self, goal: &Self, _state: &Self::AssociatedState) -> Self::Cost {
-        (goal.row - self.row).abs() + (goal.col - self.col).abs()
+        // Beyond the step count, every direction we still need to travel in costs a turn to face, unless we're
+        // already facing it. (Needing both a vertical and a horizontal leg means at least one turn, wherever we
+        // start.) The goal's own facing doesn't matter, so it isn't considered.
+        let vertical = match goal.row.cmp(&self.row) {
+            Ordering::Less => Some(Facing::North),
+            Ordering::Equal => None,
+            Ordering::Greater => Some(Facing::South),
+        };
+        let horizontal = match goal.col.cmp(&self.col) {
+            Ordering::Less => Some(Facing::West),
+            Ordering::Equal => None,
+            Ordering::Greater => Some(Facing::East),
+        };
+        let turns = match (vertical, horizontal) {
+            (None, None) => 0,
+            (Some(needed), None) | (None, Some(needed)) => self.facing.turn_cost(needed),
+            (Some(first), Some(second)) => self.facing.turn_cost(first).min(self.facing.turn_cost(second)) + 1000,
+        };
+        self.location().manhattan(&goal.location()) + turns
     }
 
     fn neighbors(&self, state: &Self::AssociatedState) -> impl Iterator<Item = (Self, Self::Cost)> {
@@ -153,7 +176,7 @@ fn path_cost(path: &[Node]) -> i64 {
         .sum()
 }
 
-fn part1(input: &Input) -> i64 {
+fn part1(input: &Input) -> Result<i64> {
     let start = Node {
         row: input.start.0,
         col: input.start.1,
@@ -164,8 +187,8 @@ fn part1(input: &Input) -> i64 {
         col: input.end.1,
         facing: Facing::East,
     };
-    let path = search_astar(start, goal, input).unwrap();
-    path_cost(&path)
+    let path = search_astar(start, goal, input).ok_or_else(|| anyhow!("No paths to target"))?;
+    Ok(path_cost(&path))
 }
 
 struct DijkstraResult {
@@ -216,48 +239,28 @@ impl DijkstraResult {
         DijkstraResult { distances, parents }
     }
 
-    fn reconstruct_paths(&self, source: Node, target: Node) -> Vec<Vec<Node>> {
-        let mut paths = Vec::new();
-        let mut current_path = Vec::new();
-        self.dfs_reconstruct(source, target, &mut current_path, &mut paths);
-        paths
-    }
-
-    fn dfs_reconstruct(&self, source: Node, current: Node, current_path: &mut Vec<Node>, paths: &mut Vec<Vec<Node>>) {
-        current_path.push(current);
-
-        if current == source {
-            let mut path = current_path.clone();
-            path.reverse();
-            paths.push(path);
-        } else if let Some(parents) = self.parents.get(&current) {
-            for &parent in parents {
-                self.dfs_reconstruct(source, parent, current_path, paths);
-            }
-        }
-
-        current_path.pop();
+    /// The cheapest cost of reaching the tile at `row`, `col` while facing `facing`, or `None` if that can't be
+    /// done.
+    fn cost_to(&self, row: i64, col: i64, facing: Facing) -> Option<i64> {
+        self.distances.get(&Node { row, col, facing }).copied()
     }
 }
 
-fn part2(world: &Input) -> Result<usize> {
+/// Every tile-and-facing that lies on some lowest-cost path from the start to the end.
+///
+/// Dijkstra's parent links form a DAG of the cheapest ways into each node; walking that backwards from the cheapest
+/// ways of arriving at the end visits exactly the nodes on optimal paths, without listing the paths themselves.
+fn optimal_path_nodes(world: &Input) -> Result<AHashSet<Node>> {
     let dj_res = DijkstraResult::dijkstra(world);
 
     // We'll have up to four "goals" in that result (one for each facing), so pick the ones with the smallest distance.
     let best_distance = [Facing::West, Facing::East, Facing::North, Facing::South]
         .iter()
-        .filter_map(|f| {
-            let goal = Node {
-                row: world.end.0,
-                col: world.end.1,
-                facing: *f,
-            };
-            dj_res.distances.get(&goal).copied()
-        })
+        .filter_map(|f| dj_res.cost_to(world.end.0, world.end.1, *f))
         .min()
         .ok_or_else(|| anyhow!("No paths to target"))?;
 
-    let targets = [Facing::West, Facing::East, Facing::North, Facing::South]
+    let mut queue = [Facing::West, Facing::East, Facing::North, Facing::South]
         .iter()
         .filter_map(|f| {
             let goal = Node {
@@ -265,32 +268,26 @@ fn part2(world: &Input) -> Result<usize> {
                 col: world.end.1,
                 facing: *f,
             };
-            if let Some(distance) = dj_res.distances.get(&goal).copied() {
-                if distance == best_distance {
-                    return Some(goal);
-                }
-            }
-            None
+            (dj_res.cost_to(goal.row, goal.col, goal.facing) == Some(best_distance)).then_some(goal)
         })
-        .collect::<Vec<_>>();
-
-    let source = Node {
-        row: world.start.0,
-        col: world.start.1,
-        facing: Facing::East,
-    };
-    let paths = targets
-        .iter()
-        .flat_map(|tgt| dj_res.reconstruct_paths(source, *tgt))
-        .collect::<Vec<_>>();
+        .collect::<VecDeque<_>>();
 
-    let mut good_seats = AHashSet::new();
-    for path in paths {
-        for seat in path {
-            good_seats.insert((seat.row, seat.col));
+    let mut nodes = queue.iter().copied().collect::<AHashSet<_>>();
+    while let Some(node) = queue.pop_front() {
+        for &parent in dj_res.parents.get(&node).into_iter().flatten() {
+            if nodes.insert(parent) {
+                queue.push_back(parent);
+            }
         }
     }
+    Ok(nodes)
+}
 
+fn part2(world: &Input) -> Result<usize> {
+    let good_seats = optimal_path_nodes(world)?
+        .into_iter()
+        .map(|node| (node.row, node.col))
+        .collect::<AHashSet<_>>();
     Ok(good_seats.len())
 }
 
@@ -302,7 +299,7 @@ fn main() -> Result<()> {
     let input = input.parse::<Input>()?;
 
     let start_time = std::time::Instant::now();
-    let part1 = part1(&input);
+    let part1 = part1(&input)?;
     let part2 = part2(&input)?;
     let elapsed = start_time.elapsed();
 
@@ -316,6 +313,7 @@ fn main() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use astar::{search_astar_all, search_astar_instrumented};
     use test_case::test_case;
 
     static SAMPLE: &str = indoc::indoc! {"
@@ -356,10 +354,166 @@ mod tests {
         #################
     "};
 
+    #[test_case(13, 1, Facing::East => Some(0); "start")]
+    #[test_case(13, 3, Facing::East => Some(2); "straight ahead")]
+    #[test_case(11, 1, Facing::North => Some(1002); "one turn")]
+    #[test_case(7, 5, Facing::North => Some(3010); "interior tile")]
+    #[test_case(12, 3, Facing::North => None; "wall")]
+    fn cost_to(row: i64, col: i64, facing: Facing) -> Option<i64> {
+        DijkstraResult::dijkstra(&SAMPLE.parse::<Input>().unwrap()).cost_to(row, col, facing)
+    }
+
     #[test_case(SAMPLE => 7036; "first sample")]
     #[test_case(SAMPLE2 => 11048; "second sample")]
     fn part1_sample(inp: &str) -> i64 {
-        part1(&inp.parse::<Input>().unwrap())
+        part1(&inp.parse::<Input>().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn walled_off_end() {
+        let input = indoc::indoc! {"
+            #######
+            #S.#..#
+            #..#.E#
+            #######
+        "}
+        .parse::<Input>()
+        .unwrap();
+        assert_eq!(part1(&input).unwrap_err().to_string(), "No paths to target");
+        assert_eq!(part2(&input).unwrap_err().to_string(), "No paths to target");
+    }
+
+    /// A [Node] that searches with the plain Manhattan-distance heuristic, for comparison.
+    #[derive(Debug, Copy, Clone, PartialEq, Hash, Eq)]
+    struct ManhattanNode(Node);
+    impl AStarNode for ManhattanNode {
+        type Cost = i64;
+        type AssociatedState = Input;
+
+        fn heuristic(&self, goal: &Self, _state: &Self::AssociatedState) -> Self::Cost {
+            self.0.location().manhattan(&goal.0.location())
+        }
+
+        fn neighbors(&self, state: &Self::AssociatedState) -> impl Iterator<Item = (Self, Self::Cost)> {
+            self.0.neighbors(state).map(|(node, cost)| (ManhattanNode(node), cost))
+        }
+
+        fn goal_match(&self, goal: &Self, state: &Self::AssociatedState) -> bool {
+            self.0.goal_match(&goal.0, state)
+        }
+    }
+
+    #[test_case(SAMPLE => 7036; "first sample")]
+    #[test_case(SAMPLE2 => 11048; "second sample")]
+    fn turn_aware_heuristic(inp: &str) -> i64 {
+        let input = inp.parse::<Input>().unwrap();
+        let start = Node {
+            row: input.start.0,
+            col: input.start.1,
+            facing: Facing::East,
+        };
+        let goal = Node {
+            row: input.end.0,
+            col: input.end.1,
+            facing: Facing::East,
+        };
+        let (path, stats) = search_astar_instrumented(start, goal, &input);
+        let (plain_path, plain_stats) = search_astar_instrumented(ManhattanNode(start), ManhattanNode(goal), &input);
+        let cost = path_cost(&path.unwrap());
+        let plain_path = plain_path.unwrap().into_iter().map(|node| node.0).collect::<Vec<_>>();
+        assert_eq!(cost, path_cost(&plain_path));
+        assert!(
+            stats.expanded < plain_stats.expanded,
+            "expanded {} nodes; plain heuristic expanded {}",
+            stats.expanded,
+            plain_stats.expanded
+        );
+        cost
+    }
+
+    fn reconstruct_paths(dj_res: &DijkstraResult, source: Node, target: Node) -> Vec<Vec<Node>> {
+        let mut paths = Vec::new();
+        let mut current_path = Vec::new();
+        dfs_reconstruct(dj_res, source, target, &mut current_path, &mut paths);
+        paths
+    }
+
+    fn dfs_reconstruct(
+        dj_res: &DijkstraResult,
+        source: Node,
+        current: Node,
+        current_path: &mut Vec<Node>,
+        paths: &mut Vec<Vec<Node>>,
+    ) {
+        current_path.push(current);
+
+        if current == source {
+            let mut path = current_path.clone();
+            path.reverse();
+            paths.push(path);
+        } else if let Some(parents) = dj_res.parents.get(&current) {
+            for &parent in parents {
+                dfs_reconstruct(dj_res, source, parent, current_path, paths);
+            }
+        }
+
+        current_path.pop();
+    }
+
+    #[test_case(SAMPLE; "first sample")]
+    #[test_case(SAMPLE2; "second sample")]
+    fn shared_prefix_paths(inp: &str) {
+        let input = inp.parse::<Input>().unwrap();
+        let start = Node {
+            row: input.start.0,
+            col: input.start.1,
+            facing: Facing::East,
+        };
+        let goal = Node {
+            row: input.end.0,
+            col: input.end.1,
+            facing: Facing::East,
+        };
+        let found = search_astar_all(start, goal, &input).unwrap();
+        let mut materialized = found.paths().collect::<Vec<_>>();
+
+        let dj_res = DijkstraResult::dijkstra(&input);
+        let mut eager = [Facing::West, Facing::East, Facing::North, Facing::South]
+            .into_iter()
+            .map(|facing| Node { facing, ..goal })
+            .filter(|end| dj_res.cost_to(end.row, end.col, end.facing) == Some(found.cost()))
+            .flat_map(|end| reconstruct_paths(&dj_res, start, end))
+            .collect::<Vec<_>>();
+
+        materialized.sort();
+        eager.sort();
+        assert_eq!(materialized, eager);
+    }
+
+    #[test_case(SAMPLE => 47; "first sample")]
+    #[test_case(SAMPLE2 => 65; "second sample")]
+    fn optimal_path_nodes(inp: &str) -> usize {
+        let input = inp.parse::<Input>().unwrap();
+        let nodes = super::optimal_path_nodes(&input).unwrap();
+
+        // The same nodes as are on all the paths found by enumerating them.
+        let start = Node {
+            row: input.start.0,
+            col: input.start.1,
+            facing: Facing::East,
+        };
+        let goal = Node {
+            row: input.end.0,
+            col: input.end.1,
+            facing: Facing::East,
+        };
+        let on_paths = search_astar_all(start, goal, &input)
+            .unwrap()
+            .paths()
+            .flatten()
+            .collect::<AHashSet<_>>();
+        assert_eq!(nodes, on_paths);
+        nodes.len()
     }
 
     #[test_case(SAMPLE => 45; "first sample")]
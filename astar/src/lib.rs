@@ -10,14 +10,28 @@
 //! The general idea: Create a data structure that defines each of your graph nodes, via the trait
 //! [AStarNode], whatever shared state you need into that trait's [AStarNode::AssociatedState], and let it
 //! rip.
+//!
+//! To find every lowest-cost path rather than just one, use [search_astar_all]. To learn the moves made along
+//! the path, and not just the nodes visited, implement [Transitions] too and use [search_astar_edges]. To watch a
+//! search as it runs (say, to animate the frontier), use [search_astar_observed].
+//!
+//! For searches over a grid of [Point]s, [WeightedGridNode] is a ready-made node, with each step's cost coming
+//! from a closure. And for a quick search where all that matters is which cells can be entered, [grid_astar] skips
+//! even that.
+//!
+//! For grids where movement isn't confined to the edges between cells, [search_theta_star] finds any-angle
+//! paths, given nodes that also implement [LineOfSight].
 #![warn(missing_docs)]
 
-use ahash::AHashMap;
-use num::Zero;
+use ahash::{AHashMap, AHashSet};
+use grid::Point;
+use num::{FromPrimitive, Zero};
 use priority_queue::PriorityQueue;
 use std::cmp::Reverse;
 use std::hash::Hash;
+use std::marker::PhantomData;
 use std::ops::Add;
+use std::rc::Rc;
 
 /// The data that uniquely specifies a node in the search graph
 ///
@@ -190,6 +204,124 @@ pub trait AStarNode: Clone + PartialEq + Eq + Hash {
 /// assert_eq!(vis, expected);
 /// ```
 pub fn search_astar<T>(initial: T, goal: T, state: &T::AssociatedState) -> Option<Vec<T>>
+where
+    T: AStarNode,
+{
+    search(
+        initial,
+        goal,
+        state,
+        &mut SearchStats::default(),
+        |_, _| {},
+        |_, _, _| {},
+    )
+}
+
+/// Counters describing how much work a search did.
+///
+/// These are mostly useful for judging the quality of a heuristic: a good one keeps `expanded` close to the
+/// length of the resulting path, while a poor one (or none at all, which turns A* into Dijkstra's algorithm)
+/// lets the search wander over much more of the graph.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// The number of nodes taken off the open set and examined (including the goal node, if found)
+    pub expanded: usize,
+    /// The number of neighbor nodes generated while expanding nodes
+    pub generated: usize,
+    /// The largest size the open set reached during the search
+    pub max_open: usize,
+}
+
+/// Run [search_astar], also returning statistics on how much work the search did.
+///
+/// The path returned is exactly the one [search_astar] would have found.
+///
+/// # Example
+/// ```
+/// use astar::{search_astar_instrumented, AStarNode};
+/// #[derive(Clone, PartialEq, Eq, Hash)]
+/// struct Spot(i64);
+/// impl AStarNode for Spot {
+///     type Cost = i64;
+///     type AssociatedState = ();
+///     fn heuristic(&self, goal: &Self, _: &()) -> i64 {
+///         (goal.0 - self.0).abs()
+///     }
+///     fn neighbors(&self, _: &()) -> impl Iterator<Item = (Self, i64)> {
+///         [(Spot(self.0 - 1), 1), (Spot(self.0 + 1), 1)].into_iter()
+///     }
+///     fn goal_match(&self, goal: &Self, _: &()) -> bool {
+///         self == goal
+///     }
+/// }
+///
+/// let (path, stats) = search_astar_instrumented(Spot(0), Spot(5), &());
+/// assert_eq!(path.unwrap().len(), 6);
+/// assert_eq!(stats.expanded, 6);
+/// ```
+pub fn search_astar_instrumented<T>(initial: T, goal: T, state: &T::AssociatedState) -> (Option<Vec<T>>, SearchStats)
+where
+    T: AStarNode,
+{
+    let mut stats = SearchStats::default();
+    let path = search(initial, goal, state, &mut stats, |_, _| {}, |_, _, _| {});
+    (path, stats)
+}
+
+/// Run [search_astar], reporting each step of the search to a pair of callbacks as it happens.
+///
+/// `on_expand` is called with each node as it's taken off the open set to be examined (the goal node included),
+/// along with the cost of the best path found to it. `on_relax` is called whenever a cheaper path to a node is
+/// found, with the node the path comes from, the node it reaches, and the path's new cost. Together they show
+/// the search's frontier as it grows, which is handy for visualizing or instrumenting a search.
+///
+/// The path returned is exactly the one [search_astar] would have found.
+///
+/// # Example
+/// ```
+/// use astar::{search_astar_observed, AStarNode};
+/// #[derive(Clone, PartialEq, Eq, Hash, Debug)]
+/// struct Spot(i64);
+/// impl AStarNode for Spot {
+///     type Cost = i64;
+///     type AssociatedState = ();
+///     fn heuristic(&self, goal: &Self, _: &()) -> i64 {
+///         (goal.0 - self.0).abs()
+///     }
+///     fn neighbors(&self, _: &()) -> impl Iterator<Item = (Self, i64)> {
+///         [(Spot(self.0 - 1), 1), (Spot(self.0 + 1), 1)].into_iter()
+///     }
+///     fn goal_match(&self, goal: &Self, _: &()) -> bool {
+///         self == goal
+///     }
+/// }
+///
+/// let mut expanded = vec![];
+/// let path = search_astar_observed(Spot(0), Spot(3), &(), |spot, cost| expanded.push((spot.0, cost)), |_, _, _| {});
+/// assert_eq!(path.unwrap().len(), 4);
+/// assert_eq!(expanded, [(0, 0), (1, 1), (2, 2), (3, 3)]);
+/// ```
+pub fn search_astar_observed<T>(
+    initial: T,
+    goal: T,
+    state: &T::AssociatedState,
+    on_expand: impl FnMut(&T, T::Cost),
+    on_relax: impl FnMut(&T, &T, T::Cost),
+) -> Option<Vec<T>>
+where
+    T: AStarNode,
+{
+    search(initial, goal, state, &mut SearchStats::default(), on_expand, on_relax)
+}
+
+fn search<T>(
+    initial: T,
+    goal: T,
+    state: &T::AssociatedState,
+    stats: &mut SearchStats,
+    mut on_expand: impl FnMut(&T, T::Cost),
+    mut on_relax: impl FnMut(&T, &T, T::Cost),
+) -> Option<Vec<T>>
 where
     T: AStarNode,
 {
@@ -204,9 +336,12 @@ where
     f_score.insert(initial.clone(), fitness);
 
     open.push(initial, Reverse(fitness));
+    stats.max_open = stats.max_open.max(open.len());
 
     while !open.is_empty() {
         let (current, _) = open.pop().unwrap();
+        stats.expanded += 1;
+        on_expand(&current, g_score[&current]);
         if current.goal_match(&goal, state) {
             let mut result = vec![goal];
             let mut current = current;
@@ -217,15 +352,1086 @@ where
             return Some(result.into_iter().rev().collect());
         }
         for (neighbor, neighbor_cost) in current.neighbors(state) {
+            stats.generated += 1;
             let tentative = g_score[&current] + neighbor_cost;
-            if g_score.get(&neighbor).map_or(true, |&previous| tentative < previous) {
+            if g_score.get(&neighbor).is_none_or(|&previous| tentative < previous) {
+                on_relax(&current, &neighbor, tentative);
                 came_from.insert(neighbor.clone(), current.clone());
                 g_score.insert(neighbor.clone(), tentative);
                 let new_fscore = tentative + neighbor.heuristic(&goal, state);
                 f_score.insert(neighbor.clone(), new_fscore);
                 open.push(neighbor, Reverse(new_fscore));
+                stats.max_open = stats.max_open.max(open.len());
             }
         }
     }
     None
 }
+
+/// A node whose moves to its neighbors carry some extra information
+///
+/// A path from [search_astar] lists the nodes visited, but not how each was reached. When the move itself
+/// matters (which direction was taken, which action was performed), implement this alongside [AStarNode] and
+/// search with [search_astar_edges] to get the moves back with the path. If a move carries nothing worth
+/// keeping, [AStarNode] and [search_astar] are all that's needed.
+pub trait Transitions: AStarNode {
+    /// The information attached to a move from one node to the next.
+    type Edge: Clone;
+    /// Generates an iterator over all the neighbors of `self`, along with the costs to get to each of them and
+    /// the moves that get there. This should describe the same neighbors and costs as [AStarNode::neighbors].
+    fn neighbors_with_edge(
+        &self,
+        state: &Self::AssociatedState,
+    ) -> impl Iterator<Item = (Self, Self::Cost, Self::Edge)>;
+}
+
+/// Run an A* search like [search_astar], also recovering the move made at each step of the path
+///
+/// Each node in the returned path is paired with the [Transitions::Edge] that led to it from the node before;
+/// the initial node, which nothing led to, is paired with `None`. The path ends at the node that matched the
+/// goal. If no path between start and the goal exists, `None` is returned.
+///
+/// # Example
+/// ```
+/// use astar::{search_astar_edges, AStarNode, Transitions};
+/// #[derive(Clone, PartialEq, Eq, Hash, Debug)]
+/// struct Spot(i64);
+/// impl AStarNode for Spot {
+///     type Cost = i64;
+///     type AssociatedState = ();
+///     fn heuristic(&self, goal: &Self, _: &()) -> i64 {
+///         (goal.0 - self.0).abs()
+///     }
+///     fn neighbors(&self, state: &()) -> impl Iterator<Item = (Self, i64)> {
+///         self.neighbors_with_edge(state).map(|(spot, cost, _)| (spot, cost))
+///     }
+///     fn goal_match(&self, goal: &Self, _: &()) -> bool {
+///         self == goal
+///     }
+/// }
+/// impl Transitions for Spot {
+///     type Edge = &'static str;
+///     fn neighbors_with_edge(&self, _: &()) -> impl Iterator<Item = (Self, i64, &'static str)> {
+///         [(Spot(self.0 - 1), 1, "left"), (Spot(self.0 + 1), 1, "right")].into_iter()
+///     }
+/// }
+///
+/// let path = search_astar_edges(Spot(0), Spot(-2), &()).unwrap();
+/// assert_eq!(path, [(Spot(0), None), (Spot(-1), Some("left")), (Spot(-2), Some("left"))]);
+/// ```
+pub fn search_astar_edges<T>(initial: T, goal: T, state: &T::AssociatedState) -> Option<Vec<(T, Option<T::Edge>)>>
+where
+    T: Transitions,
+{
+    let mut open: PriorityQueue<T, Reverse<T::Cost>> = PriorityQueue::new();
+    let mut g_score = AHashMap::new();
+    let mut came_from: AHashMap<T, (T, T::Edge)> = AHashMap::new();
+
+    g_score.insert(initial.clone(), T::Cost::zero());
+    let fitness = initial.heuristic(&goal, state);
+    open.push(initial, Reverse(fitness));
+
+    while let Some((current, _)) = open.pop() {
+        if current.goal_match(&goal, state) {
+            let mut result = vec![];
+            let mut current = current;
+            while let Some((previous, edge)) = came_from.get(&current) {
+                result.push((current, Some(edge.clone())));
+                current = previous.clone();
+            }
+            result.push((current, None));
+            result.reverse();
+            return Some(result);
+        }
+        for (neighbor, neighbor_cost, edge) in current.neighbors_with_edge(state) {
+            let tentative = g_score[&current] + neighbor_cost;
+            if g_score.get(&neighbor).is_none_or(|&previous| tentative < previous) {
+                came_from.insert(neighbor.clone(), (current.clone(), edge));
+                g_score.insert(neighbor.clone(), tentative);
+                let new_fscore = tentative + neighbor.heuristic(&goal, state);
+                open.push(neighbor, Reverse(new_fscore));
+            }
+        }
+    }
+    None
+}
+
+/// One step of a path, linked back to the step before it
+///
+/// Paths built from these share their common prefixes: every path that starts the same way points at the
+/// same chain of [Rc]-counted nodes. [search_astar_all] hands back the last step of each path it finds; the
+/// full path is only built (with [PathNode::to_path]) when it's asked for.
+#[derive(Debug)]
+pub struct PathNode<T> {
+    /// The graph node at this step
+    pub node: T,
+    /// The previous step, or `None` if this is the start of the path
+    pub parent: Option<Rc<PathNode<T>>>,
+}
+
+impl<T> PathNode<T>
+where
+    T: Clone,
+{
+    /// The full path ending at this step, from the start.
+    pub fn to_path(&self) -> Vec<T> {
+        let mut result = vec![self.node.clone()];
+        let mut current = &self.parent;
+        while let Some(step) = current {
+            result.push(step.node.clone());
+            current = &step.parent;
+        }
+        result.reverse();
+        result
+    }
+}
+
+/// Every lowest-cost path found by [search_astar_all]
+#[derive(Debug)]
+pub struct OptimalPaths<T>
+where
+    T: AStarNode,
+{
+    cost: T::Cost,
+    ends: Vec<Rc<PathNode<T>>>,
+}
+
+impl<T> OptimalPaths<T>
+where
+    T: AStarNode,
+{
+    /// The cost shared by all the paths.
+    pub fn cost(&self) -> T::Cost {
+        self.cost
+    }
+
+    /// The last step of each path. (Walk the [PathNode::parent] links to see the rest.)
+    pub fn ends(&self) -> &[Rc<PathNode<T>>] {
+        &self.ends
+    }
+
+    /// The number of paths.
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Whether there are no paths. (Never true for a result of [search_astar_all].)
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// Iterate over the paths, building each full [Vec] only as it's reached.
+    pub fn paths(&self) -> impl Iterator<Item = Vec<T>> + '_ {
+        self.ends.iter().map(|end| end.to_path())
+    }
+}
+
+/// Find _every_ lowest-cost path from a start node to a destination class of nodes
+///
+/// Where [search_astar] stops at the first path it finds, this keeps going until it has seen every path with
+/// that same cost. Since the number of such paths can be enormous, they aren't returned as a list of [Vec]s;
+/// instead, the result is a set of [PathNode] chains that share storage for the prefixes the paths have in
+/// common. The paths are in no particular order.
+///
+/// For the result to be complete, the heuristic must be _consistent_ (never dropping by more than the cost of
+/// a step), as it is for Manhattan distance on a grid. Paths are not extended past a goal node. If no path
+/// between start and the goal exists, `None` is returned.
+///
+/// # Example
+/// ```
+/// use astar::{search_astar_all, AStarNode};
+/// #[derive(Clone, PartialEq, Eq, Hash, Debug)]
+/// struct Spot(i64, i64);
+/// impl AStarNode for Spot {
+///     type Cost = i64;
+///     type AssociatedState = ();
+///     fn heuristic(&self, goal: &Self, _: &()) -> i64 {
+///         (goal.0 - self.0).abs() + (goal.1 - self.1).abs()
+///     }
+///     fn neighbors(&self, _: &()) -> impl Iterator<Item = (Self, i64)> {
+///         [(-1, 0), (1, 0), (0, -1), (0, 1)].into_iter().map(|(dr, dc)| (Spot(self.0 + dr, self.1 + dc), 1))
+///     }
+///     fn goal_match(&self, goal: &Self, _: &()) -> bool {
+///         self == goal
+///     }
+/// }
+///
+/// let found = search_astar_all(Spot(0, 0), Spot(1, 1), &()).unwrap();
+/// assert_eq!(found.cost(), 2);
+/// let mut paths = found.paths().collect::<Vec<_>>();
+/// paths.sort_by_key(|path| path[1].0);
+/// assert_eq!(paths, vec![
+///     vec![Spot(0, 0), Spot(0, 1), Spot(1, 1)],
+///     vec![Spot(0, 0), Spot(1, 0), Spot(1, 1)],
+/// ]);
+/// ```
+pub fn search_astar_all<T>(initial: T, goal: T, state: &T::AssociatedState) -> Option<OptimalPaths<T>>
+where
+    T: AStarNode,
+{
+    let mut open: PriorityQueue<T, Reverse<T::Cost>> = PriorityQueue::new();
+    let mut g_score = AHashMap::new();
+    // Every predecessor that reaches a node at its best known cost, not just the first one found.
+    let mut came_from: AHashMap<T, Vec<T>> = AHashMap::new();
+    let mut best = None;
+    let mut goals = vec![];
+
+    g_score.insert(initial.clone(), T::Cost::zero());
+    let fitness = initial.heuristic(&goal, state);
+    open.push(initial.clone(), Reverse(fitness));
+
+    while let Some((current, Reverse(fitness))) = open.pop() {
+        if best.is_some_and(|best| fitness > best) {
+            // Everything left costs more than the paths we have.
+            break;
+        }
+        if current.goal_match(&goal, state) {
+            best = Some(g_score[&current]);
+            goals.push(current);
+            continue;
+        }
+        for (neighbor, neighbor_cost) in current.neighbors(state) {
+            let tentative = g_score[&current] + neighbor_cost;
+            match g_score.get(&neighbor) {
+                Some(&previous) if tentative > previous => {}
+                Some(&previous) if tentative == previous => {
+                    let parents = came_from.entry(neighbor).or_default();
+                    if !parents.contains(&current) {
+                        parents.push(current.clone());
+                    }
+                }
+                _ => {
+                    came_from.insert(neighbor.clone(), vec![current.clone()]);
+                    g_score.insert(neighbor.clone(), tentative);
+                    let new_fscore = tentative + neighbor.heuristic(&goal, state);
+                    open.push(neighbor, Reverse(new_fscore));
+                }
+            }
+        }
+    }
+    let cost = best?;
+
+    // Turn the predecessor links around, keeping only the nodes that actually lead to a goal.
+    let mut children: AHashMap<T, Vec<T>> = AHashMap::new();
+    let mut seen = goals.iter().cloned().collect::<AHashSet<_>>();
+    let mut pending = goals.clone();
+    while let Some(node) = pending.pop() {
+        for parent in came_from.get(&node).into_iter().flatten() {
+            children.entry(parent.clone()).or_default().push(node.clone());
+            if seen.insert(parent.clone()) {
+                pending.push(parent.clone());
+            }
+        }
+    }
+
+    // And walk forward from the start, building the shared path steps.
+    let goals = goals.into_iter().collect::<AHashSet<_>>();
+    let mut ends = vec![];
+    let mut pending = vec![Rc::new(PathNode {
+        node: initial,
+        parent: None,
+    })];
+    while let Some(step) = pending.pop() {
+        for child in children.get(&step.node).into_iter().flatten() {
+            pending.push(Rc::new(PathNode {
+                node: child.clone(),
+                parent: Some(step.clone()),
+            }));
+        }
+        if goals.contains(&step.node) {
+            ends.push(step);
+        }
+    }
+    Some(OptimalPaths { cost, ends })
+}
+
+/// A node that can tell whether it has an unobstructed straight line to another node
+///
+/// This is what [search_theta_star] needs beyond a plain [AStarNode]: a way to decide whether a path can
+/// cut straight from one node to another (skipping the nodes in between), and what that straight cut costs.
+pub trait LineOfSight: AStarNode {
+    /// Whether a straight line from `self` to `other` is unobstructed.
+    fn line_of_sight(&self, other: &Self, state: &Self::AssociatedState) -> bool;
+    /// The cost of travelling in a straight line from `self` to `other`. (Only called when
+    /// [LineOfSight::line_of_sight] says the way is clear.) This should be in the same units as the costs
+    /// produced by [AStarNode::neighbors]; typically it's the Euclidean distance, suitably scaled.
+    fn straight_cost(&self, other: &Self, state: &Self::AssociatedState) -> Self::Cost;
+}
+
+/// Search for an any-angle path from a start node to a destination class of nodes, via Theta*
+///
+/// [Theta*](https://en.wikipedia.org/wiki/Theta*) is A* with one change: when a node is reached, it may take
+/// its predecessor's parent as its own parent, if that parent can see it directly. Paths therefore aren't
+/// confined to the edges of the graph; on a grid, they cut diagonally across open space rather than
+/// zig-zagging from cell to cell.
+///
+/// The returned path lists only the waypoints where it changes direction (plus the start and goal). If no path
+/// between start and the goal exists, `None` is returned.
+///
+/// # Example
+/// ```
+/// use astar::{search_theta_star, AStarNode, LineOfSight};
+/// #[derive(Clone, PartialEq, Eq, Hash)]
+/// struct Spot(i64, i64);
+/// impl AStarNode for Spot {
+///     type Cost = i64;
+///     type AssociatedState = ();
+///     fn heuristic(&self, goal: &Self, _: &()) -> i64 {
+///         (goal.0 - self.0).abs().max((goal.1 - self.1).abs())
+///     }
+///     fn neighbors(&self, _: &()) -> impl Iterator<Item = (Self, i64)> {
+///         [(-1, 0), (1, 0), (0, -1), (0, 1), (1, 1)].into_iter().map(|(dr, dc)| (Spot(self.0 + dr, self.1 + dc), 1))
+///     }
+///     fn goal_match(&self, goal: &Self, _: &()) -> bool {
+///         self == goal
+///     }
+/// }
+/// impl LineOfSight for Spot {
+///     // Nothing in the way, anywhere.
+///     fn line_of_sight(&self, _: &Self, _: &()) -> bool {
+///         true
+///     }
+///     fn straight_cost(&self, other: &Self, state: &()) -> i64 {
+///         self.heuristic(other, state)
+///     }
+/// }
+///
+/// let path = search_theta_star(Spot(0, 0), Spot(3, 5), &()).unwrap();
+/// assert!(path == vec![Spot(0, 0), Spot(3, 5)]);
+/// ```
+pub fn search_theta_star<T>(initial: T, goal: T, state: &T::AssociatedState) -> Option<Vec<T>>
+where
+    T: LineOfSight,
+{
+    let mut open: PriorityQueue<T, Reverse<T::Cost>> = PriorityQueue::new();
+    let mut closed: AHashSet<T> = AHashSet::new();
+    let mut g_score = AHashMap::new();
+    // Unlike in A*, every node (even the initial one) has a parent; the initial node is its own.
+    let mut parent: AHashMap<T, T> = AHashMap::new();
+
+    g_score.insert(initial.clone(), T::Cost::zero());
+    parent.insert(initial.clone(), initial.clone());
+    let fitness = initial.heuristic(&goal, state);
+    open.push(initial, Reverse(fitness));
+
+    while let Some((current, _)) = open.pop() {
+        if current.goal_match(&goal, state) {
+            let mut result = vec![goal];
+            let mut current = current;
+            while let Some(previous) = parent.get(&current).filter(|&previous| *previous != current) {
+                result.push(previous.clone());
+                current = previous.clone();
+            }
+            return Some(result.into_iter().rev().collect());
+        }
+        closed.insert(current.clone());
+        let current_parent = parent[&current].clone();
+        for (neighbor, neighbor_cost) in current.neighbors(state) {
+            if closed.contains(&neighbor) {
+                continue;
+            }
+            // Prefer the straight line from our parent, when there is one.
+            let (tentative, new_parent) = if current_parent.line_of_sight(&neighbor, state) {
+                (
+                    g_score[&current_parent] + current_parent.straight_cost(&neighbor, state),
+                    &current_parent,
+                )
+            } else {
+                (g_score[&current] + neighbor_cost, &current)
+            };
+            if g_score.get(&neighbor).is_none_or(|&previous| tentative < previous) {
+                parent.insert(neighbor.clone(), new_parent.clone());
+                g_score.insert(neighbor.clone(), tentative);
+                let new_fscore = tentative + neighbor.heuristic(&goal, state);
+                open.push(neighbor, Reverse(new_fscore));
+            }
+        }
+    }
+    None
+}
+
+/// How a [WeightedGridNode] estimates the remaining cost to its goal
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum GridHeuristic {
+    /// The [Manhattan distance](Point::manhattan): the number of orthogonal steps to the goal.
+    Manhattan,
+    /// The [Chebyshev distance](Point::chebyshev): the number of steps to the goal, when diagonal steps are
+    /// allowed.
+    Chebyshev,
+    /// No estimate at all, which makes the search a plain Dijkstra search.
+    Zero,
+}
+
+/// The grid a [WeightedGridNode] search runs over: what each step costs, and how to estimate the cost to the goal
+///
+/// Both distance heuristics count steps, so they only give optimal paths if no step costs less than 1. For
+/// anything cheaper, use [GridHeuristic::Zero].
+pub struct WeightedGrid<'a, C> {
+    step_cost: Box<dyn Fn(Point, Point) -> Option<C> + 'a>,
+    heuristic: GridHeuristic,
+    diagonal: bool,
+}
+
+impl<'a, C> WeightedGrid<'a, C> {
+    /// Describe a grid where stepping from one cell to a neighbor costs `step_cost(from, to)`, or can't be done if
+    /// that's `None`. The neighbors are the four orthogonal ones, plus the four diagonal ones if `diagonal` is set.
+    ///
+    /// `step_cost` is all there is to the grid, so it should also return `None` for steps off the edge of the map.
+    pub fn new(step_cost: impl Fn(Point, Point) -> Option<C> + 'a, heuristic: GridHeuristic, diagonal: bool) -> Self {
+        WeightedGrid {
+            step_cost: Box::new(step_cost),
+            heuristic,
+            diagonal,
+        }
+    }
+}
+
+/// A cell of a [WeightedGrid], ready to search with [search_astar] (or any of the other searches)
+///
+/// # Example
+/// ```
+/// use astar::{search_astar, GridHeuristic, WeightedGrid, WeightedGridNode};
+/// use grid::{Grid, Point};
+///
+/// // Entering a cell costs the number written in it.
+/// let map = Grid::from_rows(["191", "191", "111"].map(|line| line.chars().map(|ch| ch.to_digit(10).unwrap() as i64)));
+/// let world = WeightedGrid::new(|_, to| map.get(to).copied(), GridHeuristic::Manhattan, false);
+///
+/// let path = search_astar(WeightedGridNode::new(Point::new(0, 0)), WeightedGridNode::new(Point::new(0, 2)), &world)
+///     .unwrap();
+/// // Going around the 9s is cheaper than going through them.
+/// let points = path.iter().map(|node| node.point).collect::<Vec<_>>();
+/// assert_eq!(points.len(), 7);
+/// assert!(!points.contains(&Point::new(0, 1)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WeightedGridNode<'a, C> {
+    /// Where this cell is on the grid
+    pub point: Point,
+    grid: PhantomData<&'a C>,
+}
+
+impl<C> WeightedGridNode<'_, C> {
+    /// The node for the cell at `point`.
+    pub fn new(point: Point) -> Self {
+        WeightedGridNode {
+            point,
+            grid: PhantomData,
+        }
+    }
+}
+
+impl<'a, C> AStarNode for WeightedGridNode<'a, C>
+where
+    C: Ord + Hash + Copy + Add<Output = C> + Zero + FromPrimitive,
+{
+    type Cost = C;
+    type AssociatedState = WeightedGrid<'a, C>;
+
+    fn heuristic(&self, goal: &Self, state: &Self::AssociatedState) -> Self::Cost {
+        let steps = match state.heuristic {
+            GridHeuristic::Manhattan => self.point.manhattan(&goal.point),
+            GridHeuristic::Chebyshev => self.point.chebyshev(&goal.point),
+            GridHeuristic::Zero => return C::zero(),
+        };
+        C::from_i64(steps).expect("step counts should fit in the cost type")
+    }
+
+    fn neighbors(&self, state: &Self::AssociatedState) -> impl Iterator<Item = (Self, Self::Cost)> {
+        const ORTHOGONAL: [(i64, i64); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+        const DIAGONAL: [(i64, i64); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+        let diagonals = if state.diagonal { &DIAGONAL[..] } else { &[] };
+        ORTHOGONAL
+            .iter()
+            .chain(diagonals)
+            .map(|&(drow, dcol)| Point::new(self.point.row + drow, self.point.col + dcol))
+            .filter_map(|to| (state.step_cost)(self.point, to).map(|cost| (WeightedGridNode::new(to), cost)))
+    }
+
+    fn goal_match(&self, goal: &Self, _: &Self::AssociatedState) -> bool {
+        self.point == goal.point
+    }
+}
+
+/// Find a shortest path across a grid from `start` to `goal`, where `passable` says which cells can be entered
+///
+/// Points are `(row, col)` pairs. Each step moves to one of the four orthogonal neighbors, or to any of the eight
+/// surrounding cells if `diagonal` is set; every step costs the same. `passable` is all there is to the grid, so it
+/// should also return `false` for anything off the edge of the map. (The start itself needn't be passable.)
+///
+/// The returned path includes both `start` and `goal`. If the goal can't be reached, `None` is returned.
+///
+/// # Example
+/// ```
+/// use astar::grid_astar;
+///
+/// let map = ["S..#....", "##.#.##.", "...#..#.", ".###.##G", "........"];
+/// let passable = |(row, col): (i64, i64)| {
+///     usize::try_from(row)
+///         .ok()
+///         .and_then(|row| map.get(row))
+///         .and_then(|line| usize::try_from(col).ok().and_then(|col| line.as_bytes().get(col)))
+///         .is_some_and(|&ch| ch != b'#')
+/// };
+///
+/// let path = grid_astar((0, 0), (3, 7), passable, false).unwrap();
+/// assert_eq!(path.len(), 17);
+/// assert_eq!(path[..4], [(0, 0), (0, 1), (0, 2), (1, 2)]);
+///
+/// // Moving diagonally, the corners can be cut.
+/// let path = grid_astar((0, 0), (3, 7), passable, true).unwrap();
+/// assert_eq!(path.len(), 12);
+///
+/// assert_eq!(grid_astar((0, 0), (1, 0), passable, true), None);
+/// ```
+pub fn grid_astar(
+    start: (i64, i64),
+    goal: (i64, i64),
+    passable: impl Fn((i64, i64)) -> bool,
+    diagonal: bool,
+) -> Option<Vec<(i64, i64)>> {
+    let heuristic = if diagonal {
+        GridHeuristic::Chebyshev
+    } else {
+        GridHeuristic::Manhattan
+    };
+    let world = WeightedGrid::new(
+        |_, to: Point| passable((to.row, to.col)).then_some(1_i64),
+        heuristic,
+        diagonal,
+    );
+    let node = |(row, col)| WeightedGridNode::new(Point::new(row, col));
+    let path = search_astar(node(start), node(goal), &world)?;
+    Some(path.into_iter().map(|node| (node.point.row, node.point.col)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grid::Grid;
+
+    /// The world from the [search_astar] documentation example.
+    static MAP: &[&str] = &[
+        "S....#........................",
+        ".....#...............#........",
+        "###..#...............#........",
+        ".....................#........",
+        "########################......",
+        "..............................",
+        "..............................",
+        "..############################",
+        ".............................G",
+        "..............................",
+    ];
+
+    #[derive(Clone, PartialEq, Eq, Hash)]
+    struct Node {
+        row: i64,
+        col: i64,
+    }
+
+    struct World {
+        width: i64,
+        height: i64,
+        walls: AHashSet<(i64, i64)>,
+    }
+
+    impl AStarNode for Node {
+        type Cost = i64;
+        type AssociatedState = World;
+
+        fn heuristic(&self, goal: &Self, _state: &Self::AssociatedState) -> Self::Cost {
+            (goal.row - self.row).abs() + (goal.col - self.col).abs()
+        }
+
+        fn goal_match(&self, goal: &Self, _state: &Self::AssociatedState) -> bool {
+            self == goal
+        }
+
+        fn neighbors(&self, state: &Self::AssociatedState) -> impl Iterator<Item = (Self, Self::Cost)> {
+            [(0, -1), (0, 1), (-1, 0), (1, 0)]
+                .into_iter()
+                .map(|(dy, dx)| (self.row + dy, self.col + dx))
+                .filter(|&(row, col)| {
+                    row >= 0
+                        && col >= 0
+                        && row < state.height
+                        && col < state.width
+                        && !state.walls.contains(&(row, col))
+                })
+                .map(|(row, col)| (Node { row, col }, 1))
+        }
+    }
+
+    fn world() -> (World, Node, Node) {
+        let mut walls = AHashSet::new();
+        let mut start = Node { row: 0, col: 0 };
+        let mut finish = Node { row: 0, col: 0 };
+        for (row, line) in MAP.iter().enumerate() {
+            let row = i64::try_from(row).unwrap();
+            for (col, ch) in line.chars().enumerate() {
+                let col = i64::try_from(col).unwrap();
+                match ch {
+                    'S' => start = Node { row, col },
+                    'G' => finish = Node { row, col },
+                    '#' => {
+                        walls.insert((row, col));
+                    }
+                    _ => (),
+                }
+            }
+        }
+        let height = i64::try_from(MAP.len()).unwrap();
+        let width = i64::try_from(MAP[0].len()).unwrap();
+        (World { width, height, walls }, start, finish)
+    }
+
+    impl Transitions for Node {
+        type Edge = char;
+
+        fn neighbors_with_edge(
+            &self,
+            state: &Self::AssociatedState,
+        ) -> impl Iterator<Item = (Self, Self::Cost, Self::Edge)> {
+            self.neighbors(state).map(|(node, cost)| {
+                let arrow = match (node.row - self.row, node.col - self.col) {
+                    (-1, 0) => '^',
+                    (1, 0) => 'v',
+                    (0, -1) => '<',
+                    (0, 1) => '>',
+                    _ => unreachable!(),
+                };
+                (node, cost, arrow)
+            })
+        }
+    }
+
+    #[test]
+    fn edges_retrace_path() {
+        let (world, start, finish) = world();
+        let plain = search_astar(start.clone(), finish.clone(), &world).unwrap();
+        let with_edges = search_astar_edges(start.clone(), finish.clone(), &world).unwrap();
+        assert_eq!(with_edges.len(), plain.len());
+        assert!(with_edges[0] == (start.clone(), None));
+        assert!(with_edges.last().unwrap().0 == finish);
+
+        // Following the arrows from the start lands on each node of the path in turn.
+        let mut spot = start;
+        for (node, edge) in &with_edges[1..] {
+            let (drow, dcol) = match edge.unwrap() {
+                '^' => (-1, 0),
+                'v' => (1, 0),
+                '<' => (0, -1),
+                '>' => (0, 1),
+                _ => unreachable!(),
+            };
+            spot = Node {
+                row: spot.row + drow,
+                col: spot.col + dcol,
+            };
+            assert!(spot == *node);
+        }
+    }
+
+    #[test]
+    fn grid_astar_matches_search_astar() {
+        let (world, start, finish) = world();
+        let passable = |(row, col)| {
+            (0..world.height).contains(&row) && (0..world.width).contains(&col) && !world.walls.contains(&(row, col))
+        };
+        let expected = search_astar(start.clone(), finish.clone(), &world)
+            .unwrap()
+            .into_iter()
+            .map(|node| (node.row, node.col))
+            .collect::<Vec<_>>();
+        let path = grid_astar((start.row, start.col), (finish.row, finish.col), passable, false).unwrap();
+        assert_eq!(path, expected);
+
+        let diagonal = grid_astar((start.row, start.col), (finish.row, finish.col), passable, true).unwrap();
+        assert!(diagonal.len() < path.len());
+        assert!(diagonal.iter().skip(1).all(|&pt| passable(pt)));
+        for pair in diagonal.windows(2) {
+            let ((r1, c1), (r2, c2)) = (pair[0], pair[1]);
+            assert_eq!((r1 - r2).abs().max((c1 - c2).abs()), 1);
+        }
+    }
+
+    #[test]
+    fn weighted_grid() {
+        // The sample from 2021 day 15: entering a cell costs its risk level.
+        let risks = Grid::from_rows(
+            [
+                "1163751742",
+                "1381373672",
+                "2136511328",
+                "3694931569",
+                "7463417111",
+                "1319128137",
+                "1359912421",
+                "3125421639",
+                "1293138521",
+                "2311944581",
+            ]
+            .map(|line| line.chars().map(|ch| ch.to_digit(10).unwrap())),
+        );
+        let start = WeightedGridNode::new(Point::new(0, 0));
+        let goal = WeightedGridNode::new(Point::new(9, 9));
+        let risk =
+            |path: &[WeightedGridNode<u32>]| path[1..].iter().map(|node| risks.get(node.point).unwrap()).sum::<u32>();
+
+        let mut expanded = vec![];
+        for heuristic in [GridHeuristic::Manhattan, GridHeuristic::Chebyshev, GridHeuristic::Zero] {
+            let world = WeightedGrid::new(|_, to| risks.get(to).copied(), heuristic, false);
+            let (path, stats) = search_astar_instrumented(start.clone(), goal.clone(), &world);
+            let path = path.unwrap();
+            assert!(path[0] == start && *path.last().unwrap() == goal);
+            assert_eq!(risk(&path), 40);
+            expanded.push(stats.expanded);
+        }
+        // The better the estimate, the less of the grid gets looked at.
+        assert!(expanded[0] <= expanded[1] && expanded[1] <= expanded[2]);
+
+        // With diagonal steps allowed, there's a cheaper way.
+        let world = WeightedGrid::new(|_, to| risks.get(to).copied(), GridHeuristic::Chebyshev, true);
+        let path = search_astar(start.clone(), goal.clone(), &world).unwrap();
+        assert!(risk(&path) < 40);
+
+        // A step cost can depend on where the step comes from, too: here, moving up or left is forbidden.
+        let world = WeightedGrid::new(
+            |from: Point, to: Point| {
+                (to.row >= from.row && to.col >= from.col)
+                    .then(|| risks.get(to).copied())
+                    .flatten()
+            },
+            GridHeuristic::Manhattan,
+            false,
+        );
+        let path = search_astar(start, goal, &world).unwrap();
+        assert_eq!(path.len(), 19);
+        assert!(risk(&path) >= 40);
+    }
+
+    #[test]
+    fn edges_no_path() {
+        let (mut world, start, finish) = world();
+        world.walls.insert((0, 1));
+        world.walls.insert((1, 0));
+        assert!(search_astar_edges(start, finish, &world).is_none());
+    }
+
+    #[test]
+    fn observed_callbacks() {
+        let (world, start, finish) = world();
+        let (plain, stats) = search_astar_instrumented(start.clone(), finish.clone(), &world);
+        let mut expansions = vec![];
+        let mut relaxations = 0;
+        let observed = search_astar_observed(
+            start.clone(),
+            finish.clone(),
+            &world,
+            |node, cost| expansions.push((node.clone(), cost)),
+            |from, to, cost| {
+                assert_eq!((from.row - to.row).abs() + (from.col - to.col).abs(), 1);
+                assert!(cost > 0);
+                relaxations += 1;
+            },
+        );
+        assert!(plain == observed);
+        assert_eq!(expansions.len(), stats.expanded);
+        assert!(expansions[0] == (start, 0));
+        assert!(*expansions.last().unwrap() == (finish, 89));
+        // Every relaxation puts a node on the open set, and every node examined came from there (except the start).
+        assert!(relaxations <= stats.generated);
+        assert!(relaxations >= stats.expanded - 1);
+    }
+
+    #[test]
+    fn instrumented_path_matches() {
+        let (world, start, finish) = world();
+        let plain = search_astar(start.clone(), finish.clone(), &world);
+        let (instrumented, _) = search_astar_instrumented(start, finish, &world);
+        assert!(plain.is_some());
+        assert!(plain == instrumented);
+    }
+
+    #[test]
+    fn expansion_count() {
+        // A regression guard for the search's efficiency. The shortest path here is 90 cells long, and there are 239
+        // open cells in all; today the search expands 205 of them. If a change to the search (tie-breaking,
+        // re-opening, etc.) makes it noticeably worse at using the heuristic, this will catch it.
+        const EXPANSION_LIMIT: usize = 215;
+        let (world, start, finish) = world();
+        let (path, stats) = search_astar_instrumented(start, finish, &world);
+        let path = path.unwrap();
+        assert_eq!(path.len(), 90);
+        assert!(stats.expanded >= path.len());
+        assert!(stats.expanded <= EXPANSION_LIMIT, "expanded {} nodes", stats.expanded);
+        assert!(stats.generated <= 4 * stats.expanded);
+        assert!(stats.max_open <= stats.generated + 1);
+    }
+
+    /// A stop on a small weighted graph, generic over the cost type.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct Stop<C> {
+        id: usize,
+        cost: std::marker::PhantomData<C>,
+    }
+
+    impl<C> Stop<C> {
+        fn new(id: usize) -> Self {
+            Stop {
+                id,
+                cost: std::marker::PhantomData,
+            }
+        }
+    }
+
+    impl<C> AStarNode for Stop<C>
+    where
+        C: Ord + Hash + Copy + Add<Output = C> + Zero,
+    {
+        type Cost = C;
+        // For each stop, the stops reachable from it and what it costs to get there.
+        type AssociatedState = Vec<Vec<(usize, C)>>;
+
+        fn heuristic(&self, _goal: &Self, _state: &Self::AssociatedState) -> Self::Cost {
+            C::zero()
+        }
+
+        fn goal_match(&self, goal: &Self, _state: &Self::AssociatedState) -> bool {
+            self == goal
+        }
+
+        fn neighbors(&self, state: &Self::AssociatedState) -> impl Iterator<Item = (Self, Self::Cost)> {
+            state[self.id].iter().map(|&(id, cost)| (Stop::new(id), cost))
+        }
+    }
+
+    /// A cost type that's nothing but the bare minimum the search asks for.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    struct Fuel(u16);
+
+    impl Add for Fuel {
+        type Output = Fuel;
+        fn add(self, rhs: Self) -> Self::Output {
+            Fuel(self.0 + rhs.0)
+        }
+    }
+
+    impl Zero for Fuel {
+        fn zero() -> Self {
+            Fuel(0)
+        }
+        fn is_zero(&self) -> bool {
+            self.0 == 0
+        }
+    }
+
+    /// Searches, from stop 0 to stop 4, a graph whose cheapest route (0, 2, 3, 4) isn't its shortest (0, 1, 3, 4).
+    fn cheapest_route<C>(cost: impl Fn(u16) -> C) -> Option<Vec<usize>>
+    where
+        C: Ord + Hash + Copy + Add<Output = C> + Zero,
+    {
+        let graph = vec![
+            vec![(1, cost(1)), (2, cost(4))],
+            vec![(3, cost(10))],
+            vec![(3, cost(4))],
+            vec![(4, cost(2))],
+            vec![],
+        ];
+        search_astar(Stop::new(0), Stop::new(4), &graph).map(|path| path.iter().map(|stop| stop.id).collect())
+    }
+
+    #[test]
+    fn other_cost_types() {
+        assert_eq!(cheapest_route(i64::from), Some(vec![0, 2, 3, 4]));
+        assert_eq!(cheapest_route(u32::from), Some(vec![0, 2, 3, 4]));
+        assert_eq!(cheapest_route(Fuel), Some(vec![0, 2, 3, 4]));
+    }
+
+    #[test]
+    fn all_paths_match_eager_enumeration() {
+        // An open 4x4 room: the shortest paths corner to corner are every ordering of three downs and three
+        // rights.
+        let world = World {
+            width: 4,
+            height: 4,
+            walls: AHashSet::new(),
+        };
+        fn eager(here: Node, goal: &Node, path: &mut Vec<Node>, paths: &mut Vec<Vec<Node>>) {
+            path.push(here.clone());
+            if here == *goal {
+                paths.push(path.clone());
+            } else {
+                if here.row < goal.row {
+                    eager(
+                        Node {
+                            row: here.row + 1,
+                            ..here.clone()
+                        },
+                        goal,
+                        path,
+                        paths,
+                    );
+                }
+                if here.col < goal.col {
+                    eager(
+                        Node {
+                            col: here.col + 1,
+                            ..here
+                        },
+                        goal,
+                        path,
+                        paths,
+                    );
+                }
+            }
+            path.pop();
+        }
+        let (start, finish) = (Node { row: 0, col: 0 }, Node { row: 3, col: 3 });
+        let mut expected = vec![];
+        eager(start.clone(), &finish, &mut vec![], &mut expected);
+
+        let found = search_astar_all(start, finish, &world).unwrap();
+        assert_eq!(found.cost(), 6);
+        assert_eq!(found.len(), 20);
+        let key = |path: &Vec<Node>| path.iter().map(|node| (node.row, node.col)).collect::<Vec<_>>();
+        let mut materialized = found.paths().collect::<Vec<_>>();
+        materialized.sort_by_key(key);
+        expected.sort_by_key(key);
+        assert!(materialized == expected);
+    }
+
+    #[test]
+    fn all_paths_no_path() {
+        let (mut world, start, finish) = world();
+        world.walls.extend((0..world.width).map(|col| (4, col)));
+        assert!(search_astar_all(start, finish, &world).is_none());
+    }
+
+    /// A cell on the same map, but moving any-angle: eight neighbors, and straight-line shortcuts where the
+    /// line is clear. Costs are Euclidean distances, in thousandths.
+    #[derive(Clone, PartialEq, Eq, Hash, Debug)]
+    struct Cell {
+        row: i64,
+        col: i64,
+    }
+
+    fn euclid(drow: i64, dcol: i64) -> i64 {
+        ((drow * drow + dcol * dcol) as f64).sqrt().mul_add(1000.0, 0.5) as i64
+    }
+
+    fn open_cell(state: &World, row: i64, col: i64) -> bool {
+        row >= 0 && col >= 0 && row < state.height && col < state.width && !state.walls.contains(&(row, col))
+    }
+
+    impl AStarNode for Cell {
+        type Cost = i64;
+        type AssociatedState = World;
+
+        fn heuristic(&self, goal: &Self, _state: &Self::AssociatedState) -> Self::Cost {
+            // Rounding down keeps this optimistic.
+            let (drow, dcol) = (goal.row - self.row, goal.col - self.col);
+            (((drow * drow + dcol * dcol) as f64).sqrt() * 1000.0) as i64
+        }
+
+        fn goal_match(&self, goal: &Self, _state: &Self::AssociatedState) -> bool {
+            self == goal
+        }
+
+        fn neighbors(&self, state: &Self::AssociatedState) -> impl Iterator<Item = (Self, Self::Cost)> {
+            [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)]
+                .into_iter()
+                .filter(move |&(drow, dcol)| {
+                    // No squeezing diagonally between two walls
+                    open_cell(state, self.row + drow, self.col + dcol)
+                        && open_cell(state, self.row + drow, self.col)
+                        && open_cell(state, self.row, self.col + dcol)
+                })
+                .map(|(drow, dcol)| {
+                    (
+                        Cell {
+                            row: self.row + drow,
+                            col: self.col + dcol,
+                        },
+                        euclid(drow, dcol),
+                    )
+                })
+        }
+    }
+
+    impl LineOfSight for Cell {
+        fn line_of_sight(&self, other: &Self, state: &Self::AssociatedState) -> bool {
+            // Walk the segment between the cell centers in small steps, checking every cell the segment's
+            // neighborhood touches. That's conservative (it may refuse a line that just grazes a wall's corner),
+            // which costs some path length, but never lets a path through a wall.
+            let (drow, dcol) = ((other.row - self.row) as f64, (other.col - self.col) as f64);
+            let steps = ((drow.abs().max(dcol.abs())) * 8.0) as i64 + 1;
+            (0..=steps).all(|step| {
+                let t = step as f64 / steps as f64;
+                let (row, col) = (self.row as f64 + 0.5 + t * drow, self.col as f64 + 0.5 + t * dcol);
+                [(-0.05, -0.05), (-0.05, 0.05), (0.05, -0.05), (0.05, 0.05)]
+                    .into_iter()
+                    .all(|(er, ec)| open_cell(state, (row + er).floor() as i64, (col + ec).floor() as i64))
+            })
+        }
+
+        fn straight_cost(&self, other: &Self, _state: &Self::AssociatedState) -> Self::Cost {
+            euclid(other.row - self.row, other.col - self.col)
+        }
+    }
+
+    fn length(path: &[Cell]) -> i64 {
+        path.windows(2)
+            .map(|pair| euclid(pair[1].row - pair[0].row, pair[1].col - pair[0].col))
+            .sum()
+    }
+
+    #[test]
+    fn theta_star_is_shorter() {
+        let (world, start, finish) = world();
+        let start = Cell {
+            row: start.row,
+            col: start.col,
+        };
+        let finish = Cell {
+            row: finish.row,
+            col: finish.col,
+        };
+        let grid_path = search_astar(start.clone(), finish.clone(), &world).unwrap();
+        let any_angle_path = search_theta_star(start.clone(), finish.clone(), &world).unwrap();
+
+        assert_eq!(any_angle_path.first(), Some(&start));
+        assert_eq!(any_angle_path.last(), Some(&finish));
+        assert!(any_angle_path.len() < grid_path.len());
+        for leg in any_angle_path.windows(2) {
+            assert!(
+                leg[0].line_of_sight(&leg[1], &world),
+                "{:?} can't see {:?}",
+                leg[0],
+                leg[1]
+            );
+        }
+        assert!(
+            length(&any_angle_path) < length(&grid_path),
+            "theta* length {}; A* length {}",
+            length(&any_angle_path),
+            length(&grid_path)
+        );
+    }
+
+    #[test]
+    fn theta_star_no_path() {
+        let (mut world, start, finish) = world();
+        world.walls.extend((0..world.width).map(|col| (4, col)));
+        let start = Cell {
+            row: start.row,
+            col: start.col,
+        };
+        let finish = Cell {
+            row: finish.row,
+            col: finish.col,
+        };
+        assert!(search_theta_star(start, finish, &world).is_none());
+    }
+}
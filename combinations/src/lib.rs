@@ -5,6 +5,32 @@
 //! * 7.2.1.2: Generating all permutations (algorithm L)
 //! * 7.2.1.3: Generating all combinations (algorithm T)
 //!
+//! Both copy their source items up front. [CombinationRef] and [PermutationRef] are borrowing counterparts that
+//! yield references into the caller's slice instead. `with_prefix_filter` on either makes an iterator that skips
+//! whole families of results as soon as a partial selection fails a test. The combination iterators can also be
+//! drawn from either end.
+//!
+//! [CombinationSplit] walks through the same selections as [Combination], but as positions, along with the
+//! positions left behind, and without allocating anything per step.
+//!
+//! [MultisetCombination] chooses from items that come with repeat counts, producing each distinct selection once.
+//!
+//! [pairs] is a lightweight shortcut for the very common case of choosing two items at a time.
+//!
+//! For dynamic programming over subsets, [subset_masks] enumerates fixed-size subsets as bitmasks.
+//!
+//! [factorial] and [binomial] count how many results there are, without the risk of overflow going unnoticed.
+//!
+//! [nth_permutation] and [nth_combination] jump straight to a single result by its position in the iteration, and
+//! [Permutation::from_index] starts a whole iteration from there.
+//!
+//! For Cartesian products, [product_rank] and [product_unrank] convert between a tuple of indices and its position
+//! in the product space.
+//!
+//! With the `serde` feature enabled, [Combination] and [Permutation] can be serialized part way through, and then
+//! deserialized later to pick up exactly where they left off. (A deserialized iterator trusts that its state came
+//! from a real iterator; it isn't checked.)
+//!
 #![warn(missing_docs)]
 
 /// An iterator that returns [Vec]s of items representing all combinations, in lexographic order.
@@ -39,19 +65,177 @@
 /// Lexographic order is maintained assuming that the initial set of items is in lexographic order itself. (So
 /// you can adjust the initial order to rank items "higher" or "lower", if that's useful.) But play with it;
 /// it probably doesn't actually behave the way you think it should.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Combination<T> {
     source: Vec<T>,
+    state: CombinationState,
+}
+
+/// The index bookkeeping for algorithm T, shared by [Combination] and [CombinationRef].
+///
+/// `c[0..t]` is the next combination to come off the front, and `back` the next one to come off the back. The two
+/// close in on each other, and the iteration is over once the last one between them has been produced.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct CombinationState {
     c: Vec<usize>,
     j: usize,
     t: usize,
+    back: Vec<usize>,
     done: bool,
+    // The number of combinations not yet produced, or None if there are too many to count in a u128.
+    remaining: Option<u128>,
+}
+
+impl CombinationState {
+    fn new(n: usize, size: usize) -> Self {
+        let mut c = (0..size).collect::<Vec<_>>();
+        c.push(n);
+        c.push(0);
+        CombinationState {
+            c,
+            j: size,
+            t: size,
+            back: (n.saturating_sub(size)..n).collect(),
+            // There's no way to pick more items than there are, so in that case there's nothing to produce.
+            done: size > n,
+            remaining: checked_binomial(n, size),
+        }
+    }
+
+    fn reset(&mut self) {
+        let size = self.t;
+        let n = self.c[size];
+        self.c.clear();
+        self.c.extend(0..size);
+        self.c.push(n);
+        self.c.push(0);
+        self.j = size;
+        self.back.clear();
+        self.back.extend(n.saturating_sub(size)..n);
+        self.done = size > n;
+        self.remaining = checked_binomial(n, size);
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.remaining.map(usize::try_from) {
+            Some(Ok(remaining)) => (remaining, Some(remaining)),
+            _ => (usize::MAX, None),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.remaining
+            .map_or(usize::MAX, |remaining| usize::try_from(remaining).unwrap_or(usize::MAX))
+    }
+
+    /// Produce the combination at the back, then move the back one step toward the front.
+    ///
+    /// This undoes one step of algorithm T: the lowest index that has room below it (that is, isn't already
+    /// sitting on top of the indexes under it) drops by one, and everything under it is pushed as high as it
+    /// will go.
+    fn step_back<R>(&mut self, emit: impl FnOnce(&[usize]) -> R) -> Option<R> {
+        if self.done {
+            return None;
+        }
+        let result = emit(&self.back);
+        self.remaining = self.remaining.map(|remaining| remaining - 1);
+        if self.back == self.c[0..self.t] {
+            // Front and back have met; that was the last one.
+            self.done = true;
+            return Some(result);
+        }
+        // Since the back is still ahead of the front, it isn't the very first combination, so some index has room.
+        let i = (0..self.t)
+            .find(|&i| self.back[i] > i)
+            .expect("the back should be past the first combination");
+        self.back[i] -= 1;
+        for m in 0..i {
+            self.back[m] = self.back[i] - (i - m);
+        }
+        Some(result)
+    }
+
+    // algorithm T from Knuth 7.2.1.3 "Generating all combinations"
+    fn step<R>(&mut self, emit: impl FnOnce(&[usize]) -> R) -> Option<R> {
+        // This structure uses a "child vector" that contains all the indexes into the source data. We do the
+        // combinatorial work on that index vector, as copying an index is likely much cheaper than cloning
+        // the actual items being permuted. Source items are only touched (by `emit`) when the return value is
+        // constructed.
+        if self.done {
+            None
+        } else {
+            let result = emit(&self.c[0..self.t]);
+            self.remaining = self.remaining.map(|remaining| remaining - 1);
+            if self.c[0..self.t] == self.back {
+                // Front and back have met; that was the last one. (This also covers choosing nothing or
+                // everything, which can only be done one way and which algorithm T doesn't handle: it assumes
+                // 0 < t < n.)
+                self.done = true;
+                return Some(result);
+            }
+
+            let mut x;
+            if self.j > 0 {
+                x = self.j;
+            } else {
+                if self.c[0] + 1 < self.c[1] {
+                    self.c[0] += 1;
+                    return Some(result);
+                }
+                self.j = 2;
+                loop {
+                    self.c[self.j - 2] = self.j - 2;
+                    x = self.c[self.j - 1] + 1;
+                    if x != self.c[self.j] {
+                        break;
+                    }
+                    self.j += 1;
+                }
+                if self.j > self.t {
+                    self.done = true;
+                    return Some(result);
+                }
+            }
+            self.c[self.j - 1] = x;
+            self.j -= 1;
+
+            Some(result)
+        }
+    }
+}
+
+/// The reasons [Combination::try_new] can refuse to make an iterator.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CombinationError {
+    /// The combination size is larger than the number of items to choose from.
+    SizeTooLarge {
+        /// The requested combination size
+        size: usize,
+        /// The number of items available
+        len: usize,
+    },
+}
+
+impl std::fmt::Display for CombinationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CombinationError::SizeTooLarge { size, len } => {
+                write!(f, "cannot choose {size} items from a set of {len}")
+            }
+        }
+    }
 }
 
+impl std::error::Error for CombinationError {}
+
 impl<T> Combination<T> {
     /// Create a new combination-generating iterator.
     ///
     /// See the [Combination] structure for more discussion.
     ///
+    /// A `size` of zero produces a single, empty, combination. A `size` larger than the number of items produces
+    /// no combinations at all. (Use [Combination::try_new] to have that reported as an error instead.)
+    ///
     /// # Example
     /// ```
     /// use combinations::Combination;
@@ -70,14 +254,85 @@ impl<T> Combination<T> {
     where
         T: Clone,
     {
-        let mut c = (0..size).collect::<Vec<_>>();
-        c.push(items.len());
-        c.push(0);
         Combination {
             source: items.to_vec(),
-            c,
-            j: size,
-            t: size,
+            state: CombinationState::new(items.len(), size),
+        }
+    }
+
+    /// Create a new combination-generating iterator, or report an error if `size` is larger than the number of
+    /// items.
+    ///
+    /// # Example
+    /// ```
+    /// use combinations::{Combination, CombinationError};
+    ///
+    /// let combos = Combination::try_new(&[1, 2, 3], 3).unwrap().collect::<Vec<_>>();
+    /// assert_eq!(combos, vec![vec![1, 2, 3]]);
+    ///
+    /// let err = Combination::try_new(&[1, 2, 3], 4).err().unwrap();
+    /// assert_eq!(err, CombinationError::SizeTooLarge { size: 4, len: 3 });
+    /// assert_eq!(err.to_string(), "cannot choose 4 items from a set of 3");
+    /// ```
+    pub fn try_new(items: &[T], size: usize) -> Result<Combination<T>, CombinationError>
+    where
+        T: Clone,
+    {
+        if size > items.len() {
+            return Err(CombinationError::SizeTooLarge { size, len: items.len() });
+        }
+        Ok(Combination::new(items, size))
+    }
+
+    /// Restart the iteration from the first combination.
+    ///
+    /// This restores the iterator to the state it had just after construction, without needing to copy the
+    /// source items again.
+    ///
+    /// # Example
+    /// ```
+    /// use combinations::Combination;
+    ///
+    /// let mut combo_iter = Combination::new(&["a", "b", "c"], 2);
+    /// assert_eq!(combo_iter.next(), Some(vec!["a", "b"]));
+    /// assert_eq!(combo_iter.next(), Some(vec!["a", "c"]));
+    /// combo_iter.reset();
+    /// assert_eq!(combo_iter.collect::<Vec<_>>(), vec![vec!["a", "b"], vec!["a", "c"], vec!["b", "c"]]);
+    /// ```
+    pub fn reset(&mut self) {
+        self.state.reset();
+    }
+
+    /// Create an iterator over just those combinations whose every prefix satisfies `prune`.
+    ///
+    /// Where `Combination::new(...).filter(...)` builds every combination and then throws most of them away,
+    /// this checks each partial selection as it grows: as soon as `prune` returns `false` for a prefix, nothing
+    /// that starts with that prefix is generated. That makes it a good fit for constraints like "the sum so far
+    /// is within budget", which can reject a choice long before the combination is complete.
+    ///
+    /// The partial selections are handed to `prune` as references into the source items, in order of their
+    /// position in `items`. Combinations are produced in lexographic order of those positions, which is _not_
+    /// the order [Combination] itself uses.
+    ///
+    /// # Example
+    /// ```
+    /// use combinations::Combination;
+    ///
+    /// let within_budget = |prefix: &[&i32]| prefix.iter().copied().sum::<i32>() <= 6;
+    /// let combos = Combination::with_prefix_filter(&[1, 2, 3, 4, 5], 2, within_budget).collect::<Vec<_>>();
+    /// assert_eq!(combos, vec![vec![1, 2], vec![1, 3], vec![1, 4], vec![1, 5], vec![2, 3], vec![2, 4]]);
+    /// ```
+    pub fn with_prefix_filter<F>(items: &[T], size: usize, prune: F) -> PrunedCombination<T, F>
+    where
+        T: Clone,
+        F: FnMut(&[&T]) -> bool,
+    {
+        PrunedCombination {
+            source: items.to_vec(),
+            size,
+            prune,
+            chosen: Vec::with_capacity(size),
+            cursor: 0,
             done: false,
         }
     }
@@ -89,47 +344,262 @@ where
 {
     type Item = Vec<T>;
 
-    // algorithm T from Knuth 7.2.1.3 "Generating all combinations"
     fn next(&mut self) -> Option<Self::Item> {
-        // This structure uses a "child vector" that contains all the indexes into the source data. We do the
-        // combinatorial work on that index vector, as copying an index is likely much cheaper than cloning
-        // the actual items being permuted. Source items are only cloned when the return value is constructed.
-        if self.done {
-            None
-        } else {
-            let result = self.c[0..self.t]
-                .iter()
-                .map(|&idx| self.source[idx].clone())
-                .collect::<Vec<_>>();
+        let source = &self.source;
+        self.state
+            .step(|indexes| indexes.iter().map(|&idx| source[idx].clone()).collect())
+    }
 
-            let mut x;
-            if self.j > 0 {
-                x = self.j;
-            } else {
-                if self.c[0] + 1 < self.c[1] {
-                    self.c[0] += 1;
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.state.size_hint()
+    }
+}
+
+/// The number of combinations still to come is known in advance: `n` choose `k` at the start, dropping by one for
+/// each combination produced from either end.
+///
+/// If there are more combinations than fit in a [u128], [len](ExactSizeIterator::len) saturates at [usize::MAX]
+/// (as it does for anything over [usize::MAX]), and [size_hint](Iterator::size_hint) returns `(usize::MAX, None)`.
+///
+/// # Example
+/// ```
+/// use combinations::Combination;
+///
+/// let mut combo_iter = Combination::new(&[1, 2, 3, 4, 5], 3);
+/// assert_eq!(combo_iter.len(), 10);
+/// combo_iter.next();
+/// combo_iter.next_back();
+/// assert_eq!(combo_iter.len(), 8);
+/// ```
+impl<T> ExactSizeIterator for Combination<T>
+where
+    T: Clone,
+{
+    fn len(&self) -> usize {
+        self.state.len()
+    }
+}
+
+/// Combinations can also be drawn from the end of the order, working backward. Both ends can be used on the same
+/// iterator; between them, each combination is still produced exactly once.
+///
+/// # Example
+/// ```
+/// use combinations::Combination;
+///
+/// let mut combo_iter = Combination::new(&[10, 20, 30, 40], 2);
+/// assert_eq!(combo_iter.next_back(), Some(vec![30, 40]));
+/// assert_eq!(combo_iter.next_back(), Some(vec![20, 40]));
+/// assert_eq!(combo_iter.next(), Some(vec![10, 20]));
+/// assert_eq!(combo_iter.rev().collect::<Vec<_>>(), vec![vec![10, 40], vec![20, 30], vec![10, 30]]);
+/// ```
+impl<T> DoubleEndedIterator for Combination<T>
+where
+    T: Clone,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let source = &self.source;
+        self.state
+            .step_back(|indexes| indexes.iter().map(|&idx| source[idx].clone()).collect())
+    }
+}
+
+/// An iterator over the combinations whose every prefix passes a test, made by [Combination::with_prefix_filter].
+///
+/// Rather than Knuth's algorithm T, this is a plain depth-first backtracking search, which is what lets it abandon
+/// a partial selection (and everything that would follow from it) as soon as the test fails.
+pub struct PrunedCombination<T, F> {
+    source: Vec<T>,
+    size: usize,
+    prune: F,
+    chosen: Vec<usize>,
+    cursor: usize,
+    done: bool,
+}
+
+impl<T, F> Iterator for PrunedCombination<T, F>
+where
+    T: Clone,
+    F: FnMut(&[&T]) -> bool,
+{
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if self.size == 0 {
+            self.done = true;
+            return (self.prune)(&[]).then(Vec::new);
+        }
+        loop {
+            // Extend the current prefix with the next candidate, as long as there are enough items left after it
+            // to fill out the combination.
+            if self.cursor + (self.size - self.chosen.len()) <= self.source.len() {
+                self.chosen.push(self.cursor);
+                self.cursor += 1;
+                let prefix = self.chosen.iter().map(|&idx| &self.source[idx]).collect::<Vec<_>>();
+                let viable = (self.prune)(&prefix);
+                if viable && self.chosen.len() == self.size {
+                    let result = prefix.into_iter().cloned().collect();
+                    self.chosen.pop();
                     return Some(result);
                 }
-                self.j = 2;
-                loop {
-                    self.c[self.j - 2] = self.j - 2;
-                    x = self.c[self.j - 1] + 1;
-                    if x != self.c[self.j] {
-                        break;
-                    }
-                    self.j += 1;
+                if !viable {
+                    self.chosen.pop();
                 }
-                if self.j > self.t {
-                    self.done = true;
-                    return Some(result);
+            } else {
+                // Out of candidates at this depth; back up a level and try the next item there.
+                match self.chosen.pop() {
+                    Some(idx) => self.cursor = idx + 1,
+                    None => {
+                        self.done = true;
+                        return None;
+                    }
                 }
             }
-            self.c[self.j - 1] = x;
-            self.j -= 1;
+        }
+    }
+}
 
-            Some(result)
+/// An iterator like [Combination], but which borrows its source items rather than copying them.
+///
+/// Each combination is a [Vec] of references into the original slice, so no item is ever cloned (and [Clone]
+/// isn't required). The lifetime `'a` ties the iterator, and every combination it produces, to that slice: the
+/// slice must outlive them all.
+///
+/// # Example
+/// ```
+/// use combinations::CombinationRef;
+///
+/// let words = [String::from("long String A"), String::from("String B"), String::from("C")];
+/// let combos = CombinationRef::new(&words, 2).collect::<Vec<_>>();
+/// assert_eq!(combos, vec![
+///     vec![&words[0], &words[1]],
+///     vec![&words[0], &words[2]],
+///     vec![&words[1], &words[2]],
+/// ]);
+/// ```
+pub struct CombinationRef<'a, T> {
+    source: &'a [T],
+    state: CombinationState,
+}
+
+impl<'a, T> CombinationRef<'a, T> {
+    /// Create a new combination-generating iterator over borrowed items.
+    ///
+    /// See the [CombinationRef] structure for more discussion.
+    pub fn new(items: &'a [T], size: usize) -> Self {
+        CombinationRef {
+            source: items,
+            state: CombinationState::new(items.len(), size),
+        }
+    }
+
+    /// Restart the iteration from the first combination.
+    pub fn reset(&mut self) {
+        self.state.reset();
+    }
+}
+
+impl<'a, T> Iterator for CombinationRef<'a, T> {
+    type Item = Vec<&'a T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let source = self.source;
+        self.state
+            .step(|indexes| indexes.iter().map(|&idx| &source[idx]).collect())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.state.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for CombinationRef<'_, T> {
+    fn len(&self) -> usize {
+        self.state.len()
+    }
+}
+
+impl<T> DoubleEndedIterator for CombinationRef<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let source = self.source;
+        self.state
+            .step_back(|indexes| indexes.iter().map(|&idx| &source[idx]).collect())
+    }
+}
+
+/// A walk through every way to split positions `0..n` into `k` chosen ones and the rest.
+///
+/// The splits come in the same order as the combinations from [Combination]. Each one is handed out as a pair of
+/// slices (chosen, then remaining, each in increasing order) that point into buffers owned by the walker, which
+/// are overwritten for the next split. So no matter how many splits there are, nothing is allocated after
+/// construction.
+///
+/// The price is that this can't be an [Iterator]: the slices borrow the walker, and so they have to be dropped
+/// (or copied) before [CombinationSplit::next_split] can be called again. Use it with `while let`.
+///
+/// # Example
+/// ```
+/// use combinations::CombinationSplit;
+///
+/// let mut splits = CombinationSplit::new(4, 2);
+/// let mut seen = vec![];
+/// while let Some((chosen, rest)) = splits.next_split() {
+///     seen.push(format!("{chosen:?}|{rest:?}"));
+/// }
+/// assert_eq!(seen, [
+///     "[0, 1]|[2, 3]",
+///     "[0, 2]|[1, 3]",
+///     "[1, 2]|[0, 3]",
+///     "[0, 3]|[1, 2]",
+///     "[1, 3]|[0, 2]",
+///     "[2, 3]|[0, 1]",
+/// ]);
+/// ```
+pub struct CombinationSplit {
+    state: CombinationState,
+    n: usize,
+    chosen: Vec<usize>,
+    rest: Vec<usize>,
+}
+
+impl CombinationSplit {
+    /// Prepare to split `n` positions into `k` chosen and `n - k` remaining. If `k` is larger than `n`, there are
+    /// no splits at all.
+    pub fn new(n: usize, k: usize) -> Self {
+        CombinationSplit {
+            state: CombinationState::new(n, k),
+            n,
+            chosen: Vec::with_capacity(k),
+            rest: Vec::with_capacity(n.saturating_sub(k)),
         }
     }
+
+    /// The next split, as the chosen positions and the remaining positions, or `None` once they've all been seen.
+    ///
+    /// The slices are only good until the next call.
+    pub fn next_split(&mut self) -> Option<(&[usize], &[usize])> {
+        let (chosen, rest, n) = (&mut self.chosen, &mut self.rest, self.n);
+        self.state.step(|indexes| {
+            chosen.clear();
+            chosen.extend_from_slice(indexes);
+            rest.clear();
+            // The chosen positions are in increasing order, so the gaps between them are what's left.
+            let mut start = 0;
+            for &idx in indexes.iter().chain([&n]) {
+                rest.extend(start..idx);
+                start = idx + 1;
+            }
+        })?;
+        Some((&self.chosen, &self.rest))
+    }
+
+    /// Restart from the first split.
+    pub fn reset(&mut self) {
+        self.state.reset();
+    }
 }
 
 /// An iterator that returns [Vec]s of items representing all permutations, in lexographic order.
@@ -160,26 +630,124 @@ where
 ///
 /// Note that the number of items returned by the permutation iterator is the factorial of the number of
 /// items. This can be extremely large. Consider well whether you really want that list.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Permutation<T> {
     items: Vec<T>,
+    state: PermutationState,
+}
+
+/// The index bookkeeping for algorithm L, shared by [Permutation] and [PermutationRef].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct PermutationState {
     a: Vec<usize>,
     n: usize,
     done: bool,
+    emitted: u128,
 }
 
-impl<T> Permutation<T>
-where
-    T: Clone,
-{
-    /// Create a new permutation-generating iterator.
-    ///
-    /// See the [Permutation] structure for more discussion.
-    ///
-    /// # Example
-    /// ```
-    /// use combinations::Permutation;
-    ///
-    /// let perm_iter = Permutation::new(&[10, 20, 30, 40]);
+impl PermutationState {
+    fn new(n: usize) -> Self {
+        PermutationState {
+            n,
+            a: (0..=n).collect::<Vec<_>>(),
+            done: false,
+            emitted: 0,
+        }
+    }
+
+    // The state just before producing the permutation at `index` (in lexographic order). An index past the end
+    // leaves nothing more to produce.
+    fn from_index(n: usize, index: u128) -> Self {
+        match checked_factorial(n) {
+            Some(total) if index >= total => PermutationState {
+                n,
+                a: (0..=n).collect::<Vec<_>>(),
+                done: true,
+                emitted: total,
+            },
+            _ => PermutationState {
+                n,
+                a: [0]
+                    .into_iter()
+                    .chain(lehmer_decode(n, index).into_iter().map(|idx| idx + 1))
+                    .collect(),
+                done: false,
+                emitted: index,
+            },
+        }
+    }
+
+    fn reset(&mut self) {
+        self.a.clear();
+        self.a.extend(0..=self.n);
+        self.done = false;
+        self.emitted = 0;
+    }
+
+    // The number of permutations not yet produced, or None if n! is too big for a u128 (n > 34).
+    fn remaining(&self) -> Option<u128> {
+        Some(checked_factorial(self.n)? - self.emitted)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.remaining().map(usize::try_from) {
+            Some(Ok(remaining)) => (remaining, Some(remaining)),
+            _ => (usize::MAX, None),
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.remaining()
+            .map_or(usize::MAX, |remaining| usize::try_from(remaining).unwrap_or(usize::MAX))
+    }
+
+    fn step<R>(&mut self, emit: impl FnOnce(&[usize]) -> R) -> Option<R> {
+        if self.done {
+            None
+        } else {
+            // Algorithm L from Knuth 7.2.1.2. Generating all permutations. (The indexes handed to `emit` are
+            // 1-based.)
+            let result = Some(emit(&self.a[1..=self.n]));
+            self.emitted += 1;
+
+            let mut j = self.n - 1;
+            while j > 0 && self.a[j + 1] <= self.a[j] {
+                j -= 1;
+            }
+            if j == 0 {
+                self.done = true;
+            } else {
+                let mut l = self.n;
+                while self.a[j] >= self.a[l] {
+                    l -= 1;
+                }
+                (self.a[j], self.a[l]) = (self.a[l], self.a[j]);
+                let mut k = j + 1;
+                let mut l = self.n;
+                while k < l {
+                    (self.a[k], self.a[l]) = (self.a[l], self.a[k]);
+                    k += 1;
+                    l -= 1;
+                }
+            }
+            result
+        }
+    }
+}
+
+impl<T> Permutation<T>
+where
+    T: Clone,
+{
+    /// Create a new permutation-generating iterator.
+    ///
+    /// See the [Permutation] structure for more discussion.
+    ///
+    /// # Example
+    /// ```
+    /// use combinations::Permutation;
+    ///
+    /// let perm_iter = Permutation::new(&[10, 20, 30, 40]);
     /// assert_eq!(perm_iter.collect::<Vec<_>>(), vec![
     ///     vec![10, 20, 30, 40],
     ///     vec![10, 20, 40, 30],
@@ -208,11 +776,113 @@ where
     /// ]);
     /// ```
     pub fn new(items: &[T]) -> Self {
-        let n = items.len();
         Permutation {
             items: items.to_vec(),
-            n,
-            a: (0..=n).collect::<Vec<_>>(),
+            state: PermutationState::new(items.len()),
+        }
+    }
+
+    /// Restart the iteration from the first permutation.
+    ///
+    /// This restores the iterator to the state it had just after construction, without needing to copy the
+    /// source items again.
+    ///
+    /// # Example
+    /// ```
+    /// use combinations::Permutation;
+    ///
+    /// let mut perm_iter = Permutation::new(&[1, 2]);
+    /// assert_eq!(perm_iter.by_ref().collect::<Vec<_>>(), vec![vec![1, 2], vec![2, 1]]);
+    /// assert_eq!(perm_iter.next(), None);
+    /// perm_iter.reset();
+    /// assert_eq!(perm_iter.collect::<Vec<_>>(), vec![vec![1, 2], vec![2, 1]]);
+    /// ```
+    pub fn reset(&mut self) {
+        self.state.reset();
+    }
+
+    /// Create a permutation-generating iterator that starts partway through, at the permutation in position
+    /// `index` of the full iteration.
+    ///
+    /// Paired with [Permutation::current_index], this lets a long search over permutations be saved and resumed
+    /// later. The iterator is set up directly from `index` (as a Lehmer code), without stepping through the
+    /// permutations before it. If `index` is past the last permutation, the iterator produces nothing.
+    ///
+    /// # Example
+    /// ```
+    /// use combinations::Permutation;
+    ///
+    /// let mut perm_iter = Permutation::new(&["a", "b", "c", "d"]);
+    /// perm_iter.nth(9);
+    /// let saved = perm_iter.current_index();
+    /// assert_eq!(saved, 10);
+    ///
+    /// let resumed = Permutation::from_index(&["a", "b", "c", "d"], saved);
+    /// assert_eq!(resumed.collect::<Vec<_>>(), perm_iter.collect::<Vec<_>>());
+    /// ```
+    pub fn from_index(items: &[T], index: u128) -> Self {
+        Permutation {
+            items: items.to_vec(),
+            state: PermutationState::from_index(items.len(), index),
+        }
+    }
+
+    /// The position, in the full iteration, of the permutation the iterator will produce next. (Once the
+    /// iterator is exhausted, this is the total number of permutations.)
+    pub fn current_index(&self) -> u128 {
+        self.state.emitted
+    }
+
+    /// The number of permutations still to come, or `None` if that's too big for a [u128].
+    ///
+    /// The full count is the factorial of the number of items, which outgrows a [usize] past 20 items (on a
+    /// 64-bit machine) and a [u128] past 34. [Iterator::count] and [Iterator::size_hint] report in [usize], so
+    /// they saturate: `count` returns [usize::MAX], and `size_hint` returns `(usize::MAX, None)`, whenever the true
+    /// count doesn't fit. (Neither actually runs the iterator.)
+    ///
+    /// # Example
+    /// ```
+    /// use combinations::Permutation;
+    ///
+    /// let mut perm_iter = Permutation::new(&[1, 2, 3]);
+    /// assert_eq!(perm_iter.exact_count(), Some(6));
+    /// perm_iter.next();
+    /// assert_eq!(perm_iter.exact_count(), Some(5));
+    ///
+    /// let items = (0..25).collect::<Vec<_>>();
+    /// let huge = Permutation::new(&items);
+    /// assert_eq!(huge.exact_count(), Some(15_511_210_043_330_985_984_000_000));
+    /// assert_eq!(huge.size_hint(), (usize::MAX, None));
+    /// ```
+    pub fn exact_count(&self) -> Option<u128> {
+        self.state.remaining()
+    }
+
+    /// Create an iterator over just those permutations whose every prefix satisfies `prune`.
+    ///
+    /// This is the permutation counterpart of [Combination::with_prefix_filter]: each partial ordering is checked
+    /// as it grows, and as soon as `prune` returns `false`, no permutation starting that way is generated.
+    /// Permutations come out in the same lexographic order [Permutation] uses.
+    ///
+    /// # Example
+    /// ```
+    /// use combinations::Permutation;
+    ///
+    /// // Only orderings where each item is no more than one away from the one before it.
+    /// let small_steps = |prefix: &[&i32]| prefix.windows(2).all(|pair| (pair[0] - pair[1]).abs() <= 1);
+    /// let perms = Permutation::with_prefix_filter(&[1, 2, 3], small_steps).collect::<Vec<_>>();
+    /// assert_eq!(perms, vec![vec![1, 2, 3], vec![3, 2, 1]]);
+    /// ```
+    pub fn with_prefix_filter<F>(items: &[T], prune: F) -> PrunedPermutation<T, F>
+    where
+        F: FnMut(&[&T]) -> bool,
+    {
+        PrunedPermutation {
+            items: items.to_vec(),
+            prune,
+            chosen: Vec::with_capacity(items.len()),
+            used: vec![false; items.len()],
+            cursor: 0,
             done: false,
         }
     }
@@ -224,39 +894,1040 @@ where
 {
     type Item = Vec<T>;
 
+    fn next(&mut self) -> Option<Self::Item> {
+        let items = &self.items;
+        self.state
+            .step(|indexes| indexes.iter().map(|&idx| items[idx - 1].clone()).collect())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.state.size_hint()
+    }
+
+    fn count(self) -> usize {
+        self.state.count()
+    }
+}
+
+impl Permutation<usize> {
+    /// Use each permutation of positions to reorder `data`, producing the reordered items by reference.
+    ///
+    /// This is the usual way to try every ordering of one list against another: permute the positions
+    /// `0..data.len()`, and each permutation picks out `data`'s items in that order.
+    ///
+    /// # Panics
+    /// Panics if a permutation names a position that `data` doesn't have.
+    ///
+    /// # Example
+    /// ```
+    /// use combinations::Permutation;
+    ///
+    /// let weights = [5, 7, 2];
+    /// let orders = Permutation::new(&[0, 1, 2]).apply_to(&weights).collect::<Vec<_>>();
+    /// assert_eq!(orders.len(), 6);
+    /// assert_eq!(orders[1], [&5, &2, &7]);
+    /// ```
+    pub fn apply_to<U>(self, data: &[U]) -> impl Iterator<Item = Vec<&U>> {
+        self.map(move |order| order.into_iter().map(|idx| &data[idx]).collect())
+    }
+}
+
+/// An iterator over the permutations whose every prefix passes a test, made by [Permutation::with_prefix_filter].
+///
+/// Like [PrunedCombination], this is a depth-first backtracking search rather than a Knuth algorithm.
+pub struct PrunedPermutation<T, F> {
+    items: Vec<T>,
+    prune: F,
+    chosen: Vec<usize>,
+    used: Vec<bool>,
+    cursor: usize,
+    done: bool,
+}
+
+impl<T, F> Iterator for PrunedPermutation<T, F>
+where
+    T: Clone,
+    F: FnMut(&[&T]) -> bool,
+{
+    type Item = Vec<T>;
+
     fn next(&mut self) -> Option<Self::Item> {
         if self.done {
-            None
-        } else {
-            // Algorithm L from Knuth 7.2.1.2. Generating all permutations.
-            let result = Some(
-                self.a[1..=self.n]
-                    .iter()
-                    .map(|&idx| self.items[idx - 1].clone())
-                    .collect::<Vec<_>>(),
-            );
+            return None;
+        }
+        loop {
+            // Extend the current prefix with the next unused item at or after the cursor.
+            match (self.cursor..self.items.len()).find(|&idx| !self.used[idx]) {
+                Some(idx) => {
+                    self.chosen.push(idx);
+                    let prefix = self.chosen.iter().map(|&idx| &self.items[idx]).collect::<Vec<_>>();
+                    let viable = (self.prune)(&prefix);
+                    if viable && self.chosen.len() == self.items.len() {
+                        let result = prefix.into_iter().cloned().collect();
+                        self.chosen.pop();
+                        self.cursor = idx + 1;
+                        return Some(result);
+                    }
+                    if viable {
+                        self.used[idx] = true;
+                        self.cursor = 0;
+                    } else {
+                        self.chosen.pop();
+                        self.cursor = idx + 1;
+                    }
+                }
+                None => {
+                    // Out of candidates at this depth; back up a level and try the next item there.
+                    match self.chosen.pop() {
+                        Some(idx) => {
+                            self.used[idx] = false;
+                            self.cursor = idx + 1;
+                        }
+                        None => {
+                            self.done = true;
+                            return None;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
 
-            let mut j = self.n - 1;
-            while j > 0 && self.a[j + 1] <= self.a[j] {
-                j -= 1;
+/// An iterator like [Permutation], but which borrows its source items rather than copying them.
+///
+/// Each permutation is a [Vec] of references into the original slice, so no item is ever cloned (and [Clone]
+/// isn't required). The lifetime `'a` ties the iterator, and every permutation it produces, to that slice: the
+/// slice must outlive them all.
+///
+/// # Example
+/// ```
+/// use combinations::PermutationRef;
+///
+/// let words = [String::from("x"), String::from("y")];
+/// let perms = PermutationRef::new(&words).collect::<Vec<_>>();
+/// assert_eq!(perms, vec![vec![&words[0], &words[1]], vec![&words[1], &words[0]]]);
+/// ```
+pub struct PermutationRef<'a, T> {
+    items: &'a [T],
+    state: PermutationState,
+}
+
+impl<'a, T> PermutationRef<'a, T> {
+    /// Create a new permutation-generating iterator over borrowed items.
+    ///
+    /// See the [PermutationRef] structure for more discussion.
+    pub fn new(items: &'a [T]) -> Self {
+        PermutationRef {
+            items,
+            state: PermutationState::new(items.len()),
+        }
+    }
+
+    /// Create a permutation-generating iterator over borrowed items, starting at the permutation in position
+    /// `index`.
+    ///
+    /// See [Permutation::from_index].
+    pub fn from_index(items: &'a [T], index: u128) -> Self {
+        PermutationRef {
+            items,
+            state: PermutationState::from_index(items.len(), index),
+        }
+    }
+
+    /// The position, in the full iteration, of the permutation the iterator will produce next.
+    ///
+    /// See [Permutation::current_index].
+    pub fn current_index(&self) -> u128 {
+        self.state.emitted
+    }
+
+    /// Restart the iteration from the first permutation.
+    pub fn reset(&mut self) {
+        self.state.reset();
+    }
+
+    /// The number of permutations still to come, or `None` if that's too big for a [u128].
+    ///
+    /// See [Permutation::exact_count].
+    pub fn exact_count(&self) -> Option<u128> {
+        self.state.remaining()
+    }
+}
+
+impl<'a, T> Iterator for PermutationRef<'a, T> {
+    type Item = Vec<&'a T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let items = self.items;
+        self.state
+            .step(|indexes| indexes.iter().map(|&idx| &items[idx - 1]).collect())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.state.size_hint()
+    }
+
+    fn count(self) -> usize {
+        self.state.count()
+    }
+}
+
+/// An iterator over the distinct ways to choose `k` items from a multiset, where each item may be picked up to a
+/// given number of times.
+///
+/// Given a flat slice with repeated values, [Combination] treats every copy as distinct, so the same selection
+/// comes out many times. Here the repeats are given as counts instead, and each distinct selection is produced
+/// exactly once.
+///
+/// Each selection lists its items in the order they appear in `items` (repeats together). The selections
+/// themselves come in lexographic order of those lists, by position in `items`: so the ones using the most of the
+/// first item come first. If `k` is more than the total count, there are no selections; if `k` is zero, there's
+/// exactly one, which is empty.
+///
+/// # Example
+/// ```
+/// use combinations::MultisetCombination;
+///
+/// let combos = MultisetCombination::new(&[("a", 2), ("b", 1)], 2).collect::<Vec<_>>();
+/// assert_eq!(combos, vec![vec!["a", "a"], vec!["a", "b"]]);
+///
+/// // 5 apples and 2 oranges, choosing 3
+/// let fruit = MultisetCombination::new(&[('A', 5), ('O', 2)], 3).collect::<Vec<_>>();
+/// assert_eq!(fruit, vec![vec!['A', 'A', 'A'], vec!['A', 'A', 'O'], vec!['A', 'O', 'O']]);
+/// ```
+pub struct MultisetCombination<T> {
+    items: Vec<T>,
+    counts: Vec<usize>,
+    // available[i] is the total count of items i and later.
+    available: Vec<usize>,
+    k: usize,
+    // The positions (in `items`) of the current selection, in non-decreasing order.
+    chosen: Vec<usize>,
+    done: bool,
+}
+
+impl<T> MultisetCombination<T>
+where
+    T: Clone,
+{
+    /// Create a new iterator choosing `k` items, where `items` pairs each distinct item with how many of it there
+    /// are.
+    ///
+    /// See the [MultisetCombination] structure for more discussion.
+    pub fn new(items: &[(T, usize)], k: usize) -> Self {
+        let counts = items.iter().map(|(_, count)| *count).collect::<Vec<_>>();
+        let mut available = counts.clone();
+        for idx in (0..available.len().saturating_sub(1)).rev() {
+            available[idx] += available[idx + 1];
+        }
+        available.push(0);
+        let mut result = MultisetCombination {
+            items: items.iter().map(|(item, _)| item.clone()).collect(),
+            counts,
+            available,
+            k,
+            chosen: Vec::with_capacity(k),
+            done: false,
+        };
+        result.done = !result.fill_from(0);
+        result
+    }
+
+    // Fill out the selection as early (lexographically) as possible, using only items from position `start` on.
+    // Returns false (changing nothing) if there aren't enough of those items left.
+    fn fill_from(&mut self, start: usize) -> bool {
+        let need = self.k - self.chosen.len();
+        if self.available[start] < need {
+            return false;
+        }
+        let mut idx = start;
+        while self.chosen.len() < self.k {
+            let take = self.counts[idx].min(self.k - self.chosen.len());
+            self.chosen.extend(std::iter::repeat_n(idx, take));
+            idx += 1;
+        }
+        true
+    }
+
+    // Move on to the next selection, by dropping items from the end until one can be swapped for a later item.
+    fn advance(&mut self) {
+        while let Some(last) = self.chosen.pop() {
+            // Nothing earlier in the selection is at a later position than `last`, so every item after it is
+            // entirely unused.
+            if let Some(next) = (last + 1..self.items.len()).find(|&idx| self.counts[idx] > 0) {
+                if self.fill_from(next) {
+                    return;
+                }
             }
-            if j == 0 {
-                self.done = true;
-            } else {
-                let mut l = self.n;
-                while self.a[j] >= self.a[l] {
-                    l -= 1;
+        }
+        self.done = true;
+    }
+}
+
+impl<T> Iterator for MultisetCombination<T>
+where
+    T: Clone,
+{
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.chosen.iter().map(|&idx| self.items[idx].clone()).collect();
+        self.advance();
+        Some(result)
+    }
+}
+
+/// Every unordered pair of items from a slice, as a tuple of references.
+///
+/// This is the same set of pairs as `Combination::new(items, 2)`, but nothing is allocated or cloned along the way,
+/// which makes it the better choice for the common "compare everything with everything else" loop. The pairs come
+/// in order of the first item's position, then the second's: each item is paired with every item after it, in
+/// turn. (That's a different order than [Combination] uses.)
+///
+/// # Example
+/// ```
+/// use combinations::pairs;
+///
+/// assert_eq!(pairs(&[1, 2, 3]).collect::<Vec<_>>(), vec![(&1, &2), (&1, &3), (&2, &3)]);
+/// assert_eq!(pairs(&[1]).count(), 0);
+/// ```
+pub fn pairs<T>(items: &[T]) -> impl Iterator<Item = (&T, &T)> {
+    items
+        .iter()
+        .enumerate()
+        .flat_map(move |(idx, first)| items[idx + 1..].iter().map(move |second| (first, second)))
+}
+
+/// Every `k`-element subset of `n` items, as bitmasks, in increasing numeric order.
+///
+/// Bit `i` of a mask is set when item `i` is in the subset. This is the natural form for dynamic programming over
+/// subsets, where a table is indexed by the mask. The masks are generated with Gosper's hack, so each one costs
+/// only a handful of arithmetic operations. There are no subsets if `k > n`; there's exactly one (the empty
+/// mask) if `k` is zero.
+///
+/// # Panics
+/// Panics if `n` is more than 64, as the masks wouldn't fit in a [u64].
+///
+/// # Example
+/// ```
+/// use combinations::subset_masks;
+///
+/// assert_eq!(subset_masks(4, 3).collect::<Vec<_>>(), vec![0b0111, 0b1011, 0b1101, 0b1110]);
+/// assert_eq!(subset_masks(64, 64).collect::<Vec<_>>(), vec![u64::MAX]);
+/// ```
+pub fn subset_masks(n: usize, k: usize) -> impl Iterator<Item = u64> {
+    assert!(n <= 64, "subset masks hold at most 64 items (asked for {n})");
+    // The arithmetic is done in a u128, so that stepping past the last mask for n=64 doesn't overflow.
+    let limit = 1_u128 << n;
+    let first = (k <= n).then(|| (1_u128 << k) - 1);
+    std::iter::successors(first, move |&mask| {
+        if mask == 0 {
+            return None;
+        }
+        let lowest = mask & mask.wrapping_neg();
+        let ripple = mask + lowest;
+        let next = (((ripple ^ mask) >> 2) / lowest) | ripple;
+        (next < limit).then_some(next)
+    })
+    .map(|mask| mask as u64)
+}
+
+/// `n!`, the number of permutations of `n` items, or `None` if that doesn't fit in a [u128].
+///
+/// # Example
+/// ```
+/// use combinations::factorial;
+///
+/// assert_eq!(factorial(0), Some(1));
+/// assert_eq!(factorial(5), Some(120));
+/// assert_eq!(factorial(35), None);
+/// ```
+pub fn factorial(n: u32) -> Option<u128> {
+    checked_factorial(n as usize)
+}
+
+/// The binomial coefficient `n` choose `k`, the number of combinations of `k` items from `n`, or `None` if that
+/// doesn't fit in a [u128].
+///
+/// This builds up the result one factor at a time, and never makes a number bigger than the result along the
+/// way, so anything that fits can be computed.
+///
+/// # Example
+/// ```
+/// use combinations::binomial;
+///
+/// assert_eq!(binomial(4, 2), Some(6));
+/// assert_eq!(binomial(4, 5), Some(0));
+/// assert_eq!(binomial(52, 5), Some(2_598_960));
+/// ```
+pub fn binomial(n: u32, k: u32) -> Option<u128> {
+    checked_binomial(n as usize, k as usize)
+}
+
+// The crate's iterators count items in `usize`s, so they use these rather than the public versions.
+fn checked_factorial(n: usize) -> Option<u128> {
+    (1..=n as u128).try_fold(1_u128, |acc, k| acc.checked_mul(k))
+}
+
+fn checked_binomial(n: usize, k: usize) -> Option<u128> {
+    if k > n {
+        return Some(0);
+    }
+    let k = k.min(n - k) as u128;
+    let n = n as u128;
+    // Each partial product is itself a binomial coefficient, so `acc * (n - i)` is always a multiple of `i + 1`.
+    // Whatever part of `i + 1` doesn't divide `acc` must then divide `n - i`, so both divisions can be done before
+    // the multiplication.
+    (0..k).try_fold(1_u128, |acc, i| {
+        let common = gcd(acc, i + 1);
+        (acc / common).checked_mul((n - i) / ((i + 1) / common))
+    })
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// The permutation of `items` at position `index` in the order [Permutation] produces them, or `None` if there
+/// aren't that many permutations.
+///
+/// This decodes `index` in the factorial number system (as a Lehmer code), so it takes time proportional to the
+/// square of the number of items, no matter how big `index` is.
+///
+/// # Example
+/// ```
+/// use combinations::{nth_permutation, Permutation};
+///
+/// assert_eq!(nth_permutation(&["a", "b", "c"], 3), Some(vec!["b", "c", "a"]));
+/// assert_eq!(nth_permutation(&["a", "b", "c"], 6), None);
+///
+/// let items = (0..10).collect::<Vec<_>>();
+/// assert_eq!(nth_permutation(&items, 500), Permutation::new(&items).nth(500));
+/// ```
+pub fn nth_permutation<T: Clone>(items: &[T], index: u128) -> Option<Vec<T>> {
+    if checked_factorial(items.len()).is_some_and(|total| index >= total) {
+        return None;
+    }
+    Some(
+        lehmer_decode(items.len(), index)
+            .into_iter()
+            .map(|idx| items[idx].clone())
+            .collect(),
+    )
+}
+
+/// The positions (0-based) making up the permutation of `n` items at lexographic position `index`.
+///
+/// Each place takes one digit of `index` in the factorial number system, which picks from the positions not
+/// yet used. `index` must be less than `n!`.
+fn lehmer_decode(n: usize, index: u128) -> Vec<usize> {
+    let mut pool = (0..n).collect::<Vec<_>>();
+    let mut remainder = index;
+    let mut result = Vec::with_capacity(n);
+    for left in (0..n).rev() {
+        // When `left!` doesn't fit in a u128, it's certainly bigger than what remains of the index.
+        let digit = checked_factorial(left).map_or(0, |place| {
+            let digit = remainder / place;
+            remainder %= place;
+            digit as usize
+        });
+        result.push(pool.remove(digit));
+    }
+    result
+}
+
+/// The `k`-item combination of `items` at position `index` in the order [Combination] produces them, or `None` if
+/// there aren't that many combinations.
+///
+/// This decodes `index` in the combinatorial number system, so there's no need to step through all the
+/// combinations that come before it.
+///
+/// # Example
+/// ```
+/// use combinations::{nth_combination, Combination};
+///
+/// assert_eq!(nth_combination(&[10, 20, 30, 40], 2, 3), Some(vec![10, 40]));
+/// assert_eq!(nth_combination(&[10, 20, 30, 40], 2, 6), None);
+///
+/// let items = (0..20).collect::<Vec<_>>();
+/// assert_eq!(nth_combination(&items, 5, 500), Combination::new(&items, 5).nth(500));
+/// ```
+pub fn nth_combination<T: Clone>(items: &[T], k: usize, index: u128) -> Option<Vec<T>> {
+    if checked_binomial(items.len(), k).is_some_and(|total| index >= total) {
+        return None;
+    }
+    // Working from the highest chosen position down: each is the largest `c` with `c choose i` no more than what
+    // remains of the index. (A coefficient too big for a u128 is certainly too big for the index.)
+    let mut remainder = index;
+    let mut positions = vec![0; k];
+    let mut c = items.len();
+    for i in (1..=k).rev() {
+        c -= 1;
+        while checked_binomial(c, i).is_none_or(|count| count > remainder) {
+            c -= 1;
+        }
+        remainder -= checked_binomial(c, i).expect("coefficient should fit, as it's no more than the index");
+        positions[i - 1] = c;
+    }
+    Some(positions.into_iter().map(|idx| items[idx].clone()).collect())
+}
+
+/// The position of a tuple of indices within a Cartesian product space, counting from zero.
+///
+/// The product of dimensions `dims` is all the tuples `[i₀, i₁, …]` with each `iₖ < dims[k]`, taken in
+/// lexographic order (so the last index varies fastest). This is the inverse of [product_unrank], and lets a big
+/// product space be split into numbered chunks, or entered partway through.
+///
+/// # Panics
+/// Panics if `indices` and `dims` differ in length, if any index is out of range for its dimension, or if the
+/// size of the product space doesn't fit in a [u128].
+///
+/// # Example
+/// ```
+/// use combinations::product_rank;
+///
+/// assert_eq!(product_rank(&[0, 0], &[2, 3]), 0);
+/// assert_eq!(product_rank(&[0, 2], &[2, 3]), 2);
+/// assert_eq!(product_rank(&[1, 0], &[2, 3]), 3);
+/// assert_eq!(product_rank(&[1, 2], &[2, 3]), 5);
+/// ```
+pub fn product_rank(indices: &[usize], dims: &[usize]) -> u128 {
+    assert_eq!(indices.len(), dims.len(), "indices and dims must be the same length");
+    indices.iter().zip(dims).fold(0, |rank, (&idx, &dim)| {
+        assert!(idx < dim, "index {idx} is out of range for a dimension of size {dim}");
+        rank.checked_mul(dim as u128)
+            .and_then(|rank| rank.checked_add(idx as u128))
+            .expect("product space should fit in a u128")
+    })
+}
+
+/// The tuple of indices at position `m` within a Cartesian product space.
+///
+/// This is the inverse of [product_rank]; see there for the ordering.
+///
+/// # Panics
+/// Panics if `m` is not less than the number of tuples in the space.
+///
+/// # Example
+/// ```
+/// use combinations::product_unrank;
+///
+/// assert_eq!(product_unrank(0, &[2, 3]), vec![0, 0]);
+/// assert_eq!(product_unrank(4, &[2, 3]), vec![1, 1]);
+/// assert_eq!(product_unrank(0, &[]), Vec::<usize>::new());
+/// ```
+pub fn product_unrank(m: u128, dims: &[usize]) -> Vec<usize> {
+    let mut remainder = m;
+    let mut indices = vec![0; dims.len()];
+    for (slot, &dim) in indices.iter_mut().zip(dims).rev() {
+        assert!(dim > 0, "the product space is empty");
+        let dim = dim as u128;
+        *slot = (remainder % dim) as usize;
+        remainder /= dim;
+    }
+    assert_eq!(remainder, 0, "rank {m} is beyond the end of the product space");
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An item that refuses to be cloned.
+    #[derive(Debug, PartialEq)]
+    struct NoClone(u32);
+    impl Clone for NoClone {
+        fn clone(&self) -> Self {
+            panic!("NoClone({}) was cloned", self.0)
+        }
+    }
+
+    #[test]
+    fn combination_ref_never_clones() {
+        let items = [NoClone(1), NoClone(2), NoClone(3), NoClone(4)];
+        let combos = CombinationRef::new(&items, 3)
+            .map(|combo| combo.iter().map(|item| item.0).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(combos, vec![vec![1, 2, 3], vec![1, 2, 4], vec![1, 3, 4], vec![2, 3, 4]]);
+    }
+
+    #[test]
+    fn combination_of_nothing() {
+        let mut combo_iter = Combination::try_new(&[1, 2, 3], 0).unwrap();
+        assert_eq!(combo_iter.next(), Some(vec![]));
+        assert_eq!(combo_iter.next(), None);
+        combo_iter.reset();
+        assert_eq!(combo_iter.count(), 1);
+        assert_eq!(Combination::new(&Vec::<i32>::new(), 0).count(), 1);
+        assert_eq!(
+            CombinationRef::new(&[1, 2, 3], 0).collect::<Vec<_>>(),
+            vec![Vec::<&i32>::new()]
+        );
+    }
+
+    #[test]
+    fn combination_of_everything() {
+        let combos = Combination::try_new(&[1, 2, 3], 3).unwrap().collect::<Vec<_>>();
+        assert_eq!(combos, vec![vec![1, 2, 3]]);
+        assert_eq!(CombinationRef::new(&[1], 1).collect::<Vec<_>>(), vec![vec![&1]]);
+    }
+
+    #[test]
+    fn combination_too_large() {
+        assert_eq!(
+            Combination::try_new(&[1, 2, 3], 4).err(),
+            Some(CombinationError::SizeTooLarge { size: 4, len: 3 })
+        );
+        let mut combo_iter = Combination::new(&[1, 2, 3], 4);
+        assert_eq!(combo_iter.next(), None);
+        combo_iter.reset();
+        assert_eq!(combo_iter.next(), None);
+        assert_eq!(CombinationRef::new(&Vec::<i32>::new(), 1).count(), 0);
+    }
+
+    #[test]
+    fn combination_from_both_ends() {
+        let items = (0..7).collect::<Vec<_>>();
+        for k in 0..=7 {
+            let forward = Combination::new(&items, k).collect::<Vec<_>>();
+            let mut backward = Combination::new(&items, k).rev().collect::<Vec<_>>();
+            backward.reverse();
+            assert_eq!(backward, forward);
+
+            // Alternating ends, the front picks up the first half and the back the second, with no overlap.
+            let mut combo_iter = Combination::new(&items, k);
+            let (mut front, mut back) = (vec![], vec![]);
+            while let Some(combo) = combo_iter.next() {
+                front.push(combo);
+                match combo_iter.next_back() {
+                    Some(combo) => back.push(combo),
+                    None => break,
                 }
-                (self.a[j], self.a[l]) = (self.a[l], self.a[j]);
-                let mut k = j + 1;
-                let mut l = self.n;
-                while k < l {
-                    (self.a[k], self.a[l]) = (self.a[l], self.a[k]);
-                    k += 1;
-                    l -= 1;
+            }
+            assert_eq!(combo_iter.next(), None);
+            assert_eq!(combo_iter.next_back(), None);
+            assert_eq!(front.len() + back.len(), forward.len());
+            back.reverse();
+            front.extend(back);
+            assert_eq!(front, forward);
+        }
+        assert_eq!(Combination::new(&items, 8).next_back(), None);
+        assert_eq!(
+            CombinationRef::new(&items, 2).rev().take(2).collect::<Vec<_>>(),
+            vec![vec![&5, &6], vec![&4, &6]]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut combo_iter = Combination::new(&["a", "b", "c", "d", "e"], 3);
+        combo_iter.nth(3);
+        combo_iter.next_back();
+        let saved = serde_json::to_string(&combo_iter).unwrap();
+        let restored = serde_json::from_str::<Combination<String>>(&saved).unwrap();
+        let expected = combo_iter
+            .map(|combo| combo.into_iter().map(String::from).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(expected.len(), 5);
+        assert_eq!(restored.collect::<Vec<_>>(), expected);
+
+        let mut permute_iter = Permutation::new(&[1, 2, 3, 4]);
+        permute_iter.nth(10);
+        let saved = serde_json::to_string(&permute_iter).unwrap();
+        let restored = serde_json::from_str::<Permutation<i32>>(&saved).unwrap();
+        assert_eq!(restored.current_index(), 11);
+        assert_eq!(restored.collect::<Vec<_>>(), permute_iter.collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn counting() {
+        assert_eq!(factorial(21), Some(51_090_942_171_709_440_000));
+        assert_eq!(factorial(34), Some(295_232_799_039_604_140_847_618_609_643_520_000_000));
+        assert_eq!(factorial(35), None);
+        assert_eq!(binomial(52, 5), Some(2_598_960));
+        assert_eq!(binomial(52, 47), binomial(52, 5));
+        assert_eq!(binomial(0, 0), Some(1));
+        assert_eq!(binomial(7, 0), Some(1));
+        assert_eq!(binomial(7, 7), Some(1));
+        for k in 0..=7 {
+            assert_eq!(
+                binomial(7, k),
+                Some(Combination::new(&[0; 7], k as usize).count() as u128)
+            );
+        }
+        // Close to the limit, but still fits; multiplying before dividing would overflow along the way.
+        let big = binomial(130, 65).unwrap();
+        assert_eq!(
+            Some(big),
+            binomial(129, 64).unwrap().checked_add(binomial(129, 65).unwrap())
+        );
+        assert_eq!(binomial(140, 70), None);
+    }
+
+    #[test]
+    fn combination_len() {
+        let mut combo_iter = Combination::new(&[1, 2, 3, 4, 5], 3);
+        assert_eq!(combo_iter.len(), 10);
+        for remaining in (0..10).rev() {
+            assert!(combo_iter.next().is_some());
+            assert_eq!(combo_iter.len(), remaining);
+            assert_eq!(combo_iter.size_hint(), (remaining, Some(remaining)));
+        }
+        assert_eq!(combo_iter.next(), None);
+        assert_eq!(combo_iter.len(), 0);
+        combo_iter.reset();
+        assert_eq!(combo_iter.len(), 10);
+
+        let items = (0..7).collect::<Vec<_>>();
+        for k in 0..=8 {
+            let mut combo_iter = CombinationRef::new(&items, k);
+            let mut expected = CombinationRef::new(&items, k).count();
+            assert_eq!(combo_iter.len(), expected);
+            while expected > 0 {
+                let produced = if expected.is_multiple_of(2) {
+                    combo_iter.next()
+                } else {
+                    combo_iter.next_back()
+                };
+                assert!(produced.is_some());
+                expected -= 1;
+                assert_eq!(combo_iter.len(), expected);
+            }
+            assert_eq!(combo_iter.next(), None);
+            assert_eq!(combo_iter.next_back(), None);
+        }
+
+        let huge = CombinationRef::new(&[0_u8; 200], 100);
+        assert_eq!(huge.size_hint(), (usize::MAX, None));
+        assert_eq!(huge.len(), usize::MAX);
+    }
+
+    #[test]
+    fn combination_split_matches_combination() {
+        let positions = (0..6).collect::<Vec<_>>();
+        for k in 0..=7 {
+            let mut splits = CombinationSplit::new(6, k);
+            let mut combos = Combination::new(&positions, k);
+            let capacity = (splits.chosen.capacity(), splits.rest.capacity());
+            let mut count = 0;
+            while let Some((chosen, rest)) = splits.next_split() {
+                assert_eq!(Some(chosen.to_vec()), combos.next());
+                assert_eq!(chosen.len() + rest.len(), 6);
+                assert!(rest.windows(2).all(|pair| pair[0] < pair[1]));
+                assert!(rest.iter().all(|idx| !chosen.contains(idx)));
+                count += 1;
+            }
+            assert_eq!(combos.next(), None);
+            assert_eq!(Some(count), binomial(6, k as u32).map(|total| total as usize));
+            // The buffers were big enough from the start.
+            assert_eq!((splits.chosen.capacity(), splits.rest.capacity()), capacity);
+
+            splits.reset();
+            assert_eq!(splits.next_split().is_some(), k <= 6);
+        }
+    }
+
+    #[test]
+    fn permutation_apply_to() {
+        let data = ['a', 'b', 'c'];
+        let orders = Permutation::new(&[0, 1, 2]).apply_to(&data).collect::<Vec<_>>();
+        assert_eq!(
+            orders,
+            [
+                [&'a', &'b', &'c'],
+                [&'a', &'c', &'b'],
+                [&'b', &'a', &'c'],
+                [&'b', &'c', &'a'],
+                [&'c', &'a', &'b'],
+                [&'c', &'b', &'a'],
+            ]
+        );
+        assert_eq!(orders, PermutationRef::new(&data).collect::<Vec<_>>());
+
+        // Positions can be a subset, or in any order; the result follows the permutations of the positions.
+        let orders = Permutation::new(&[2, 0]).apply_to(&data).collect::<Vec<_>>();
+        assert_eq!(orders, [[&'c', &'a'], [&'a', &'c']]);
+    }
+
+    #[test]
+    fn nth_permutation_matches_iteration() {
+        let items = ['a', 'b', 'c', 'd', 'e'];
+        for (index, expected) in Permutation::new(&items).enumerate() {
+            assert_eq!(nth_permutation(&items, index as u128), Some(expected));
+        }
+        assert_eq!(nth_permutation(&items, 120), None);
+        assert_eq!(nth_permutation(&Vec::<char>::new(), 0), Some(vec![]));
+
+        // Indices are fine for item counts whose permutations overflow a u128.
+        let items = (0..40).collect::<Vec<_>>();
+        let last = nth_permutation(&items, u128::MAX).unwrap();
+        // Only the last 35 places can hold a nonzero digit, and u128::MAX is just over 34!.
+        assert_eq!(last[..6], [0, 1, 2, 3, 4, 6]);
+        assert_eq!(nth_permutation(&items, 1).unwrap()[38..], [39, 38]);
+    }
+
+    #[test]
+    fn nth_combination_matches_iteration() {
+        let items = (1..=7).collect::<Vec<_>>();
+        for k in 0..=7 {
+            let mut count = 0;
+            for (index, expected) in Combination::new(&items, k).enumerate() {
+                assert_eq!(nth_combination(&items, k, index as u128), Some(expected));
+                count += 1;
+            }
+            assert_eq!(nth_combination(&items, k, count), None);
+        }
+        assert_eq!(nth_combination(&items, 8, 0), None);
+
+        let items = (0..200).collect::<Vec<_>>();
+        assert_eq!(nth_combination(&items, 100, u128::MAX).unwrap().len(), 100);
+    }
+
+    #[test]
+    fn permutation_ref_never_clones() {
+        let items = [NoClone(1), NoClone(2), NoClone(3)];
+        let mut perm_iter = PermutationRef::new(&items);
+        assert_eq!(perm_iter.next(), Some(vec![&items[0], &items[1], &items[2]]));
+        perm_iter.reset();
+        assert_eq!(perm_iter.count(), 6);
+    }
+
+    #[test]
+    fn ref_matches_owned() {
+        let items = [5, 3, 8, 1, 9];
+        for size in 1..items.len() {
+            let owned = Combination::new(&items, size).collect::<Vec<_>>();
+            let borrowed = CombinationRef::new(&items, size)
+                .map(|combo| combo.into_iter().copied().collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            assert_eq!(owned, borrowed);
+        }
+        let owned = Permutation::new(&items).collect::<Vec<_>>();
+        let borrowed = PermutationRef::new(&items)
+            .map(|perm| perm.into_iter().copied().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(owned, borrowed);
+    }
+
+    #[test]
+    fn product_unrank_inverts_rank() {
+        let dims = [3, 1, 4, 2];
+        let total = dims.iter().product::<usize>() as u128;
+        for m in 0..total {
+            assert_eq!(product_rank(&product_unrank(m, &dims), &dims), m);
+        }
+    }
+
+    #[test]
+    fn product_unrank_matches_iteration() {
+        let dims = [2, 3, 2];
+        let mut m = 0;
+        for a in 0..dims[0] {
+            for b in 0..dims[1] {
+                for c in 0..dims[2] {
+                    assert_eq!(product_unrank(m, &dims), vec![a, b, c]);
+                    m += 1;
                 }
             }
-            result
         }
     }
+
+    #[test]
+    #[should_panic(expected = "beyond the end of the product space")]
+    fn product_unrank_past_end() {
+        product_unrank(6, &[2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn product_rank_bad_index() {
+        product_rank(&[2, 0], &[2, 3]);
+    }
+
+    #[test]
+    fn prefix_filter_matches_filter() {
+        let items = (1..=8).collect::<Vec<_>>();
+        let within_budget = |combo: &[&i32]| combo.iter().copied().sum::<i32>() <= 12;
+        let mut pruned = Combination::with_prefix_filter(&items, 3, within_budget).collect::<Vec<_>>();
+        let mut filtered = Combination::new(&items, 3)
+            .filter(|combo| combo.iter().sum::<i32>() <= 12)
+            .collect::<Vec<_>>();
+        pruned.sort();
+        filtered.sort();
+        assert_eq!(pruned, filtered);
+    }
+
+    #[test]
+    fn prefix_filter_visits_fewer_states() {
+        let items = (1..=20).collect::<Vec<_>>();
+        let mut pruned_checks = 0;
+        let pruned = Combination::with_prefix_filter(&items, 4, |prefix: &[&i32]| {
+            pruned_checks += 1;
+            prefix.iter().copied().sum::<i32>() <= 12
+        })
+        .count();
+        let mut filtered_checks = 0;
+        let filtered = Combination::new(&items, 4)
+            .filter(|combo| {
+                filtered_checks += 1;
+                combo.iter().sum::<i32>() <= 12
+            })
+            .count();
+        assert_eq!(pruned, filtered);
+        assert_eq!(filtered_checks, 4845);
+        assert!(
+            pruned_checks < filtered_checks / 5,
+            "{pruned_checks} checks while pruning"
+        );
+    }
+
+    #[test]
+    fn permutation_prefix_filter_matches_filter() {
+        let items = [1, 2, 3, 4, 5];
+        let starts_low = |perm: &[&i32]| perm.len() < 2 || perm[0] < perm[1];
+        let pruned = Permutation::with_prefix_filter(&items, starts_low).collect::<Vec<_>>();
+        let filtered = Permutation::new(&items)
+            .filter(|perm| perm[0] < perm[1])
+            .collect::<Vec<_>>();
+        assert_eq!(pruned, filtered);
+    }
+
+    #[test]
+    fn prefix_filter_rejecting_everything() {
+        assert_eq!(
+            Combination::with_prefix_filter(&[1, 2, 3], 2, |_: &[&i32]| false).count(),
+            0
+        );
+        assert_eq!(
+            Permutation::with_prefix_filter(&[1, 2, 3], |_: &[&i32]| false).count(),
+            0
+        );
+        assert_eq!(
+            Combination::with_prefix_filter(&[1, 2, 3], 0, |_: &[&i32]| true).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn subset_masks_four_choose_two() {
+        assert_eq!(
+            subset_masks(4, 2).collect::<Vec<_>>(),
+            vec![0b0011, 0b0101, 0b0110, 0b1001, 0b1010, 0b1100]
+        );
+    }
+
+    #[test]
+    fn subset_masks_edges() {
+        assert_eq!(subset_masks(5, 0).collect::<Vec<_>>(), vec![0]);
+        assert_eq!(subset_masks(3, 4).count(), 0);
+        assert_eq!(subset_masks(3, 3).collect::<Vec<_>>(), vec![0b111]);
+        assert_eq!(subset_masks(64, 1).count(), 64);
+        assert_eq!(subset_masks(64, 63).last(), Some(u64::MAX - 1));
+    }
+
+    #[test]
+    fn permutation_count_fits() {
+        let items = (0..20).collect::<Vec<_>>();
+        let mut perm_iter = Permutation::new(&items);
+        assert_eq!(perm_iter.exact_count(), Some(2_432_902_008_176_640_000));
+        assert_eq!(
+            perm_iter.size_hint(),
+            (2_432_902_008_176_640_000, Some(2_432_902_008_176_640_000))
+        );
+        perm_iter.next();
+        assert_eq!(perm_iter.size_hint().1, Some(2_432_902_008_176_639_999));
+        assert_eq!(perm_iter.count(), 2_432_902_008_176_639_999);
+    }
+
+    #[test]
+    fn permutation_count_overflows_usize() {
+        let items = (0..25).collect::<Vec<_>>();
+        let perm_iter = PermutationRef::new(&items);
+        assert_eq!(perm_iter.exact_count(), Some(15_511_210_043_330_985_984_000_000));
+        assert_eq!(perm_iter.size_hint(), (usize::MAX, None));
+        assert_eq!(perm_iter.count(), usize::MAX);
+
+        let items = (0..35).collect::<Vec<_>>();
+        assert_eq!(Permutation::new(&items).exact_count(), None);
+    }
+
+    #[test]
+    fn permutation_count_runs_down() {
+        let mut perm_iter = Permutation::new(&[1, 2, 3]);
+        for left in (0..6).rev() {
+            perm_iter.next();
+            assert_eq!(perm_iter.size_hint(), (left, Some(left)));
+        }
+        assert_eq!(perm_iter.next(), None);
+        assert_eq!(perm_iter.exact_count(), Some(0));
+        perm_iter.reset();
+        assert_eq!(perm_iter.count(), 6);
+    }
+
+    #[test]
+    fn permutation_from_index() {
+        let items = [3, 1, 4, 1, 5];
+        for k in 0..=121 {
+            let resumed = Permutation::from_index(&items, k);
+            assert_eq!(resumed.current_index(), k.min(120));
+            assert_eq!(resumed.exact_count(), Some(120 - k.min(120)));
+            let expected = Permutation::new(&items).skip(k as usize).collect::<Vec<_>>();
+            assert_eq!(resumed.collect::<Vec<_>>(), expected);
+
+            let resumed = PermutationRef::from_index(&items, k).collect::<Vec<_>>();
+            let expected = PermutationRef::new(&items).skip(k as usize).collect::<Vec<_>>();
+            assert_eq!(resumed, expected);
+        }
+
+        let mut perm_iter = Permutation::new(&items);
+        for k in 0..120 {
+            assert_eq!(perm_iter.current_index(), k);
+            perm_iter.next();
+        }
+        assert_eq!(perm_iter.current_index(), 120);
+        perm_iter.reset();
+        assert_eq!(perm_iter.current_index(), 0);
+
+        // Far too many permutations to step through, but starting late is no trouble.
+        let items = (0..30).collect::<Vec<_>>();
+        let mut perm_iter = Permutation::from_index(&items, 265_252_859_812_191_058_636_308_479_999_999);
+        assert_eq!(perm_iter.next(), Some((0..30).rev().collect::<Vec<_>>()));
+        assert_eq!(perm_iter.next(), None);
+    }
+
+    #[test]
+    fn multiset_combination_matches_deduplicated() {
+        let items = [('a', 3), ('b', 0), ('c', 2), ('d', 1)];
+        let flat = items
+            .iter()
+            .flat_map(|&(item, count)| std::iter::repeat_n(item, count))
+            .collect::<Vec<_>>();
+        for k in 0..=7 {
+            let mut expected = Combination::new(&flat, k).collect::<Vec<_>>();
+            expected.sort();
+            expected.dedup();
+            let actual = MultisetCombination::new(&items, k).collect::<Vec<_>>();
+            assert_eq!(actual, expected, "choosing {k}");
+        }
+        assert_eq!(MultisetCombination::new(&Vec::<(u8, usize)>::new(), 0).count(), 1);
+        assert_eq!(MultisetCombination::new(&Vec::<(u8, usize)>::new(), 1).count(), 0);
+    }
+
+    #[test]
+    fn pairs_match_combinations() {
+        let items = (0..7).collect::<Vec<_>>();
+        let mut expected = Combination::new(&items, 2).collect::<Vec<_>>();
+        expected.sort();
+        let actual = pairs(&items).map(|(a, b)| vec![*a, *b]).collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+        assert_eq!(pairs(&Vec::<u8>::new()).count(), 0);
+    }
 }
@@ -0,0 +1,492 @@
+//! # Grid Utilities
+//!
+//! Many puzzles take place on a two-dimensional grid of cells. This crate collects the pieces that keep getting
+//! rewritten for them, starting with [Point], a location on such a grid, and [Direction], a way to step from one
+//! point to the next. [walk] and [walk_bounded] replay a whole list of such steps. [flood_fill] and
+//! [connected_components] pick out the regions of a map. [Grid] stores a value for every cell of a rectangular map,
+//! can list the neighbors of any cell, and can be rotated, mirrored, or transposed.
+//!
+//! The coordinate convention throughout is the one used when reading puzzle input: `row` grows downward (it's
+//! the line number), and `col` grows to the right (it's the character offset within the line).
+#![warn(missing_docs)]
+
+use ahash::AHashSet;
+
+/// A location on a two-dimensional grid.
+///
+/// Coordinates are signed, so that points just off the edge of a map (or deltas between two points) can be
+/// represented without any special handling.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Point {
+    /// The row (vertical position; increases downward)
+    pub row: i64,
+    /// The column (horizontal position; increases to the right)
+    pub col: i64,
+}
+
+impl Point {
+    /// Create a new point from its row and column.
+    ///
+    /// # Example
+    /// ```
+    /// use grid::Point;
+    ///
+    /// let pt = Point::new(3, 7);
+    /// assert_eq!(pt, Point { row: 3, col: 7 });
+    /// ```
+    pub fn new(row: i64, col: i64) -> Self {
+        Point { row, col }
+    }
+
+    /// The [Manhattan distance](https://en.wikipedia.org/wiki/Taxicab_geometry) between two points.
+    ///
+    /// This is the number of orthogonal steps needed to walk from one point to the other, which makes it the
+    /// usual A* heuristic for grids without diagonal movement.
+    ///
+    /// # Example
+    /// ```
+    /// use grid::Point;
+    ///
+    /// assert_eq!(Point::new(1, 1).manhattan(&Point::new(4, -3)), 7);
+    /// assert_eq!(Point::new(4, -3).manhattan(&Point::new(1, 1)), 7);
+    /// assert_eq!(Point::new(5, 5).manhattan(&Point::new(5, 5)), 0);
+    /// ```
+    pub fn manhattan(&self, other: &Point) -> i64 {
+        (self.row - other.row).abs() + (self.col - other.col).abs()
+    }
+
+    /// The [Chebyshev distance](https://en.wikipedia.org/wiki/Chebyshev_distance) between two points.
+    ///
+    /// This is the number of steps needed to walk from one point to the other when diagonal moves are allowed
+    /// (and cost the same as orthogonal ones); i.e., the number of moves a chess king would need.
+    ///
+    /// # Example
+    /// ```
+    /// use grid::Point;
+    ///
+    /// assert_eq!(Point::new(1, 1).chebyshev(&Point::new(4, -3)), 4);
+    /// assert_eq!(Point::new(4, -3).chebyshev(&Point::new(1, 1)), 4);
+    /// assert_eq!(Point::new(0, 0).chebyshev(&Point::new(-2, 2)), 2);
+    /// assert_eq!(Point::new(5, 5).chebyshev(&Point::new(5, 5)), 0);
+    /// ```
+    pub fn chebyshev(&self, other: &Point) -> i64 {
+        (self.row - other.row).abs().max((self.col - other.col).abs())
+    }
+
+    /// The point one step away in the given direction.
+    ///
+    /// # Example
+    /// ```
+    /// use grid::{Direction, Point};
+    ///
+    /// assert_eq!(Point::new(3, 7).step(Direction::Up), Point::new(2, 7));
+    /// assert_eq!(Point::new(3, 7).step(Direction::Left), Point::new(3, 6));
+    /// ```
+    pub fn step(&self, dir: Direction) -> Point {
+        let (drow, dcol) = dir.delta();
+        Point::new(self.row + drow, self.col + dcol)
+    }
+}
+
+/// One of the four orthogonal directions of travel on a grid.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Direction {
+    /// Toward lower rows
+    Up,
+    /// Toward higher rows
+    Down,
+    /// Toward lower columns
+    Left,
+    /// Toward higher columns
+    Right,
+}
+
+impl Direction {
+    /// The change in `(row, col)` from taking one step in this direction.
+    pub fn delta(self) -> (i64, i64) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+        }
+    }
+}
+
+/// The extent of a rectangular map: rows `0..height` and columns `0..width`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Bounds {
+    /// The number of rows
+    pub height: i64,
+    /// The number of columns
+    pub width: i64,
+}
+
+/// What [walk_bounded] does with a step that would leave the map.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum WrapMode {
+    /// The step is blocked by the edge of the map, and the walker stays where it is.
+    Clamp,
+    /// The map is a torus: leaving one edge brings the walker back in at the opposite edge.
+    Wrap,
+    /// The map's edges are ignored entirely.
+    Unbounded,
+}
+
+/// Follow a list of directions from `start`, one step per direction, on an endless grid.
+///
+/// The result holds every position visited, starting with `start` itself, so it is one longer than `dirs`.
+///
+/// # Example
+/// ```
+/// use grid::{walk, Direction, Point};
+///
+/// let path = walk(Point::new(0, 0), &[Direction::Right, Direction::Right, Direction::Up]);
+/// assert_eq!(path, vec![Point::new(0, 0), Point::new(0, 1), Point::new(0, 2), Point::new(-1, 2)]);
+/// ```
+pub fn walk(start: Point, dirs: &[Direction]) -> Vec<Point> {
+    walk_bounded(start, dirs, Bounds { height: 0, width: 0 }, WrapMode::Unbounded)
+}
+
+/// Follow a list of directions from `start`, one step per direction, on a map of size `bounds`.
+///
+/// The result holds every position visited, starting with `start` itself, so it is one longer than `dirs`. How
+/// steps off the edge of the map are handled depends on `wrap` (and `bounds` is ignored entirely for
+/// [WrapMode::Unbounded]). The start itself is taken as given, even if it lies outside the map. A map with no rows
+/// or no columns has nowhere to step to, so with [WrapMode::Clamp] or [WrapMode::Wrap] the walker never moves.
+///
+/// # Examples
+/// ```
+/// use grid::{walk_bounded, Bounds, Direction, Point, WrapMode};
+/// use Direction::*;
+///
+/// let bounds = Bounds { height: 3, width: 4 };
+/// let dirs = [Left, Left, Down, Up, Up, Up];
+/// let start = Point::new(1, 1);
+///
+/// let clamped = walk_bounded(start, &dirs, bounds, WrapMode::Clamp);
+/// let rows_and_cols = clamped.iter().map(|pt| (pt.row, pt.col)).collect::<Vec<_>>();
+/// assert_eq!(rows_and_cols, [(1, 1), (1, 0), (1, 0), (2, 0), (1, 0), (0, 0), (0, 0)]);
+///
+/// let wrapped = walk_bounded(start, &dirs, bounds, WrapMode::Wrap);
+/// let rows_and_cols = wrapped.iter().map(|pt| (pt.row, pt.col)).collect::<Vec<_>>();
+/// assert_eq!(rows_and_cols, [(1, 1), (1, 0), (1, 3), (2, 3), (1, 3), (0, 3), (2, 3)]);
+///
+/// let unbounded = walk_bounded(start, &dirs, bounds, WrapMode::Unbounded);
+/// let rows_and_cols = unbounded.iter().map(|pt| (pt.row, pt.col)).collect::<Vec<_>>();
+/// assert_eq!(rows_and_cols, [(1, 1), (1, 0), (1, -1), (2, -1), (1, -1), (0, -1), (-1, -1)]);
+///
+/// let empty = Bounds { height: 0, width: 4 };
+/// assert_eq!(walk_bounded(start, &dirs, empty, WrapMode::Wrap), vec![start; 7]);
+/// ```
+pub fn walk_bounded(start: Point, dirs: &[Direction], bounds: Bounds, wrap: WrapMode) -> Vec<Point> {
+    let mut path = Vec::with_capacity(dirs.len() + 1);
+    path.push(start);
+    let mut current = start;
+    for &dir in dirs {
+        let next = current.step(dir);
+        current = match wrap {
+            WrapMode::Unbounded => next,
+            WrapMode::Wrap if bounds.height <= 0 || bounds.width <= 0 => current,
+            WrapMode::Wrap => Point::new(next.row.rem_euclid(bounds.height), next.col.rem_euclid(bounds.width)),
+            WrapMode::Clamp => {
+                if in_bounds(bounds, next) {
+                    next
+                } else {
+                    current
+                }
+            }
+        };
+        path.push(current);
+    }
+    path
+}
+
+/// Every point reachable from `start` by orthogonal steps through passable points, staying within `bounds`.
+///
+/// The result includes `start` itself, unless `start` is out of bounds or not passable (in which case the result
+/// is empty).
+///
+/// # Example
+/// ```
+/// use grid::{flood_fill, Bounds, Point};
+///
+/// let map = ["..#..", "..#..", "###.."];
+/// let bounds = Bounds { height: 3, width: 5 };
+/// let open = |pt: Point| map[pt.row as usize].as_bytes()[pt.col as usize] == b'.';
+///
+/// let region = flood_fill(bounds, Point::new(0, 0), open);
+/// assert_eq!(region.len(), 4);
+/// assert!(region.contains(&Point::new(1, 1)));
+/// assert!(!region.contains(&Point::new(0, 3)));
+///
+/// assert_eq!(flood_fill(bounds, Point::new(0, 4), open).len(), 6);
+/// assert!(flood_fill(bounds, Point::new(0, 2), open).is_empty());
+/// ```
+pub fn flood_fill(bounds: Bounds, start: Point, passable: impl Fn(Point) -> bool) -> AHashSet<Point> {
+    let mut region = AHashSet::new();
+    if !in_bounds(bounds, start) || !passable(start) {
+        return region;
+    }
+    region.insert(start);
+    let mut pending = vec![start];
+    while let Some(here) = pending.pop() {
+        for dir in [Direction::Up, Direction::Down, Direction::Left, Direction::Right] {
+            let next = here.step(dir);
+            if in_bounds(bounds, next) && !region.contains(&next) && passable(next) {
+                region.insert(next);
+                pending.push(next);
+            }
+        }
+    }
+    region
+}
+
+/// All the separate regions of passable points within `bounds`, where each region is a set of points connected
+/// by orthogonal steps.
+///
+/// Regions are ordered by their first point in reading order (top to bottom, then left to right).
+///
+/// # Example
+/// ```
+/// use grid::{connected_components, Bounds, Point};
+///
+/// let map = ["..#..", "..#..", "###.."];
+/// let bounds = Bounds { height: 3, width: 5 };
+/// let open = |pt: Point| map[pt.row as usize].as_bytes()[pt.col as usize] == b'.';
+///
+/// let regions = connected_components(bounds, open);
+/// assert_eq!(regions.iter().map(|region| region.len()).collect::<Vec<_>>(), [4, 6]);
+/// assert!(regions[0].contains(&Point::new(0, 0)));
+/// assert!(regions[1].contains(&Point::new(2, 4)));
+/// ```
+pub fn connected_components(bounds: Bounds, passable: impl Fn(Point) -> bool) -> Vec<AHashSet<Point>> {
+    let mut regions = vec![];
+    let mut claimed = AHashSet::new();
+    for row in 0..bounds.height {
+        for col in 0..bounds.width {
+            let pt = Point::new(row, col);
+            if !claimed.contains(&pt) && passable(pt) {
+                let region = flood_fill(bounds, pt, &passable);
+                claimed.extend(region.iter().copied());
+                regions.push(region);
+            }
+        }
+    }
+    regions
+}
+
+/// A rectangular map holding one value of type `T` in each cell.
+///
+/// The cells are stored row by row, so a lookup is just an index calculation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    bounds: Bounds,
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    /// Create a grid from its rows, given top to bottom.
+    ///
+    /// # Panics
+    /// Panics if the rows aren't all the same length.
+    ///
+    /// # Example
+    /// ```
+    /// use grid::{Bounds, Grid, Point};
+    ///
+    /// let grid = Grid::from_rows(["abc", "def"].map(|line| line.chars()));
+    /// assert_eq!(grid.bounds(), Bounds { height: 2, width: 3 });
+    /// assert_eq!(grid.get(Point::new(1, 0)), Some(&'d'));
+    /// ```
+    pub fn from_rows<R>(rows: impl IntoIterator<Item = R>) -> Self
+    where
+        R: IntoIterator<Item = T>,
+    {
+        let mut cells = vec![];
+        let mut height = 0;
+        let mut width = None;
+        for row in rows {
+            let before = cells.len();
+            cells.extend(row);
+            let row_width = cells.len() - before;
+            assert_eq!(
+                *width.get_or_insert(row_width),
+                row_width,
+                "row {height} is the wrong length"
+            );
+            height += 1;
+        }
+        let width = i64::try_from(width.unwrap_or(0)).expect("grid width should fit in an i64");
+        Grid {
+            bounds: Bounds { height, width },
+            cells,
+        }
+    }
+
+    /// The size of the grid.
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    /// The value stored at `pt`, or `None` if `pt` is off the grid.
+    pub fn get(&self, pt: Point) -> Option<&T> {
+        in_bounds(self.bounds, pt).then(|| &self.cells[(pt.row * self.bounds.width + pt.col) as usize])
+    }
+
+    /// The cells next to `pt` that are on the grid, along with their values.
+    ///
+    /// These are the orthogonal neighbors (in the order up, down, left, right), followed by the diagonal ones (in
+    /// the order up-left, up-right, down-left, down-right) if `diagonal` is set. `pt` itself needn't be on the
+    /// grid.
+    ///
+    /// # Example
+    /// ```
+    /// use grid::{Grid, Point};
+    ///
+    /// let grid = Grid::from_rows([[1, 2, 3], [4, 5, 6], [7, 8, 9]]);
+    /// let values = |pt: Point, diagonal: bool| grid.neighbors(pt, diagonal).map(|(_, &v)| v).collect::<Vec<_>>();
+    ///
+    /// // The center has all its neighbors.
+    /// assert_eq!(values(Point::new(1, 1), false), [2, 8, 4, 6]);
+    /// assert_eq!(values(Point::new(1, 1), true), [2, 8, 4, 6, 1, 3, 7, 9]);
+    /// // A corner has only two orthogonal neighbors, and one diagonal one.
+    /// assert_eq!(values(Point::new(0, 0), false), [4, 2]);
+    /// assert_eq!(values(Point::new(0, 0), true), [4, 2, 5]);
+    /// assert_eq!(values(Point::new(2, 2), true), [6, 8, 5]);
+    /// // An edge cell is missing a side.
+    /// assert_eq!(values(Point::new(1, 2), false), [3, 9, 5]);
+    /// assert_eq!(values(Point::new(1, 2), true), [3, 9, 5, 2, 8]);
+    /// // Points come along with the values.
+    /// let first = grid.neighbors(Point::new(2, 1), false).next();
+    /// assert_eq!(first, Some((Point::new(1, 1), &5)));
+    /// // Just off the grid, only the cells on the grid are reported.
+    /// assert_eq!(values(Point::new(-1, 0), true), [1, 2]);
+    /// ```
+    pub fn neighbors(&self, pt: Point, diagonal: bool) -> impl Iterator<Item = (Point, &T)> {
+        const ORTHOGONAL: [(i64, i64); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+        const DIAGONAL: [(i64, i64); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+        let diagonals = if diagonal { &DIAGONAL[..] } else { &[] };
+        ORTHOGONAL
+            .iter()
+            .chain(diagonals)
+            .map(move |&(drow, dcol)| Point::new(pt.row + drow, pt.col + dcol))
+            .filter_map(|neighbor| self.get(neighbor).map(|value| (neighbor, value)))
+    }
+
+    /// A copy of the grid turned a quarter turn clockwise. The left column becomes the top row.
+    ///
+    /// # Example
+    /// ```
+    /// use grid::{Bounds, Grid};
+    ///
+    /// let grid = Grid::from_rows(["abc", "def"].map(|line| line.chars()));
+    /// let turned = grid.rotated_cw();
+    /// assert_eq!(turned, Grid::from_rows(["da", "eb", "fc"].map(|line| line.chars())));
+    /// assert_eq!(turned.bounds(), Bounds { height: 3, width: 2 });
+    /// // Four turns make a full circle.
+    /// assert_eq!(turned.rotated_cw().rotated_cw().rotated_cw(), grid);
+    /// ```
+    pub fn rotated_cw(&self) -> Grid<T>
+    where
+        T: Clone,
+    {
+        let height = self.bounds.height;
+        self.remapped(self.bounds.width, height, |pt| Point::new(height - 1 - pt.col, pt.row))
+    }
+
+    /// A copy of the grid turned a quarter turn counterclockwise. The right column becomes the top row.
+    ///
+    /// # Example
+    /// ```
+    /// use grid::Grid;
+    ///
+    /// let grid = Grid::from_rows(["abc", "def"].map(|line| line.chars()));
+    /// assert_eq!(grid.rotated_ccw(), Grid::from_rows(["cf", "be", "ad"].map(|line| line.chars())));
+    /// assert_eq!(grid.rotated_ccw().rotated_cw(), grid);
+    /// ```
+    pub fn rotated_ccw(&self) -> Grid<T>
+    where
+        T: Clone,
+    {
+        let width = self.bounds.width;
+        self.remapped(width, self.bounds.height, |pt| Point::new(pt.col, width - 1 - pt.row))
+    }
+
+    /// A mirror image of the grid, with left and right swapped.
+    ///
+    /// # Example
+    /// ```
+    /// use grid::Grid;
+    ///
+    /// let grid = Grid::from_rows(["abc", "def"].map(|line| line.chars()));
+    /// assert_eq!(grid.flipped_horizontal(), Grid::from_rows(["cba", "fed"].map(|line| line.chars())));
+    /// ```
+    pub fn flipped_horizontal(&self) -> Grid<T>
+    where
+        T: Clone,
+    {
+        let width = self.bounds.width;
+        self.remapped(self.bounds.height, width, |pt| Point::new(pt.row, width - 1 - pt.col))
+    }
+
+    /// A mirror image of the grid, with top and bottom swapped.
+    ///
+    /// # Example
+    /// ```
+    /// use grid::Grid;
+    ///
+    /// let grid = Grid::from_rows(["abc", "def"].map(|line| line.chars()));
+    /// assert_eq!(grid.flipped_vertical(), Grid::from_rows(["def", "abc"].map(|line| line.chars())));
+    /// // Flipping both ways is the same as turning halfway around.
+    /// assert_eq!(grid.flipped_vertical().flipped_horizontal(), grid.rotated_cw().rotated_cw());
+    /// ```
+    pub fn flipped_vertical(&self) -> Grid<T>
+    where
+        T: Clone,
+    {
+        let height = self.bounds.height;
+        self.remapped(height, self.bounds.width, |pt| Point::new(height - 1 - pt.row, pt.col))
+    }
+
+    /// A copy of the grid mirrored across its main diagonal, so that rows become columns.
+    ///
+    /// # Example
+    /// ```
+    /// use grid::{Grid, Point};
+    ///
+    /// let grid = Grid::from_rows(["abc", "def"].map(|line| line.chars()));
+    /// let transposed = grid.transposed();
+    /// assert_eq!(transposed, Grid::from_rows(["ad", "be", "cf"].map(|line| line.chars())));
+    /// assert_eq!(transposed.get(Point::new(2, 1)), grid.get(Point::new(1, 2)));
+    /// ```
+    pub fn transposed(&self) -> Grid<T>
+    where
+        T: Clone,
+    {
+        self.remapped(self.bounds.width, self.bounds.height, |pt| Point::new(pt.col, pt.row))
+    }
+
+    /// A new `height` by `width` grid, where each cell is copied from the cell `source` maps it to in this one.
+    fn remapped(&self, height: i64, width: i64, source: impl Fn(Point) -> Point) -> Grid<T>
+    where
+        T: Clone,
+    {
+        let cells = (0..height)
+            .flat_map(|row| (0..width).map(move |col| Point::new(row, col)))
+            .map(|pt| {
+                self.get(source(pt))
+                    .expect("transformed points should stay on the grid")
+                    .clone()
+            })
+            .collect();
+        Grid {
+            bounds: Bounds { height, width },
+            cells,
+        }
+    }
+}
+
+fn in_bounds(bounds: Bounds, pt: Point) -> bool {
+    (0..bounds.height).contains(&pt.row) && (0..bounds.width).contains(&pt.col)
+}
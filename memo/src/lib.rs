@@ -0,0 +1,244 @@
+//! # Memoization
+//!
+//! A lot of puzzles boil down to a recursive definition that revisits the same subproblems over and over. The
+//! usual fix is a cache keyed on the arguments of the recursion. This crate holds that cache plumbing so the
+//! solutions don't each have to re-create it.
+//!
+//! [Memo] owns its table. [MemoRef] is the borrowing counterpart: it works against a [MemoTable] the caller owns,
+//! which is handy when the cache has to outlive (or be inspected after) the call that fills it.
+//!
+//! In both, the computation closure is handed the memo itself, so it can recurse through the cache.
+#![warn(missing_docs)]
+
+use ahash::AHashMap;
+use std::hash::Hash;
+
+/// The table behind a memo: the values computed so far, by key.
+///
+/// # Example
+/// ```
+/// use memo::MemoTable;
+///
+/// let mut table = MemoTable::new();
+/// table.insert("answer", 42);
+/// assert_eq!(table.get(&"answer"), Some(&42));
+/// assert_eq!(table.get(&"question"), None);
+/// assert_eq!(table.len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MemoTable<K, V>(AHashMap<K, V>);
+
+impl<K, V> Default for MemoTable<K, V> {
+    fn default() -> Self {
+        MemoTable(AHashMap::new())
+    }
+}
+
+impl<K, V> MemoTable<K, V>
+where
+    K: Hash + Eq,
+{
+    /// Create an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The value cached for `key`, if there is one.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.0.get(key)
+    }
+
+    /// Cache `value` for `key`, returning whatever was cached for it before.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.0.insert(key, value)
+    }
+
+    /// The number of values in the table.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// The lookup shared by [Memo] and [MemoRef]: `table` finds the memo's table, and `f` (handed the memo, so it can
+/// recurse) computes a missing value.
+fn get_or_compute<M, K, V>(
+    memo: &mut M,
+    table: fn(&mut M) -> &mut MemoTable<K, V>,
+    key: K,
+    f: impl FnOnce(&mut M) -> V,
+) -> V
+where
+    K: Hash + Eq,
+    V: Clone,
+{
+    if let Some(value) = table(memo).get(&key) {
+        return value.clone();
+    }
+    let value = f(memo);
+    table(memo).insert(key, value.clone());
+    value
+}
+
+/// A cache of previously computed values, owning its table.
+///
+/// # Example
+/// ```
+/// use memo::Memo;
+///
+/// fn fib(memo: &mut Memo<u64, u64>, n: u64) -> u64 {
+///     memo.get_or_compute(n, |memo| if n < 2 { n } else { fib(memo, n - 1) + fib(memo, n - 2) })
+/// }
+///
+/// let mut memo = Memo::new();
+/// assert_eq!(fib(&mut memo, 90), 2880067194370816120);
+/// assert_eq!(memo.len(), 91);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Memo<K, V> {
+    table: MemoTable<K, V>,
+}
+
+impl<K, V> Default for Memo<K, V> {
+    fn default() -> Self {
+        Memo {
+            table: MemoTable::default(),
+        }
+    }
+}
+
+impl<K, V> Memo<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    /// Create an empty memo.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the value cached for `key`, or, if there isn't one, run `f` to compute it, cache it, and return
+    /// it.
+    ///
+    /// `f` receives the memo so that it can make recursive calls that use (and fill) the same cache.
+    pub fn get_or_compute<F>(&mut self, key: K, f: F) -> V
+    where
+        F: FnOnce(&mut Self) -> V,
+    {
+        get_or_compute(self, |memo| &mut memo.table, key, f)
+    }
+
+    /// The number of values in the cache.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns `true` if nothing has been cached yet.
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// Discard the memo, returning its table.
+    pub fn into_inner(self) -> MemoTable<K, V> {
+        self.table
+    }
+}
+
+/// A cache of previously computed values, working against a table owned by the caller.
+///
+/// # Example
+/// ```
+/// use memo::{MemoRef, MemoTable};
+///
+/// fn fib(memo: &mut MemoRef<u64, u64>, n: u64) -> u64 {
+///     memo.get_or_compute(n, |memo| if n < 2 { n } else { fib(memo, n - 1) + fib(memo, n - 2) })
+/// }
+///
+/// let mut table = MemoTable::new();
+/// assert_eq!(fib(&mut MemoRef::new(&mut table), 20), 6765);
+/// assert_eq!(table.get(&10), Some(&55));
+/// ```
+#[derive(Debug)]
+pub struct MemoRef<'a, K, V> {
+    table: &'a mut MemoTable<K, V>,
+}
+
+impl<'a, K, V> MemoRef<'a, K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    /// Create a memo that reads from and adds to `table`.
+    pub fn new(table: &'a mut MemoTable<K, V>) -> Self {
+        MemoRef { table }
+    }
+
+    /// Return the value cached for `key`, or, if there isn't one, run `f` to compute it, cache it, and return
+    /// it.
+    ///
+    /// `f` receives the memo so that it can make recursive calls that use (and fill) the same cache.
+    pub fn get_or_compute<F>(&mut self, key: K, f: F) -> V
+    where
+        F: FnOnce(&mut Self) -> V,
+    {
+        get_or_compute(self, |memo| &mut *memo.table, key, f)
+    }
+
+    /// The number of values in the cache.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns `true` if nothing has been cached yet.
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn fib(memo: &mut Memo<u32, u64>, n: u32, calls: &Cell<usize>) -> u64 {
+        memo.get_or_compute(n, |memo| {
+            calls.set(calls.get() + 1);
+            if n < 2 {
+                u64::from(n)
+            } else {
+                fib(memo, n - 1, calls) + fib(memo, n - 2, calls)
+            }
+        })
+    }
+
+    #[test]
+    fn fibonacci() {
+        let mut memo = Memo::new();
+        let calls = Cell::new(0);
+        assert_eq!(fib(&mut memo, 50, &calls), 12_586_269_025);
+        // Each value is computed exactly once.
+        assert_eq!(calls.get(), 51);
+        assert_eq!(memo.len(), 51);
+
+        // And asking again is served entirely from the cache.
+        assert_eq!(fib(&mut memo, 50, &calls), 12_586_269_025);
+        assert_eq!(calls.get(), 51);
+    }
+
+    #[test]
+    fn borrowed_table_keeps_values() {
+        let mut table = MemoTable::new();
+        table.insert(3, 100);
+        let mut memo = MemoRef::new(&mut table);
+        assert!(!memo.is_empty());
+        // A value already in the table is returned without computing.
+        assert_eq!(memo.get_or_compute(3, |_| unreachable!()), 100);
+        assert_eq!(memo.get_or_compute(4, |_| 7), 7);
+        assert_eq!(memo.len(), 2);
+        assert_eq!(table.get(&4), Some(&7));
+    }
+}
@@ -0,0 +1,355 @@
+//! # Range Utilities
+//!
+//! Several puzzles deal in large spans of integers that are far too big to enumerate one value at a time
+//! (seed ranges, sensor coverage, scan windows). This crate collects the half-open interval operations those
+//! puzzles keep needing, built on the standard library's [Range].
+//!
+//! The free functions [intersect] and [simplify] work on individual ranges and loose lists of them; [RangeSet]
+//! keeps a normalized collection and supplies whole-set operations. [PiecewiseShift] moves values (or whole
+//! ranges of them) by different amounts depending on where they fall.
+#![warn(missing_docs)]
+
+use std::ops::{Add, Range};
+
+/// The overlap of two ranges, or `None` if they don't overlap.
+///
+/// # Example
+/// ```
+/// use ranges::intersect;
+///
+/// assert_eq!(intersect(&(0..10), &(5..20)), Some(5..10));
+/// assert_eq!(intersect(&(0..5), &(5..10)), None);
+/// ```
+pub fn intersect<T>(left: &Range<T>, right: &Range<T>) -> Option<Range<T>>
+where
+    T: Ord + Copy,
+{
+    let start = left.start.max(right.start);
+    let end = left.end.min(right.end);
+    if start < end {
+        Some(start..end)
+    } else {
+        None
+    }
+}
+
+/// Sort a list of ranges and merge any that overlap or abut.
+///
+/// # Example
+/// ```
+/// use ranges::simplify;
+///
+/// assert_eq!(simplify(vec![8..10, 0..3, 2..5, 5..6]), vec![0..6, 8..10]);
+/// ```
+pub fn simplify<T>(mut items: Vec<Range<T>>) -> Vec<Range<T>>
+where
+    T: Ord + Copy,
+{
+    items.sort_by_key(|item| item.start);
+    let mut output: Vec<Range<T>> = vec![];
+    'outer: for item in items {
+        for outs in output.iter_mut() {
+            if item.start <= outs.end {
+                if item.end > outs.end {
+                    outs.end = item.end;
+                }
+                continue 'outer;
+            }
+        }
+        output.push(item)
+    }
+    output
+}
+
+/// A set of values, stored as a sorted list of disjoint, non-adjacent, non-empty ranges.
+///
+/// # Example
+/// ```
+/// use ranges::RangeSet;
+///
+/// let set = RangeSet::from_iter([5..8, 0..2, 1..3, 9..9]);
+/// assert_eq!(set.ranges(), &[0..3, 5..8]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct RangeSet<T> {
+    ranges: Vec<Range<T>>,
+}
+
+impl<T> FromIterator<Range<T>> for RangeSet<T>
+where
+    T: Ord + Copy,
+{
+    fn from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> Self {
+        RangeSet {
+            ranges: simplify(iter.into_iter().filter(|rng| rng.start < rng.end).collect()),
+        }
+    }
+}
+
+impl<T> From<Range<T>> for RangeSet<T>
+where
+    T: Ord + Copy,
+{
+    fn from(rng: Range<T>) -> Self {
+        RangeSet::from_iter(std::iter::once(rng))
+    }
+}
+
+impl<T> RangeSet<T>
+where
+    T: Ord + Copy,
+{
+    /// The ranges making up this set, in ascending order.
+    pub fn ranges(&self) -> &[Range<T>] {
+        &self.ranges
+    }
+
+    /// Whether the set contains no values at all.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The values present in both `self` and `other`.
+    ///
+    /// # Example
+    /// ```
+    /// use ranges::RangeSet;
+    ///
+    /// let left = RangeSet::from_iter([0..10, 20..30]);
+    /// let right = RangeSet::from(5..25);
+    /// assert_eq!(left.intersect(&right).ranges(), &[5..10, 20..25]);
+    /// ```
+    pub fn intersect(&self, other: &RangeSet<T>) -> RangeSet<T> {
+        let mut result = vec![];
+        let mut left = self.ranges.iter().peekable();
+        let mut right = other.ranges.iter().peekable();
+        while let (Some(&l), Some(&r)) = (left.peek(), right.peek()) {
+            if let Some(overlap) = intersect(l, r) {
+                result.push(overlap);
+            }
+            // Whichever range ends first can't overlap anything further along in the other set.
+            if l.end <= r.end {
+                left.next();
+            } else {
+                right.next();
+            }
+        }
+        RangeSet { ranges: result }
+    }
+
+    /// The values present in every one of `sets`. The intersection of no sets at all is empty.
+    ///
+    /// # Example
+    /// ```
+    /// use ranges::RangeSet;
+    ///
+    /// let sets = [
+    ///     RangeSet::from_iter([0..10, 20..30]),
+    ///     RangeSet::from(5..25),
+    ///     RangeSet::from_iter([0..6, 24..40]),
+    /// ];
+    /// assert_eq!(RangeSet::intersect_all(&sets).ranges(), &[5..6, 24..25]);
+    /// ```
+    pub fn intersect_all(sets: &[RangeSet<T>]) -> RangeSet<T> {
+        match sets.split_first() {
+            None => RangeSet { ranges: vec![] },
+            Some((first, rest)) => rest.iter().fold(first.clone(), |acc, set| acc.intersect(set)),
+        }
+    }
+
+    /// The values in `self` that are not in `other`.
+    ///
+    /// # Example
+    /// ```
+    /// use ranges::RangeSet;
+    ///
+    /// let whole = RangeSet::from(0..10);
+    /// let hole = RangeSet::from(3..5);
+    /// assert_eq!(whole.difference(&hole).ranges(), &[0..3, 5..10]);
+    /// ```
+    pub fn difference(&self, other: &RangeSet<T>) -> RangeSet<T> {
+        let mut result = vec![];
+        let mut holes = other.ranges.iter().peekable();
+        for rng in self.ranges.iter() {
+            let mut start = rng.start;
+            // Holes wholly before this range can't affect it, or any later range either.
+            while holes.next_if(|hole| hole.end <= start).is_some() {}
+            for hole in holes.clone() {
+                if hole.start >= rng.end {
+                    break;
+                }
+                if hole.start > start {
+                    result.push(start..hole.start);
+                }
+                start = start.max(hole.end);
+            }
+            if start < rng.end {
+                result.push(start..rng.end);
+            }
+        }
+        RangeSet { ranges: result }
+    }
+}
+
+/// A remapping of values that shifts each one by an amount that depends on which interval it falls in.
+///
+/// Each mapping is a source range and the delta added to any value in it. Mappings are tried in the order they
+/// were given, and the first one containing a value wins; a value in none of them is left alone. (This is the
+/// shape of the seed-to-soil style maps in AoC 2023 day 5.)
+///
+/// # Example
+/// ```
+/// use ranges::PiecewiseShift;
+///
+/// let shift = PiecewiseShift::from_iter([(98..100, -48), (50..98, 2)]);
+/// assert_eq!(shift.apply(79), 81);
+/// assert_eq!(shift.apply(99), 51);
+/// assert_eq!(shift.apply(10), 10);
+/// assert_eq!(shift.apply_range(45..55), vec![45..50, 52..57]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PiecewiseShift<T> {
+    shifts: Vec<(Range<T>, T)>,
+}
+
+impl<T> Default for PiecewiseShift<T> {
+    fn default() -> Self {
+        PiecewiseShift { shifts: vec![] }
+    }
+}
+
+impl<T> FromIterator<(Range<T>, T)> for PiecewiseShift<T> {
+    fn from_iter<I: IntoIterator<Item = (Range<T>, T)>>(iter: I) -> Self {
+        PiecewiseShift {
+            shifts: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<T> PiecewiseShift<T>
+where
+    T: Ord + Copy + Add<Output = T>,
+{
+    /// Add a mapping, shifting values in `source` by `delta`. It applies only to values not already covered by
+    /// earlier mappings.
+    pub fn push(&mut self, source: Range<T>, delta: T) {
+        self.shifts.push((source, delta));
+    }
+
+    /// The mappings, in the order they're tried.
+    pub fn shifts(&self) -> &[(Range<T>, T)] {
+        &self.shifts
+    }
+
+    /// Where `point` ends up.
+    pub fn apply(&self, point: T) -> T {
+        self.shifts
+            .iter()
+            .find(|(source, _)| source.contains(&point))
+            .map_or(point, |&(_, delta)| point + delta)
+    }
+
+    /// Where all the values in `incoming` end up, as a sorted list of merged ranges.
+    ///
+    /// The range is split at the edges of the mappings it crosses, each piece is shifted by its own delta, and the
+    /// results are [simplify]'d.
+    pub fn apply_range(&self, incoming: Range<T>) -> Vec<Range<T>> {
+        let mut unshifted = vec![incoming];
+        let mut shifted = vec![];
+
+        'new_work: while let Some(work_item) = unshifted.pop() {
+            for (source, delta) in self.shifts.iter() {
+                if let Some(overlap) = intersect(&work_item, source) {
+                    shifted.push(overlap.start + *delta..overlap.end + *delta);
+                    if work_item.start < overlap.start {
+                        unshifted.push(work_item.start..overlap.start);
+                    }
+                    if work_item.end > overlap.end {
+                        unshifted.push(overlap.end..work_item.end);
+                    }
+                    continue 'new_work;
+                }
+            }
+            shifted.push(work_item);
+        }
+        simplify(shifted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn difference_punches_a_hole() {
+        let whole = RangeSet::from(0..10);
+        let hole = RangeSet::from(3..5);
+        assert_eq!(whole.difference(&hole).ranges(), &[0..3, 5..10]);
+    }
+
+    #[test]
+    fn difference_spanning_holes() {
+        let set = RangeSet::from_iter([0..10, 20..30, 40..50]);
+        let other = RangeSet::from_iter([-5..2, 8..22, 25..26, 29..45, 50..60]);
+        assert_eq!(set.difference(&other).ranges(), &[2..8, 22..25, 26..29, 45..50]);
+    }
+
+    #[test]
+    fn difference_disjoint_and_covered() {
+        let set = RangeSet::from(0..10);
+        assert_eq!(set.difference(&RangeSet::from(20..30)), set);
+        assert!(set.difference(&RangeSet::from(-1..11)).is_empty());
+        assert_eq!(set.difference(&RangeSet::default()), set);
+    }
+
+    #[test]
+    fn intersect_all_edge_cases() {
+        assert!(RangeSet::<i64>::intersect_all(&[]).is_empty());
+        let only = RangeSet::from_iter([1..4, 7..9]);
+        assert_eq!(RangeSet::intersect_all(std::slice::from_ref(&only)), only);
+        let disjoint = [RangeSet::from(0..5), RangeSet::from(5..10)];
+        assert!(RangeSet::intersect_all(&disjoint).is_empty());
+    }
+
+    // The maps from the AoC 2023 day 5 tests. (Each was given there as destination, source, length.)
+    fn map_1() -> PiecewiseShift<i64> {
+        PiecewiseShift::from_iter([(10..20, 40), (20..30, 20), (40..60, -30)])
+    }
+
+    #[test]
+    fn shift_unaltered_range() {
+        let shift = PiecewiseShift::from_iter([(10..40, 89), (99..129, -89)]);
+        assert_eq!(shift.apply_range(0..10), vec![0..10]);
+    }
+
+    #[test]
+    fn shift_ranges() {
+        let shift = map_1();
+        assert_eq!(shift.apply_range(0..1), vec![0..1]);
+        assert_eq!(shift.apply_range(10..11), vec![50..51]);
+        assert_eq!(shift.apply_range(9..11), vec![9..10, 50..51]);
+        assert_eq!(shift.apply_range(9..21), vec![9..10, 40..41, 50..60]);
+    }
+
+    #[test]
+    fn shift_points() {
+        let shift = map_1();
+        assert_eq!(shift.apply(9), 9);
+        assert_eq!(shift.apply(10), 50);
+        assert_eq!(shift.apply(29), 49);
+        assert_eq!(shift.apply(45), 15);
+        assert_eq!(shift.apply(60), 60);
+        assert_eq!(PiecewiseShift::default().apply(7), 7);
+    }
+
+    #[test]
+    fn earlier_shifts_win() {
+        let mut shift = PiecewiseShift::default();
+        shift.push(0..10, 100);
+        shift.push(5..15, 1000);
+        assert_eq!(shift.shifts(), &[(0..10, 100), (5..15, 1000)]);
+        assert_eq!(shift.apply(7), 107);
+        assert_eq!(shift.apply(12), 1012);
+        assert_eq!(shift.apply_range(0..15), vec![100..110, 1010..1015]);
+    }
+}
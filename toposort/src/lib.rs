@@ -0,0 +1,178 @@
+//! # Topological Sorting
+//!
+//! Some puzzles describe a web of values, each computed from others (wires fed by gates, monkeys yelling sums of
+//! what other monkeys yell). Rather than sweeping over the whole web until nothing changes, it's simpler to put
+//! everything in dependency order once, and then evaluate each item exactly once. [toposort] finds that order, or
+//! reports a [Cycle] if there isn't one.
+#![warn(missing_docs)]
+
+use ahash::AHashMap;
+use std::fmt;
+use std::hash::Hash;
+
+/// A set of nodes that depend on each other in a loop, so that no order can put each after its dependencies.
+///
+/// Each node depends on the one after it, and the last depends on the first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycle<N> {
+    nodes: Vec<N>,
+}
+
+impl<N> Cycle<N> {
+    /// The nodes making up the loop.
+    pub fn nodes(&self) -> &[N] {
+        &self.nodes
+    }
+}
+
+impl<N: fmt::Display> fmt::Display for Cycle<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "dependency cycle: ")?;
+        for node in &self.nodes {
+            write!(f, "{node} -> ")?;
+        }
+        write!(f, "{}", self.nodes[0])
+    }
+}
+
+impl<N: fmt::Debug + fmt::Display> std::error::Error for Cycle<N> {}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Mark {
+    InProgress,
+    Done,
+}
+
+/// Put `nodes` in an order where each comes after everything it depends on.
+///
+/// `deps` lists the nodes a node depends on directly. Anything reachable through `deps` is included in the result,
+/// even if it wasn't among `nodes`; each node appears exactly once. If the dependencies loop back on themselves,
+/// the loop is returned as the error.
+///
+/// The search is depth-first, without recursion, so long chains of dependencies are fine.
+///
+/// # Example
+/// ```
+/// use toposort::toposort;
+///
+/// // Each number depends on its proper divisors.
+/// let divisors = |&n: &u32| (1..n).filter(|d| n % d == 0).collect::<Vec<_>>();
+/// let order = toposort([12, 9], divisors).unwrap();
+/// assert_eq!(order, [1, 2, 3, 4, 6, 12, 9]);
+///
+/// // Every odd number depends on the next one up, and every even number on the previous one.
+/// let pairs = |&n: &u32| vec![if n % 2 == 1 { n + 1 } else { n - 1 }];
+/// let cycle = toposort([3], pairs).unwrap_err();
+/// assert_eq!(cycle.nodes(), [3, 4]);
+/// assert_eq!(cycle.to_string(), "dependency cycle: 3 -> 4 -> 3");
+/// ```
+pub fn toposort<N, I, F>(nodes: I, deps: F) -> Result<Vec<N>, Cycle<N>>
+where
+    N: Hash + Eq + Clone,
+    I: IntoIterator<Item = N>,
+    F: Fn(&N) -> Vec<N>,
+{
+    let mut marks = AHashMap::new();
+    let mut order = vec![];
+    for root in nodes {
+        if marks.contains_key(&root) {
+            continue;
+        }
+        marks.insert(root.clone(), Mark::InProgress);
+        // Each stack entry is a node whose dependencies are still being visited, along with the ones not yet
+        // looked at.
+        let pending = deps(&root).into_iter();
+        let mut stack = vec![(root, pending)];
+        while let Some((_, pending)) = stack.last_mut() {
+            match pending.next() {
+                Some(dep) => match marks.get(&dep) {
+                    None => {
+                        marks.insert(dep.clone(), Mark::InProgress);
+                        let pending = deps(&dep).into_iter();
+                        stack.push((dep, pending));
+                    }
+                    Some(Mark::InProgress) => {
+                        // `dep` is further down the stack; everything from there to here is the loop.
+                        let start = stack
+                            .iter()
+                            .position(|(node, _)| *node == dep)
+                            .expect("nodes in progress should be on the stack");
+                        let nodes = stack.drain(start..).map(|(node, _)| node).collect();
+                        return Err(Cycle { nodes });
+                    }
+                    Some(Mark::Done) => {}
+                },
+                None => {
+                    let (node, _) = stack.pop().expect("stack should not be empty");
+                    marks.insert(node.clone(), Mark::Done);
+                    order.push(node);
+                }
+            }
+        }
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph<'a>(edges: &'a [(&'static str, &'static str)]) -> impl Fn(&&'static str) -> Vec<&'static str> + 'a {
+        |node| {
+            edges
+                .iter()
+                .filter_map(|&(from, to)| (from == *node).then_some(to))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn dag() {
+        // shirt before tie, tie and pants before belt, belt before jacket, and so on.
+        let edges = [
+            ("tie", "shirt"),
+            ("belt", "tie"),
+            ("belt", "pants"),
+            ("jacket", "belt"),
+            ("shoes", "pants"),
+            ("shoes", "socks"),
+            ("pants", "undershorts"),
+        ];
+        let nodes = ["jacket", "shoes", "watch", "socks"];
+        let order = toposort(nodes, graph(&edges)).unwrap();
+        assert_eq!(order.len(), 9);
+        let position = |name| order.iter().position(|&node| node == name).unwrap();
+        for (later, earlier) in edges {
+            assert!(
+                position(earlier) < position(later),
+                "{earlier} should come before {later}"
+            );
+        }
+        // Nodes reached only through dependencies are included too, but just once.
+        assert!(order.contains(&"undershorts"));
+        assert_eq!(order.iter().filter(|&&node| node == "socks").count(), 1);
+    }
+
+    #[test]
+    fn cycle() {
+        let edges = [("a", "b"), ("b", "c"), ("c", "d"), ("d", "b")];
+        let cycle = toposort(["a"], graph(&edges)).unwrap_err();
+        assert_eq!(cycle.nodes(), ["b", "c", "d"]);
+        assert_eq!(cycle.to_string(), "dependency cycle: b -> c -> d -> b");
+
+        let cycle = toposort(["x"], graph(&[("x", "x")])).unwrap_err();
+        assert_eq!(cycle.nodes(), ["x"]);
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(toposort(Vec::<u8>::new(), |_| vec![]), Ok(vec![]));
+    }
+
+    #[test]
+    fn long_chain() {
+        let order = toposort([0_u32], |&n| if n < 100_000 { vec![n + 1] } else { vec![] }).unwrap();
+        assert_eq!(order.len(), 100_001);
+        assert_eq!(order[0], 100_000);
+    }
+}